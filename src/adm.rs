@@ -0,0 +1,395 @@
+use crate::channels::ChannelConfig;
+use crate::error::{DecodeError, Result};
+use std::fmt::Write as _;
+
+/// ADM 声道定义（DirectSpeakers）/ ADM channel definition (DirectSpeakers)
+struct SpeakerDef {
+    /// BS.2051 扬声器标签 / BS.2051 speaker label
+    label: &'static str,
+    azimuth: f32,
+    elevation: f32,
+    /// BS.2094 公共定义中的 audioChannelFormat 编号（无则为自定义）/ Common-definition audioChannelFormat number, if any
+    common: Option<u32>,
+}
+
+/// 自定义 ID 起始编号（BS.2076：0x1000 以下保留给公共定义）/ First custom ID number (BS.2076: below 0x1000 is reserved for common definitions)
+const CUSTOM_BASE: u32 = 0x1001;
+
+/// 杜比声道名对应的扬声器；Ls/Rs 在含 Lrs/Rrs 的布局中为侧环绕（M±090），否则为 M±110
+/// Speaker for a Dolby channel name; Ls/Rs are side surrounds (M±090) in layouts with Lrs/Rrs, otherwise M±110
+fn speaker_def(name: &str, has_rear: bool) -> Option<SpeakerDef> {
+    let def = |label, azimuth, elevation, common| SpeakerDef {
+        label,
+        azimuth,
+        elevation,
+        common,
+    };
+    Some(match (name, has_rear) {
+        ("L", _) => def("M+030", 30.0, 0.0, Some(0x01)),
+        ("R", _) => def("M-030", -30.0, 0.0, Some(0x02)),
+        ("C", _) => def("M+000", 0.0, 0.0, Some(0x03)),
+        ("LFE", _) => def("LFE1", 0.0, -30.0, Some(0x04)),
+        ("Ls", false) => def("M+110", 110.0, 0.0, Some(0x05)),
+        ("Rs", false) => def("M-110", -110.0, 0.0, Some(0x06)),
+        ("Ls", true) => def("M+090", 90.0, 0.0, Some(0x0a)),
+        ("Rs", true) => def("M-090", -90.0, 0.0, Some(0x0b)),
+        ("Lrs", _) => def("M+135", 135.0, 0.0, Some(0x1c)),
+        ("Rrs", _) => def("M-135", -135.0, 0.0, Some(0x1d)),
+        ("Lw", _) => def("M+060", 60.0, 0.0, None),
+        ("Rw", _) => def("M-060", -60.0, 0.0, None),
+        ("Ltf", _) => def("U+030", 30.0, 30.0, Some(0x0d)),
+        ("Rtf", _) => def("U-030", -30.0, 30.0, Some(0x0f)),
+        ("Ltm", _) => def("U+090", 90.0, 30.0, Some(0x13)),
+        ("Rtm", _) => def("U-090", -90.0, 30.0, Some(0x14)),
+        ("Ltr", _) => def("U+110", 110.0, 30.0, Some(0x10)),
+        ("Rtr", _) => def("U-110", -110.0, 30.0, Some(0x12)),
+        _ => return None,
+    })
+}
+
+/// 布局对应的公共 audioPackFormat 编号（仅声道完全一致时）/ Common audioPackFormat number for a layout (only when channels match exactly)
+fn common_pack(layout: &str) -> Option<u32> {
+    match layout {
+        "2.0" => Some(0x02),   // 0+2+0
+        "5.1" => Some(0x03),   // 0+5+0
+        "5.1.4" => Some(0x05), // 4+5+0
+        "7.1" => Some(0x0f),   // 0+7+0
+        "7.1.4" => Some(0x17), // 4+7+0
+        _ => None,
+    }
+}
+
+/// 单个声道的 ADM 引用 / ADM references for one channel
+struct TrackRefs {
+    name: String,
+    def: SpeakerDef,
+    /// audioChannelFormat / audioStreamFormat / audioTrackFormat 共用的编号 / Number shared by channel/stream/track formats
+    id: u32,
+}
+
+impl TrackRefs {
+    fn channel_id(&self) -> String {
+        format!("AC_0001{:04x}", self.id)
+    }
+    fn stream_id(&self) -> String {
+        format!("AS_0001{:04x}", self.id)
+    }
+    fn track_format_id(&self) -> String {
+        format!("AT_0001{:04x}_01", self.id)
+    }
+    fn is_custom(&self) -> bool {
+        self.def.common.is_none()
+    }
+}
+
+/// 由声道配置生成的 ADM 描述 / ADM description generated from a channel configuration
+pub struct AdmLayout {
+    name: String,
+    pack_id: String,
+    custom_pack: bool,
+    tracks: Vec<TrackRefs>,
+}
+
+impl AdmLayout {
+    pub fn from_config(config: &ChannelConfig) -> Result<Self> {
+        if config.names.is_empty() {
+            return Err(DecodeError::MergeFailed(
+                "ADM 需要已知声道布局/ADM export needs a known channel layout".to_string(),
+            ));
+        }
+        let has_rear = config.names.iter().any(|n| n == "Lrs" || n == "Rrs");
+        let mut next_custom = CUSTOM_BASE;
+        let mut tracks = Vec::with_capacity(config.names.len());
+        for name in &config.names {
+            let def = speaker_def(name, has_rear).ok_or_else(|| {
+                DecodeError::MergeFailed(format!(
+                    "ADM 不支持声道/Channel not supported for ADM: {name}"
+                ))
+            })?;
+            let id = def.common.unwrap_or_else(|| {
+                next_custom += 1;
+                next_custom - 1
+            });
+            tracks.push(TrackRefs {
+                name: name.clone(),
+                def,
+                id,
+            });
+        }
+
+        let common = common_pack(&config.name);
+        Ok(Self {
+            name: config.name.clone(),
+            pack_id: format!("AP_0001{:04x}", common.unwrap_or(CUSTOM_BASE)),
+            custom_pack: common.is_none(),
+            tracks,
+        })
+    }
+
+    /// `chna` 块：每个音轨一条，引用 audioTrackFormat 与 audioPackFormat
+    /// `chna` chunk: one entry per track referencing its audioTrackFormat and audioPackFormat
+    pub fn chna(&self) -> Vec<u8> {
+        let count = u16::try_from(self.tracks.len()).expect("tracks <= u16");
+        let mut out = Vec::with_capacity(4 + self.tracks.len() * 40);
+        out.extend_from_slice(&count.to_le_bytes()); // numTracks
+        out.extend_from_slice(&count.to_le_bytes()); // numUIDs
+        for (idx, track) in self.tracks.iter().enumerate() {
+            out.extend_from_slice(&(idx as u16 + 1).to_le_bytes());
+            out.extend_from_slice(track_uid(idx).as_bytes());
+            out.extend_from_slice(track.track_format_id().as_bytes());
+            out.extend_from_slice(self.pack_id.as_bytes());
+            out.push(0); // pad
+        }
+        out
+    }
+
+    /// `axml` 块：BS.2076 audioFormatExtended；公共定义只引用，自定义元素完整写出
+    /// `axml` chunk: BS.2076 audioFormatExtended; common definitions are referenced, custom elements written in full
+    pub fn axml(&self, sample_rate: u32) -> String {
+        let mut x = String::new();
+        let _ = writeln!(x, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            x,
+            r#"<ebuCoreMain xmlns="urn:ebu:metadata-schema:ebucore" xmlns:dc="http://purl.org/dc/elements/1.1/">"#
+        );
+        let _ = writeln!(x, "  <coreMetadata>\n    <format>");
+        let _ = writeln!(
+            x,
+            r#"      <audioFormatExtended version="ITU-R_BS.2076-2">"#
+        );
+
+        let _ = writeln!(
+            x,
+            r#"        <audioProgramme audioProgrammeID="APR_1001" audioProgrammeName="{}">"#,
+            self.name
+        );
+        let _ = writeln!(
+            x,
+            "          <audioContentIDRef>ACO_1001</audioContentIDRef>"
+        );
+        let _ = writeln!(x, "        </audioProgramme>");
+
+        let _ = writeln!(
+            x,
+            r#"        <audioContent audioContentID="ACO_1001" audioContentName="{} bed">"#,
+            self.name
+        );
+        let _ = writeln!(x, "          <audioObjectIDRef>AO_1001</audioObjectIDRef>");
+        let _ = writeln!(x, "        </audioContent>");
+
+        let _ = writeln!(
+            x,
+            r#"        <audioObject audioObjectID="AO_1001" audioObjectName="{} bed">"#,
+            self.name
+        );
+        let _ = writeln!(
+            x,
+            "          <audioPackFormatIDRef>{}</audioPackFormatIDRef>",
+            self.pack_id
+        );
+        for idx in 0..self.tracks.len() {
+            let _ = writeln!(
+                x,
+                "          <audioTrackUIDRef>{}</audioTrackUIDRef>",
+                track_uid(idx)
+            );
+        }
+        let _ = writeln!(x, "        </audioObject>");
+
+        for (idx, track) in self.tracks.iter().enumerate() {
+            let _ = writeln!(
+                x,
+                r#"        <audioTrackUID UID="{}" sampleRate="{sample_rate}" bitDepth="32">"#,
+                track_uid(idx)
+            );
+            let _ = writeln!(
+                x,
+                "          <audioTrackFormatIDRef>{}</audioTrackFormatIDRef>",
+                track.track_format_id()
+            );
+            let _ = writeln!(
+                x,
+                "          <audioPackFormatIDRef>{}</audioPackFormatIDRef>",
+                self.pack_id
+            );
+            let _ = writeln!(x, "        </audioTrackUID>");
+        }
+
+        if self.custom_pack {
+            let _ = writeln!(
+                x,
+                r#"        <audioPackFormat audioPackFormatID="{}" audioPackFormatName="{}" typeLabel="0001" typeDefinition="DirectSpeakers">"#,
+                self.pack_id, self.name
+            );
+            for track in &self.tracks {
+                let _ = writeln!(
+                    x,
+                    "          <audioChannelFormatIDRef>{}</audioChannelFormatIDRef>",
+                    track.channel_id()
+                );
+            }
+            let _ = writeln!(x, "        </audioPackFormat>");
+        }
+
+        for track in self.tracks.iter().filter(|t| t.is_custom()) {
+            let _ = writeln!(
+                x,
+                r#"        <audioChannelFormat audioChannelFormatID="{}" audioChannelFormatName="{}" typeLabel="0001" typeDefinition="DirectSpeakers">"#,
+                track.channel_id(),
+                track.name
+            );
+            let _ = writeln!(
+                x,
+                r#"          <audioBlockFormat audioBlockFormatID="AB_0001{:04x}_00000001">"#,
+                track.id
+            );
+            let _ = writeln!(
+                x,
+                "            <speakerLabel>{}</speakerLabel>",
+                track.def.label
+            );
+            let _ = writeln!(
+                x,
+                r#"            <position coordinate="azimuth">{:.1}</position>"#,
+                track.def.azimuth
+            );
+            let _ = writeln!(
+                x,
+                r#"            <position coordinate="elevation">{:.1}</position>"#,
+                track.def.elevation
+            );
+            let _ = writeln!(
+                x,
+                r#"            <position coordinate="distance">1.0</position>"#
+            );
+            let _ = writeln!(x, "          </audioBlockFormat>");
+            let _ = writeln!(x, "        </audioChannelFormat>");
+
+            let _ = writeln!(
+                x,
+                r#"        <audioStreamFormat audioStreamFormatID="{}" audioStreamFormatName="PCM_{}" formatLabel="0001" formatDefinition="PCM">"#,
+                track.stream_id(),
+                track.name
+            );
+            let _ = writeln!(
+                x,
+                "          <audioChannelFormatIDRef>{}</audioChannelFormatIDRef>",
+                track.channel_id()
+            );
+            let _ = writeln!(
+                x,
+                "          <audioTrackFormatIDRef>{}</audioTrackFormatIDRef>",
+                track.track_format_id()
+            );
+            let _ = writeln!(x, "        </audioStreamFormat>");
+
+            let _ = writeln!(
+                x,
+                r#"        <audioTrackFormat audioTrackFormatID="{}" audioTrackFormatName="PCM_{}" formatLabel="0001" formatDefinition="PCM">"#,
+                track.track_format_id(),
+                track.name
+            );
+            let _ = writeln!(
+                x,
+                "          <audioStreamFormatIDRef>{}</audioStreamFormatIDRef>",
+                track.stream_id()
+            );
+            let _ = writeln!(x, "        </audioTrackFormat>");
+        }
+
+        let _ = writeln!(x, "      </audioFormatExtended>");
+        let _ = writeln!(x, "    </format>\n  </coreMetadata>");
+        let _ = writeln!(x, "</ebuCoreMain>");
+        x
+    }
+}
+
+/// 音轨 UID（1 起始）/ Track UID (1-based)
+fn track_uid(idx: usize) -> String {
+    format!("ATU_{:08x}", idx + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::get_config;
+
+    // chna：每条 40 字节，引用公共音轨格式与包格式 / chna: 40 bytes per entry, referencing common track and pack formats
+    #[test]
+    fn chna_entries_reference_common_definitions_for_51() {
+        let layout = AdmLayout::from_config(&get_config("5.1").unwrap()).unwrap();
+        let chna = layout.chna();
+        assert_eq!(chna.len(), 4 + 6 * 40);
+        assert_eq!(u16::from_le_bytes([chna[0], chna[1]]), 6);
+        assert_eq!(u16::from_le_bytes([chna[2], chna[3]]), 6);
+
+        let entry = |i: usize| &chna[4 + i * 40..4 + (i + 1) * 40];
+        assert_eq!(u16::from_le_bytes([entry(0)[0], entry(0)[1]]), 1);
+        assert_eq!(&entry(0)[2..14], b"ATU_00000001");
+        assert_eq!(&entry(0)[14..28], b"AT_00010001_01");
+        assert_eq!(&entry(0)[28..39], b"AP_00010003");
+        assert_eq!(&entry(4)[14..28], b"AT_00010005_01"); // Ls = M+110
+    }
+
+    // 5.1 完全使用公共定义，axml 不写自定义元素 / 5.1 uses common definitions only; no custom elements in axml
+    #[test]
+    fn axml_for_51_only_references_common_ids() {
+        let layout = AdmLayout::from_config(&get_config("5.1").unwrap()).unwrap();
+        let axml = layout.axml(48000);
+        assert!(axml.contains("<audioPackFormatIDRef>AP_00010003</audioPackFormatIDRef>"));
+        assert!(!axml.contains("<audioPackFormat "));
+        assert!(!axml.contains("<audioChannelFormat "));
+        assert_eq!(axml.matches("<audioTrackUID ").count(), 6);
+        assert!(axml.contains(r#"sampleRate="48000""#));
+    }
+
+    // 7.1.4：使用 4+7+0 公共包，Ls/Rs 为侧环绕（M±090），Lrs/Rrs 为 M±135
+    // 7.1.4: uses the common 4+7+0 pack, with Ls/Rs as side surrounds (M±090) and Lrs/Rrs as M±135
+    #[test]
+    fn chna_for_714_references_common_pack_and_channels() {
+        let layout = AdmLayout::from_config(&get_config("7.1.4").unwrap()).unwrap();
+        let chna = layout.chna();
+        let entry = |i: usize| &chna[4 + i * 40..4 + (i + 1) * 40];
+        let expected = [
+            "01", "02", "03", "04", "0a", "0b", "1c", "1d", "0d", "0f", "10", "12",
+        ];
+        for (i, id) in expected.iter().enumerate() {
+            assert_eq!(&entry(i)[14..28], format!("AT_000100{id}_01").as_bytes());
+            assert_eq!(&entry(i)[28..39], b"AP_00010017");
+        }
+        let axml = layout.axml(48000);
+        assert!(axml.contains("<audioPackFormatIDRef>AP_00010017</audioPackFormatIDRef>"));
+        assert!(!axml.contains("<audioPackFormat "));
+        assert!(!axml.contains("<audioChannelFormat "));
+    }
+
+    // 9.1.4：宽声道没有公共定义，写出自定义包与声道
+    // 9.1.4: wides have no common definition, so a custom pack and channels are written
+    #[test]
+    fn axml_for_914_defines_custom_pack_and_wide_channels() {
+        let layout = AdmLayout::from_config(&get_config("9.1.4").unwrap()).unwrap();
+        let axml = layout.axml(48000);
+        assert!(axml.contains(r#"audioPackFormatID="AP_00011001""#));
+        assert!(axml.contains("<audioChannelFormatIDRef>AC_0001001c</audioChannelFormatIDRef>"));
+        assert!(axml.contains(r#"audioChannelFormatID="AC_00011001" audioChannelFormatName="Lw""#));
+        assert!(axml.contains(r#"audioChannelFormatID="AC_00011002" audioChannelFormatName="Rw""#));
+        assert!(axml.contains("<speakerLabel>M+060</speakerLabel>"));
+        assert!(axml.contains(r#"audioTrackFormatID="AT_00011001_01""#));
+
+        // chna 中的每个音轨格式都能在公共定义或 axml 中找到 / every chna track format is common or defined in axml
+        let chna = layout.chna();
+        for i in 0..14 {
+            let at = std::str::from_utf8(&chna[4 + i * 40 + 14..4 + i * 40 + 28]).unwrap();
+            assert!(
+                at.starts_with("AT_000100")
+                    || axml.contains(&format!(r#"audioTrackFormatID="{at}""#))
+            );
+        }
+    }
+
+    // 未知布局（auto）无法导出 ADM / Unknown layouts (auto) cannot be exported as ADM
+    #[test]
+    fn auto_layout_is_rejected() {
+        assert!(AdmLayout::from_config(&get_config("auto").unwrap()).is_err());
+    }
+}
@@ -93,6 +93,65 @@ const CONFIGS: &[ChannelDef] = &[
     },
 ];
 
+/// Microsoft `dwChannelMask` 扬声器位 / Microsoft `dwChannelMask` speaker bits
+const SPEAKER_FRONT_LEFT: u32 = 0x1;
+const SPEAKER_FRONT_RIGHT: u32 = 0x2;
+const SPEAKER_FRONT_CENTER: u32 = 0x4;
+const SPEAKER_LOW_FREQUENCY: u32 = 0x8;
+const SPEAKER_BACK_LEFT: u32 = 0x10;
+const SPEAKER_BACK_RIGHT: u32 = 0x20;
+const SPEAKER_SIDE_LEFT: u32 = 0x200;
+const SPEAKER_SIDE_RIGHT: u32 = 0x400;
+const SPEAKER_TOP_FRONT_LEFT: u32 = 0x1000;
+const SPEAKER_TOP_FRONT_RIGHT: u32 = 0x4000;
+const SPEAKER_TOP_BACK_LEFT: u32 = 0x8000;
+const SPEAKER_TOP_BACK_RIGHT: u32 = 0x20000;
+
+/// 杜比声道名对应的扬声器位；宽声道（Lw/Rw）与顶中（Ltm/Rtm）没有标准位
+/// Speaker bit for a Dolby channel name; wides (Lw/Rw) and top-middle (Ltm/Rtm) have no standard bit
+fn speaker_bit(name: &str) -> Option<u32> {
+    match name {
+        "L" => Some(SPEAKER_FRONT_LEFT),
+        "R" => Some(SPEAKER_FRONT_RIGHT),
+        "C" => Some(SPEAKER_FRONT_CENTER),
+        "LFE" => Some(SPEAKER_LOW_FREQUENCY),
+        "Ls" => Some(SPEAKER_SIDE_LEFT),
+        "Rs" => Some(SPEAKER_SIDE_RIGHT),
+        "Lrs" => Some(SPEAKER_BACK_LEFT),
+        "Rrs" => Some(SPEAKER_BACK_RIGHT),
+        "Ltf" => Some(SPEAKER_TOP_FRONT_LEFT),
+        "Rtf" => Some(SPEAKER_TOP_FRONT_RIGHT),
+        "Ltr" => Some(SPEAKER_TOP_BACK_LEFT),
+        "Rtr" => Some(SPEAKER_TOP_BACK_RIGHT),
+        _ => None,
+    }
+}
+
+/// 由声道名推导 WAVE_FORMAT_EXTENSIBLE 的 `dwChannelMask`
+/// Derive the WAVE_FORMAT_EXTENSIBLE `dwChannelMask` from channel names
+///
+/// 读取方按掩码位从低到高把扬声器分给前面的声道，未分配的声道必须排在最后，因此采用前缀规则：
+/// 从第一个声道起依次取位，遇到没有标准位的声道（宽声道、顶中）或位序倒退（如 7.1 中 Ls→SIDE
+/// 之后的 Lrs→BACK）即停止，其后的声道均视为未分配（仅由 ICOM 备注描述）。
+/// Readers hand the mask bits to the leading channels in ascending bit order and unassigned channels must come
+/// last, so a prefix rule applies: bits are taken from the first channel onwards and stop at the first channel
+/// with no standard bit (wides, top-middle) or whose bit would go backwards (e.g. Lrs→BACK after Ls→SIDE in
+/// 7.1); all later channels are left unassigned (described only by the ICOM comment).
+pub fn channel_mask(names: &[String]) -> u32 {
+    let mut mask = 0u32;
+    let mut last = 0u32;
+    for name in names {
+        match speaker_bit(name) {
+            Some(bit) if bit > last => {
+                mask |= bit;
+                last = bit;
+            }
+            _ => break,
+        }
+    }
+    mask
+}
+
 pub fn get_config(config_name: &str) -> Result<ChannelConfig> {
     // 处理特殊的"auto"配置 / Handle special "auto" configuration
     // 在此模式下，解码器将不会指定 out-ch-config，使用文件的原生声道配置
@@ -125,6 +184,20 @@ pub fn get_config(config_name: &str) -> Result<ChannelConfig> {
     )))
 }
 
+/// 覆盖给定声道的最小标准布局（声道数相同时取较小的 id）
+/// Smallest standard layout covering the given channels (the lower id wins on equal channel counts)
+pub fn covering_config(names: &[&str]) -> Option<ChannelConfig> {
+    CONFIGS
+        .iter()
+        .filter(|d| names.iter().all(|n| d.names.contains(n)))
+        .min_by_key(|d| (d.names.len(), d.id))
+        .map(|def| ChannelConfig {
+            name: def.name.to_string(),
+            id: def.id,
+            names: def.names.iter().map(|s| (*s).to_string()).collect(),
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,6 +232,21 @@ mod tests {
         }
     }
 
+    // 最小覆盖布局：顶部前置选 5.1.4，宽声道选 9.1 / Smallest covering layout: top front picks 5.1.4, wides pick 9.1
+    #[test]
+    fn covering_config_picks_smallest_layout() {
+        let name = |names: &[&str]| covering_config(names).map(|c| c.name);
+        assert_eq!(name(&["L", "R"]).as_deref(), Some("2.0"));
+        assert_eq!(name(&["C"]).as_deref(), Some("3.1"));
+        assert_eq!(
+            name(&["L", "R", "C", "LFE", "Ls", "Rs", "Lrs", "Rrs"]).as_deref(),
+            Some("7.1")
+        );
+        assert_eq!(name(&["L", "R", "Ltf", "Rtf"]).as_deref(), Some("5.1.4"));
+        assert_eq!(name(&["L", "Lw", "Ltm"]).as_deref(), Some("9.1.2"));
+        assert!(covering_config(&["Cs"]).is_none());
+    }
+
     // 未知配置返回 InvalidChannelConfig / Unknown config returns InvalidChannelConfig
     #[test]
     fn get_config_unknown_returns_error() {
@@ -166,6 +254,35 @@ mod tests {
         assert!(matches!(err, DecodeError::InvalidChannelConfig(_)));
     }
 
+    // 读取方按位序把掩码分给前面的声道：每个被分配的声道都得到自己的扬声器位
+    // Readers hand the mask bits to the leading channels in order: every assigned channel gets its own speaker
+    #[test]
+    fn channel_mask_assigns_each_channel_its_speaker() {
+        // 按读取方的规则得到的 声道 → 扬声器位 / Channel → speaker bit as a reader assigns them
+        let assigned = |names: &[String]| -> Vec<(String, u32)> {
+            let mask = channel_mask(names);
+            let bits = (0..32).map(|i| 1u32 << i).filter(|bit| mask & bit != 0);
+            names.iter().cloned().zip(bits).collect()
+        };
+        for def in CONFIGS {
+            let names: Vec<String> = def.names.iter().map(|s| (*s).to_string()).collect();
+            for (name, bit) in assigned(&names) {
+                assert_eq!(speaker_bit(&name), Some(bit), "{}: {name}", def.name);
+            }
+        }
+
+        let count = |name: &str| assigned(&get_config(name).unwrap().names).len();
+        assert_eq!(count("2.0"), 2);
+        assert_eq!(count("5.1"), 6);
+        assert_eq!(count("5.1.4"), 10);
+        // 7.1：Lrs 的 BACK 位低于 SIDE，之后均未分配 / Lrs BACK bit is below SIDE, the rest are unassigned
+        assert_eq!(count("7.1"), 6);
+        // Ltm 与 Lw 没有标准位 / Ltm and Lw have no standard bit
+        assert_eq!(count("5.1.2"), 6);
+        assert_eq!(count("9.1.6"), 6);
+        assert_eq!(channel_mask(&[]), 0);
+    }
+
     // 健康检查：名称与 id 均无重复 / Health check: no duplicate names or ids
     #[test]
     fn config_table_has_no_duplicate_names_or_ids() {
@@ -1,4 +1,5 @@
-use clap::{Args as ClapArgs, Parser};
+use crate::decoder::PipelineMode;
+use clap::{Args as ClapArgs, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -7,9 +8,12 @@ use std::path::PathBuf;
 #[command(author = "Sakuzy")]
 #[command(version)]
 pub struct Args {
-    /// 输入文件（E-AC3/TrueHD 格式；懒人模式可省略）/Input file (E-AC3/TrueHD format; optional in lazy mode)
-    #[arg(short, long)]
-    pub input: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// 输入文件、目录或 glob 模式，可给出多个（E-AC3/TrueHD 码流或 MKV/MP4/M2TS 容器；懒人模式可省略）/Input files, directories or glob patterns, several allowed (E-AC3/TrueHD streams or MKV/MP4/M2TS containers; optional in lazy mode)
+    #[arg(short, long, num_args = 1..)]
+    pub input: Vec<PathBuf>,
 
     /// 输出基础路径（可选，默认为输入目录）/Output base path (optional, defaults to input directory)
     #[arg(short, long)]
@@ -20,9 +24,17 @@ pub struct Args {
     pub channels: String,
 
     /// 输入音频格式（如果未指定则自动检测）/Input audio format (auto-detect if not specified)
-    #[arg(short, long, value_parser = ["eac3", "truehd"])]
+    #[arg(short, long, value_parser = ["ac3", "eac3", "eac3-joc", "truehd"])]
     pub format: Option<String>,
 
+    /// 容器输入（MKV/MP4/M2TS）时选择的音轨号（MKV 轨道号、MP4 track ID 或 TS PID；默认优先 TrueHD）/Audio track to use for container inputs (MKV track number, MP4 track ID or TS PID; defaults to the first TrueHD, then E-AC3 track)
+    #[arg(long)]
+    pub track: Option<u32>,
+
+    /// 寻找同步字的扫描窗口（字节，默认 65536，覆盖环境变量 MCAT_SCAN_WINDOW）/Sync-word scan window in bytes (default 65536, overrides env MCAT_SCAN_WINDOW)
+    #[arg(long)]
+    pub scan_window: Option<usize>,
+
     /// 输出文件名不带声道编号/Don't use numbers in output channel filenames
     #[arg(long)]
     pub no_numbers: bool,
@@ -31,6 +43,10 @@ pub struct Args {
     #[command(flatten)]
     pub flags: FlagSet,
 
+    /// FLAC 压缩等级（0 最快，8 最小）/FLAC compression level (0 fastest, 8 smallest)
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(0..=8))]
+    pub flac_level: u8,
+
     /// 指定 dolby-tools 基目录（包含 gstreamer/bin 与 gst-plugins）/Specify dolby-tools base directory (contains gstreamer/bin and gst-plugins)
     #[arg(long)]
     pub dolby_tools: Option<PathBuf>,
@@ -38,6 +54,37 @@ pub struct Args {
     /// 并行作业数（覆盖默认与环境变量）/Number of parallel jobs (overrides default and env)
     #[arg(short = 'j', long)]
     pub jobs: Option<usize>,
+
+    /// 单个 gst-launch 进程（每声道或整条管道）的超时秒数，超时即终止（0 为不限，覆盖环境变量 MCAT_GST_TIMEOUT）/Timeout in seconds for each gst-launch process (per channel or whole pipeline); the process is killed when it expires (0 means none, overrides env MCAT_GST_TIMEOUT)
+    #[arg(long)]
+    pub timeout: Option<u64>,
+
+    /// 超时后的重试次数，带指数退避（默认 0，覆盖环境变量 MCAT_GST_RETRIES）/Retries after a timeout, with exponential backoff (default 0, overrides env MCAT_GST_RETRIES)
+    #[arg(long)]
+    pub retries: Option<u32>,
+
+    /// 解码管道模式：per-channel 每声道一个进程；combined 单管道一次解码全部声道（验证中）；stream 交错 PCM 直接写出合并 WAV/Pipeline mode: per-channel runs one process per channel; combined decodes all channels in one pipeline (under validation); stream writes the merged WAV straight from interleaved PCM
+    #[arg(long, value_enum, default_value_t = PipelineMode::PerChannel)]
+    pub pipeline: PipelineMode,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 读取码流头信息而不解码（采样率、码率、声道、子流、全景声等）/Report bitstream info without decoding (rate, bitrate, channels, substreams, Atmos, ...)
+    Probe {
+        /// 输入文件/Input file
+        input: PathBuf,
+
+        /// 输入音频格式（如果未指定则自动检测）/Input audio format (auto-detect if not specified)
+        #[arg(short, long, value_parser = ["ac3", "eac3", "eac3-joc", "truehd"])]
+        format: Option<String>,
+
+        /// 以 JSON 输出/Output as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// 检查 Dolby/GStreamer 安装（工具位置、插件元素、插件扫描器、flac）/Validate the Dolby/GStreamer installation (tool location, plugin elements, plugin scanner, flac)
+    Doctor,
 }
 
 #[allow(clippy::struct_excessive_bools)]
@@ -59,11 +106,47 @@ pub struct FlagSet {
     #[arg(long)]
     pub lazy: bool,
 
+    /// 扫描输入目录（及懒人模式的当前目录）时进入子目录/Descend into subdirectories when scanning input directories (and the current directory in lazy mode)
+    #[arg(short, long)]
+    pub recursive: bool,
+
     /// 转码合并文件为 FLAC（24-bit，仅支持 ≤8 声道）/Convert merged file to FLAC (24-bit, only for ≤8 channels)
     #[arg(long)]
     pub flac: bool,
 
-    /// 转码为 FLAC 后保留原始 WAV 文件/Keep original WAV file after converting to FLAC
+    /// 转码合并文件为 WavPack（32-bit Float 无损，支持 16 声道，需要 wavpack 命令）/Convert merged file to WavPack (lossless 32-bit float, up to 16 channels, needs the wavpack command)
+    #[arg(long)]
+    pub wavpack: bool,
+
+    /// 将合并文件拆分为床/宽/顶部 FLAC 分轨并写出 JSON 清单（适用于 >8 声道）/Split the merged file into bed/wides/heights FLAC stems with a JSON manifest (for >8 channels)
+    #[arg(long)]
+    pub stems: bool,
+
+    /// 转码为 FLAC/WavPack/分轨后保留原始 WAV 文件/Keep original WAV file after converting to FLAC/WavPack/stems
     #[arg(long)]
     pub keep_wav: bool,
+
+    /// 将合并文件写为带 ADM 元数据（axml + chna）的 BW64，需要已知声道布局/Write the merged file as BW64 with ADM metadata (axml + chna); needs a known channel layout
+    #[arg(long)]
+    pub adm: bool,
+
+    /// 不显示进度（默认：终端上显示进度条，否则定期输出百分比行）/Hide progress (default: a bar on a terminal, periodic percentage lines otherwise)
+    #[arg(long)]
+    pub no_progress: bool,
+
+    /// 批处理时某个文件失败后继续处理其余文件，结束时输出摘要；有失败时以非零码退出/Keep processing the remaining files when one fails, print a summary at the end and exit non-zero if any failed
+    #[arg(long)]
+    pub keep_going: bool,
+
+    /// 跳过输出已全部存在的输入/Skip inputs whose outputs all exist already
+    #[arg(long)]
+    pub skip_existing: bool,
+
+    /// 续跑：在输出目录的 .mcat-state.json 中记录每个输入已完成的阶段（按大小与修改时间识别），重跑时从中断处继续/Resume: record each input's completed stages in .mcat-state.json in the output directory (keyed by size and mtime) and pick up where a rerun stopped
+    #[arg(long)]
+    pub resume: bool,
+
+    /// 在 stdout 输出逐行 JSON 事件（文本信息改写到 stderr）/Emit newline-delimited JSON events on stdout (text messages move to stderr)
+    #[arg(long)]
+    pub json: bool,
 }
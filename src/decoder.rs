@@ -1,10 +1,53 @@
 use crate::channels::ChannelConfig;
+use crate::demux::InputSource;
 use crate::error::{DecodeError, Result};
+use crate::events::{self, say, Event};
 use crate::format::AudioFormat;
+use crate::interrupt;
+use crate::progress;
+use crate::tools;
 use rayon::{prelude::*, ThreadPoolBuilder};
+use std::collections::VecDeque;
 use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// 子进程环境：插件扫描器与库搜索路径 / Child environment: plugin scanner and library search dirs
+pub(crate) struct ChildEnv {
+    pub(crate) gst_scanner: Option<PathBuf>,
+    pub(crate) library_dirs: Vec<PathBuf>,
+    /// 置位后终止子进程且不再启动新进程 / Once set, children are killed and no new ones start
+    pub(crate) cancel: &'static AtomicBool,
+}
+
+impl ChildEnv {
+    pub(crate) fn new(gst_launch: &Path, gst_plugins: &Path) -> Self {
+        Self {
+            gst_scanner: find_gst_scanner(gst_launch),
+            library_dirs: tools::library_dirs(gst_launch, gst_plugins),
+            cancel: interrupt::flag(),
+        }
+    }
+
+    /// 为子进程设置库路径与插件扫描器 / Set the library path and plugin scanner on a child command
+    pub(crate) fn apply(&self, cmd: &mut Command) {
+        // 库路径来自已定位的工具目录 / Library path comes from the located tool directory
+        let existing = env::var(tools::LIBRARY_PATH_VAR).ok();
+        if let Some(path) = tools::join_library_path(&self.library_dirs, existing.as_deref()) {
+            cmd.env(tools::LIBRARY_PATH_VAR, path);
+        }
+
+        if let Some(scanner) = &self.gst_scanner {
+            cmd.env("GST_PLUGIN_SCANNER", scanner);
+        }
+    }
+}
 
 fn find_gst_scanner(gst_launch: &Path) -> Option<PathBuf> {
     let bin_dir = gst_launch.parent()?;
@@ -18,7 +61,11 @@ fn find_gst_scanner(gst_launch: &Path) -> Option<PathBuf> {
 
 /// 生成单声道输出文件名后缀（带/不带编号）/ Build mono output filename suffix (with/without index)
 /// `channel_index` 为 0 起始；带编号时显示为两位数的 1 起始序号 / `channel_index` is 0-based; numbered form shows a 1-based two-digit index
-fn channel_output_suffix(channel_name: &str, channel_index: usize, no_numbers: bool) -> String {
+pub(crate) fn channel_output_suffix(
+    channel_name: &str,
+    channel_index: usize,
+    no_numbers: bool,
+) -> String {
     if no_numbers {
         format!(".{channel_name}.wav")
     } else {
@@ -26,9 +73,20 @@ fn channel_output_suffix(channel_name: &str, channel_index: usize, no_numbers: b
     }
 }
 
+/// 解码管道模式 / Decode pipeline mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PipelineMode {
+    /// 每个声道一个 gst-launch 进程（回退方案）/ One gst-launch process per channel (fallback)
+    PerChannel,
+    /// 单个管道一次解码，deinterleave 的每个输出各写一个文件 / One pipeline decodes once, each deinterleave pad writes its own file
+    Combined,
+    /// 交错 PCM 经 stdout 流入合并器，直接写出多声道 WAV（见 `decode_streamed`）/ Interleaved PCM streams over stdout into the merger, writing the multichannel WAV directly (see `decode_streamed`)
+    Stream,
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn decode(
-    input_file: &Path,
+    input: &InputSource,
     output_base: Option<&PathBuf>,
     gst_launch: &Path,
     gst_plugins: &Path,
@@ -36,11 +94,18 @@ pub fn decode(
     channel_config: &ChannelConfig,
     single: bool,
     no_numbers: bool,
+    pipeline: PipelineMode,
 ) -> Result<Vec<PathBuf>> {
-    // 处理 "auto" 模式：先解码第一个声道来获取实际声道数 / Handle "auto" mode: first decode one channel to get actual count
+    // 处理 "auto" 模式：仅在无法从码流头确定布局时到达（如容器输入），逐声道试探实际声道数
+    // Handle "auto" mode: only reached when the headers give no layout (e.g. container inputs); probe the channel count by trial decoding
     if channel_config.id == u32::MAX {
+        if pipeline == PipelineMode::Combined {
+            say!(
+                "自动模式不支持单管道解码，改用逐声道模式/Auto mode doesn't support combined pipeline, using per-channel mode"
+            );
+        }
         return decode_auto(
-            input_file,
+            input,
             output_base,
             gst_launch,
             gst_plugins,
@@ -52,13 +117,16 @@ pub fn decode(
 
     let mut decoded_files = Vec::new();
     let mut handles = Vec::new();
-    let gst_scanner = find_gst_scanner(gst_launch);
+    let child_env = ChildEnv::new(gst_launch, gst_plugins);
+    let policy = RunPolicy::from_env();
+    let report = progress::is_active();
+    progress::set_slots(channel_config.names.len());
 
     for (channel_id, channel_name) in channel_config.names.iter().enumerate() {
         let suffix = channel_output_suffix(channel_name, channel_id, no_numbers);
 
         let out_path = output_base.map_or_else(
-            || input_file.with_extension(&suffix[1..]),
+            || input.path.with_extension(&suffix[1..]),
             |base| base.with_extension(&suffix[1..]),
         );
 
@@ -66,35 +134,76 @@ pub fn decode(
         if out_path.exists() {
             let _ = std::fs::remove_file(&out_path);
         }
+        interrupt::track_output(out_path.clone());
         decoded_files.push(out_path.clone());
 
+        if pipeline == PipelineMode::Combined {
+            continue;
+        }
+
         let command = build_gstreamer_command(
-            input_file,
+            input,
             &out_path,
             channel_id,
             channel_config.id,
             gst_launch,
             gst_plugins,
             audio_format,
+            report,
         );
 
         if single {
-            println!(
-                "正在解码声道 {}：{}/Decoding channel {}：{}",
-                channel_id + 1,
+            run_channel(
+                &command,
+                &child_env,
+                input,
+                &out_path,
+                policy,
+                channel_id,
                 channel_name,
-                channel_id + 1,
-                channel_name
-            );
-            execute_command(&command, gst_scanner.as_deref())?;
+            )?;
         } else {
-            handles.push((channel_id + 1, channel_name.clone(), command));
+            handles.push((channel_id, channel_name.clone(), out_path, command));
+        }
+    }
+
+    // 单管道：一个进程解码全部声道 / Combined: one process decodes every channel
+    if pipeline == PipelineMode::Combined {
+        say!(
+            "单管道解码 {} 个声道/Decoding {} channels in one pipeline",
+            decoded_files.len(),
+            decoded_files.len()
+        );
+        let command = build_gstreamer_command_combined(
+            input,
+            &decoded_files,
+            channel_config.id,
+            gst_launch,
+            gst_plugins,
+            audio_format,
+            report,
+        );
+        progress::set_slots(1);
+        let channels = channel_config.names.iter().zip(&decoded_files);
+        for (channel_id, (name, out_path)) in channels.clone().enumerate() {
+            events::emit(&Event::DecodeStart {
+                input: &input.path,
+                channel: channel_id + 1,
+                name,
+                output: out_path,
+            });
+        }
+        let result = run_with_policy(&command, &child_env, input, &decoded_files, policy, 0);
+        for (channel_id, (name, out_path)) in channels.enumerate() {
+            emit_channel_result(input, channel_id, name, out_path, &result);
         }
+        result?;
+        return Ok(decoded_files);
     }
 
     // Execute parallel decoding (rayon) / 执行并行解码（rayon）
     if !single && !handles.is_empty() {
-        println!(
+        say!(
             "并行解码 {} 个声道/Decoding {} channels in parallel",
             handles.len(),
             handles.len()
@@ -120,9 +229,16 @@ pub fn decode(
         pool.install(|| -> Result<()> {
             handles
                 .par_iter()
-                .map(|(id, name, command)| {
-                    println!("正在解码声道 {id}：{name}/Decoding channel {id}：{name}");
-                    execute_command(command, gst_scanner.as_deref())
+                .map(|(channel_id, name, out_path, command)| {
+                    run_channel(
+                        command,
+                        &child_env,
+                        input,
+                        out_path,
+                        policy,
+                        *channel_id,
+                        name,
+                    )
                 })
                 .collect::<Result<()>>()
         })?;
@@ -132,7 +248,7 @@ pub fn decode(
 }
 
 fn decode_auto(
-    input_file: &Path,
+    input: &InputSource,
     output_base: Option<&PathBuf>,
     gst_launch: &Path,
     gst_plugins: &Path,
@@ -142,11 +258,15 @@ fn decode_auto(
 ) -> Result<Vec<PathBuf>> {
     let mut decoded_files = Vec::new();
     let mut handles = Vec::new();
-    let gst_scanner = find_gst_scanner(gst_launch);
+    let child_env = ChildEnv::new(gst_launch, gst_plugins);
+    // 仅使用超时：auto 模式靠失败判断声道耗尽，不重试 / Timeout only: auto mode reads failures as the end of the channels, so it never retries
+    let timeout = RunPolicy::from_env().timeout;
+    let report = progress::is_active();
+    progress::set_slots(1);
 
     // 自动模式：尝试解码最多 32 个声道（通常文件不会这么多）
     // Auto mode: try decoding up to 32 channels (files typically don't have this many)
-    println!(
+    say!(
         "自动模式：检测文件的原生声道配置/Auto mode: detecting file's native channel configuration"
     );
 
@@ -156,7 +276,7 @@ fn decode_auto(
         let suffix = channel_output_suffix(&channel_name, channel_id, no_numbers);
 
         let out_path = output_base.map_or_else(
-            || input_file.with_extension(&suffix[1..]),
+            || input.path.with_extension(&suffix[1..]),
             |base| base.with_extension(&suffix[1..]),
         );
 
@@ -164,37 +284,59 @@ fn decode_auto(
         if out_path.exists() {
             let _ = std::fs::remove_file(&out_path);
         }
+        interrupt::track_output(out_path.clone());
         decoded_files.push(out_path.clone());
 
         let command = build_gstreamer_command_auto(
-            input_file,
+            input,
             &out_path,
             channel_id,
             gst_launch,
             gst_plugins,
             audio_format,
+            report,
         );
 
         if single {
-            println!(
+            say!(
                 "正在解码声道 {}：{}/Decoding channel {}：{}",
-                channel_id, &channel_name, channel_id, &channel_name
+                channel_id,
+                &channel_name,
+                channel_id,
+                &channel_name
             );
-            match execute_command(&command, gst_scanner.as_deref()) {
+            events::emit(&Event::DecodeStart {
+                input: &input.path,
+                channel: channel_id + 1,
+                name: &channel_name,
+                output: &out_path,
+            });
+            let result = execute_command(&command, &child_env, input, timeout, 0);
+            match &result {
                 Ok(()) => {
                     // 成功了，继续下一个声道 / Success, continue to next channel
+                    emit_channel_result(input, channel_id, &channel_name, &out_path, &result);
+                }
+                // 缺少插件并非声道耗尽 / A missing plugin is not the end of the channels
+                Err(DecodeError::GStreamerMissingPlugin(_)) => {
+                    emit_channel_result(input, channel_id, &channel_name, &out_path, &result);
+                    result?;
                 }
                 Err(_) => {
                     // 解码失败，说明没有这个声道了，删除输出文件并停止
                     // Decode failed, this channel doesn't exist, remove output and stop
                     let _ = std::fs::remove_file(&out_path);
                     decoded_files.pop();
-                    println!("已检测到 {channel_id} 个声道/Detected {channel_id} channels");
+                    say!("已检测到 {channel_id} 个声道/Detected {channel_id} channels");
+                    events::emit(&Event::ChannelsDetected {
+                        input: &input.path,
+                        channels: channel_id,
+                    });
                     break;
                 }
             }
         } else {
-            handles.push((channel_id, channel_name, command));
+            handles.push((channel_id, channel_name, out_path, command));
         }
     }
 
@@ -203,17 +345,32 @@ fn decode_auto(
         // 在并行模式下，我们需要逐个尝试声道直到失败
         // In parallel mode, we need to try channels one by one until one fails
         // 为了简化，我们还是逐个处理 / For simplicity, process one by one
-        println!(
+        say!(
             "自动模式不支持并行解码，转换为顺序解码/Auto mode doesn't support parallel decoding, switching to sequential"
         );
-        for (channel_id, channel_name, command) in handles {
-            println!(
+        for (channel_id, channel_name, out_path, command) in handles {
+            say!(
                 "正在解码声道 {}：{}/Decoding channel {}：{}",
-                channel_id, &channel_name, channel_id, &channel_name
+                channel_id,
+                &channel_name,
+                channel_id,
+                &channel_name
             );
-            match execute_command(&command, gst_scanner.as_deref()) {
+            events::emit(&Event::DecodeStart {
+                input: &input.path,
+                channel: channel_id + 1,
+                name: &channel_name,
+                output: &out_path,
+            });
+            let result = execute_command(&command, &child_env, input, timeout, 0);
+            match &result {
                 Ok(()) => {
                     // 成功 / Success
+                    emit_channel_result(input, channel_id, &channel_name, &out_path, &result);
+                }
+                Err(DecodeError::GStreamerMissingPlugin(_)) => {
+                    emit_channel_result(input, channel_id, &channel_name, &out_path, &result);
+                    result?;
                 }
                 Err(_) => {
                     // 失败，停止 / Failed, stop
@@ -225,7 +382,11 @@ fn decode_auto(
                         let _ = std::fs::remove_file(&decoded_files[pos]);
                         decoded_files.pop();
                     }
-                    println!("已检测到 {channel_id} 个声道/Detected {channel_id} channels");
+                    say!("已检测到 {channel_id} 个声道/Detected {channel_id} channels");
+                    events::emit(&Event::ChannelsDetected {
+                        input: &input.path,
+                        channels: channel_id,
+                    });
                     break;
                 }
             }
@@ -236,55 +397,116 @@ fn decode_auto(
 }
 
 fn build_gstreamer_command_auto(
-    input_file: &Path,
+    input: &InputSource,
     output_file: &Path,
     channel_id: usize,
     gst_launch: &Path,
     gst_plugins: &Path,
     format: AudioFormat,
+    report: bool,
 ) -> Vec<String> {
-    let mut cmd = vec![
-        gst_launch.to_string_lossy().to_string(),
-        "--gst-plugin-path".to_string(),
-        gst_plugins.to_string_lossy().to_string(),
-        "filesrc".to_string(),
-        format!("location={}", input_file.display()),
-        "!".to_string(),
-    ];
+    // 使用最高的声道配置（20 = 9.1.6）以获得文件中所有可用的声道
+    // Use highest channel config (20 = 9.1.6) to get all available channels in file
+    let mut cmd = pipeline_head(input, 20, gst_launch, gst_plugins, format, report);
+    cmd.extend(channel_branch(channel_id, output_file));
+    cmd
+}
 
-    match format {
-        AudioFormat::Eac3 => {
-            cmd.extend(vec!["dlbac3parse".to_string(), "!".to_string()]);
-        }
-        AudioFormat::TrueHD => {
-            cmd.extend(vec![
-                "dlbtruehdparse".to_string(),
-                "align-major-sync=false".to_string(),
-                "!".to_string(),
-            ]);
-        }
-    }
+#[allow(clippy::too_many_arguments)]
+fn build_gstreamer_command(
+    input: &InputSource,
+    output_file: &Path,
+    channel_id: usize,
+    out_ch_config: u32,
+    gst_launch: &Path,
+    gst_plugins: &Path,
+    format: AudioFormat,
+    report: bool,
+) -> Vec<String> {
+    let mut cmd = pipeline_head(
+        input,
+        out_ch_config,
+        gst_launch,
+        gst_plugins,
+        format,
+        report,
+    );
+    cmd.extend(channel_branch(channel_id, output_file));
+    cmd
+}
 
-    // dlbaudiodecbin with max out-ch-config to get all available channels / 使用最大的 out-ch-config 以获得所有可用声道
-    cmd.push("dlbaudiodecbin".to_string());
-    if matches!(format, AudioFormat::TrueHD) {
-        let pres = env::var("MCAT_TRUEHD_PRESENTATION")
-            .ok()
-            .and_then(|v| v.parse::<u32>().ok())
-            .unwrap_or(16);
-        cmd.push(format!("truehddec::presentation={pres}"));
+/// 构建单管道命令：一次解码，deinterleave 的每个 `src_N` 各自写入文件
+/// Build a single-pipeline command: decode once, every deinterleave `src_N` pad goes to its own file
+fn build_gstreamer_command_combined(
+    input: &InputSource,
+    output_files: &[PathBuf],
+    out_ch_config: u32,
+    gst_launch: &Path,
+    gst_plugins: &Path,
+    format: AudioFormat,
+    report: bool,
+) -> Vec<String> {
+    let mut cmd = pipeline_head(
+        input,
+        out_ch_config,
+        gst_launch,
+        gst_plugins,
+        format,
+        report,
+    );
+    for (channel_id, output_file) in output_files.iter().enumerate() {
+        cmd.extend(channel_branch(channel_id, output_file));
     }
-    // 使用最高的声道配置（20 = 9.1.6）以获得文件中所有可用的声道
-    // Use highest channel config (20 = 9.1.6) to get all available channels in file
-    cmd.push("out-ch-config=20".to_string());
+    cmd
+}
 
+/// 管道公共部分：filesrc → 解析器 → dlbaudiodecbin → deinterleave
+/// Shared pipeline head: filesrc → parser → dlbaudiodecbin → deinterleave
+fn pipeline_head(
+    input: &InputSource,
+    out_ch_config: u32,
+    gst_launch: &Path,
+    gst_plugins: &Path,
+    format: AudioFormat,
+    report: bool,
+) -> Vec<String> {
+    let mut cmd = decoder_head(
+        input,
+        out_ch_config,
+        gst_launch,
+        gst_plugins,
+        format,
+        false,
+        report,
+    );
     cmd.extend(vec![
         "!".to_string(),
         "deinterleave".to_string(),
         "name=d".to_string(),
-        format!("d.src_{channel_id}"),
-        "!".to_string(),
-        "queue".to_string(),
+    ]);
+    cmd
+}
+
+/// 构建流式命令：交错 PCM 经 wavenc 写到 stdout（`-q` 避免状态信息混入）
+/// Build a streaming command: interleaved PCM through wavenc to stdout (`-q` keeps status text out)
+fn build_gstreamer_command_stream(
+    input: &InputSource,
+    out_ch_config: u32,
+    gst_launch: &Path,
+    gst_plugins: &Path,
+    format: AudioFormat,
+) -> Vec<String> {
+    let mut cmd = decoder_head(
+        input,
+        out_ch_config,
+        gst_launch,
+        gst_plugins,
+        format,
+        true,
+        false,
+    );
+    cmd.insert(1, "-q".to_string());
+    cmd.extend(vec![
         "!".to_string(),
         "audioconvert".to_string(),
         "!".to_string(),
@@ -292,34 +514,59 @@ fn build_gstreamer_command_auto(
         "!".to_string(),
         "wavenc".to_string(),
         "!".to_string(),
-        "filesink".to_string(),
+        "fdsink".to_string(),
+        "fd=1".to_string(),
         "sync=false".to_string(),
-        format!("location={}", output_file.display()),
     ]);
-
     cmd
 }
 
-fn build_gstreamer_command(
-    input_file: &Path,
-    output_file: &Path,
-    channel_id: usize,
+/// 解码器部分：filesrc/fdsrc →（解复用）→ 解析器 → dlbaudiodecbin / Decoder part: filesrc/fdsrc → (demux) → parser → dlbaudiodecbin
+/// `stream` 为真时 stdout 承载 PCM：不加 progressreport（其输出写到 stdout），
+/// 裸码流改由 stdin 读取，以便轮询读取位置作为进度
+/// With `stream` stdout carries PCM: no progressreport (it prints to stdout),
+/// and raw streams are read from stdin so the read position can be polled for progress
+/// `report` 为真时在解码器前加入 progressreport / With `report` a progressreport goes in front of the decoder
+#[allow(clippy::too_many_arguments)]
+fn decoder_head(
+    input: &InputSource,
     out_ch_config: u32,
     gst_launch: &Path,
     gst_plugins: &Path,
     format: AudioFormat,
+    stream: bool,
+    report: bool,
 ) -> Vec<String> {
     let mut cmd = vec![
         gst_launch.to_string_lossy().to_string(),
         "--gst-plugin-path".to_string(),
         gst_plugins.to_string_lossy().to_string(),
-        "filesrc".to_string(),
-        format!("location={}", input_file.display()),
-        "!".to_string(),
     ];
+    // 有前导数据时由已定位到码流起点的 stdin 读取 / With leading junk, read from stdin positioned at the stream start
+    if input.offset > 0 || (stream && input.demux.is_none()) {
+        cmd.extend(vec!["fdsrc".to_string(), "fd=0".to_string()]);
+    } else {
+        cmd.extend(vec![
+            "filesrc".to_string(),
+            format!("location={}", input.path.display()),
+        ]);
+    }
+    cmd.push("!".to_string());
+
+    // 容器输入：解复用并只取选中的音轨 / Container input: demux and take only the selected track
+    if let Some(demux) = &input.demux {
+        cmd.extend(vec![
+            demux.element.to_string(),
+            "name=demux".to_string(),
+            format!("demux.{}", demux.pad),
+            "!".to_string(),
+            "queue".to_string(),
+            "!".to_string(),
+        ]);
+    }
 
     match format {
-        AudioFormat::Eac3 => {
+        AudioFormat::Ac3 | AudioFormat::Eac3 | AudioFormat::Eac3Joc => {
             cmd.extend(vec!["dlbac3parse".to_string(), "!".to_string()]);
         }
         AudioFormat::TrueHD => {
@@ -331,6 +578,17 @@ fn build_gstreamer_command(
         }
     }
 
+    // 进度来源：progressreport 每秒向 stdout 输出一行；仅在有进度显示时加入
+    // Progress source: progressreport prints one line per second to stdout; only added while progress is shown
+    if report {
+        cmd.extend(vec![
+            "progressreport".to_string(),
+            "name=progress".to_string(),
+            "update-freq=1".to_string(),
+            "!".to_string(),
+        ]);
+    }
+
     // dlbaudiodecbin + properties / 杜比音频解码器及其属性
     cmd.push("dlbaudiodecbin".to_string());
     if matches!(format, AudioFormat::TrueHD) {
@@ -344,10 +602,13 @@ fn build_gstreamer_command(
     // 明确指定声道配置 / Explicitly specify channel configuration
     cmd.push(format!("out-ch-config={out_ch_config}"));
 
-    cmd.extend(vec![
-        "!".to_string(),
-        "deinterleave".to_string(),
-        "name=d".to_string(),
+    cmd
+}
+
+/// 单个声道分支：`d.src_N` → queue → F32LE → wavenc → filesink
+/// Per-channel branch: `d.src_N` → queue → F32LE → wavenc → filesink
+fn channel_branch(channel_id: usize, output_file: &Path) -> Vec<String> {
+    vec![
         format!("d.src_{channel_id}"),
         "!".to_string(),
         "queue".to_string(),
@@ -361,78 +622,531 @@ fn build_gstreamer_command(
         "filesink".to_string(),
         "sync=false".to_string(),
         format!("location={}", output_file.display()),
-    ]);
+    ]
+}
 
-    cmd
+/// 流式解码：gst-launch 的交错 PCM 经 stdout 直接写入最终多声道 WAV，不产生单声道临时文件
+/// Streamed decode: interleaved PCM from gst-launch stdout goes straight into the final multichannel WAV, no mono temp files
+pub fn decode_streamed(
+    input: &InputSource,
+    merged_file: &Path,
+    gst_launch: &Path,
+    gst_plugins: &Path,
+    audio_format: AudioFormat,
+    channel_config: &ChannelConfig,
+) -> Result<()> {
+    // auto 模式使用最高声道配置，实际声道数由流头给出 / Auto mode uses the highest config; the stream header carries the real count
+    let out_ch_config = if channel_config.id == u32::MAX {
+        20
+    } else {
+        channel_config.id
+    };
+    let command =
+        build_gstreamer_command_stream(input, out_ch_config, gst_launch, gst_plugins, audio_format);
+    let child_env = ChildEnv::new(gst_launch, gst_plugins);
+
+    say!(
+        "流式解码 {} 至 {}/Streaming decode of {} into {}",
+        input.path.display(),
+        merged_file.display(),
+        input.path.display(),
+        merged_file.display()
+    );
+    let outputs = [merged_file.to_path_buf()];
+    progress::set_slots(1);
+    with_retries(
+        RunPolicy::from_env(),
+        child_env.cancel,
+        &outputs,
+        |timeout| {
+            stream_once(
+                &command,
+                &child_env,
+                input,
+                merged_file,
+                channel_config,
+                timeout,
+            )
+        },
+    )
 }
 
-fn execute_command(command: &[String], gst_scanner: Option<&Path>) -> Result<()> {
-    let mut cmd = Command::new(&command[0]);
-    cmd.args(&command[1..]);
+/// 运行一次流式管道；超时由看门狗线程终止子进程 / Run the streamed pipeline once; a watchdog thread kills the child on timeout
+fn stream_once(
+    command: &[String],
+    child_env: &ChildEnv,
+    input: &InputSource,
+    merged_file: &Path,
+    channel_config: &ChannelConfig,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    if child_env.cancel.load(Ordering::SeqCst) {
+        return Err(DecodeError::Interrupted);
+    }
+    let (mut cmd, position) = prepare_command(command, child_env, input)?;
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    interrupt::track_output(merged_file.to_path_buf());
 
-    // 为本地 Dolby 工具设置 DYLD_LIBRARY_PATH/Set DYLD_LIBRARY_PATH for local Dolby tools
-    if std::path::Path::new("./dolby-tools").exists() {
-        let libs_path = std::path::PathBuf::from("./dolby-tools/gst-plugins-libs")
-            .canonicalize()
-            .ok();
+    let mut child = cmd.spawn().map_err(|e| {
+        DecodeError::GStreamerFailed(format!(
+            "无法执行 gst-launch/Failed to execute gst-launch: {e}"
+        ))
+    })?;
+    let stdout = child.stdout.take().ok_or_else(|| {
+        DecodeError::GStreamerFailed(
+            "无法获取 gst-launch 输出/Cannot capture gst-launch stdout".to_string(),
+        )
+    })?;
+    let stderr = capture_stderr(&mut child);
 
-        if let Some(libs) = libs_path {
-            let mut dyld_path = libs.to_string_lossy().to_string();
-            if let Ok(existing) = env::var("DYLD_LIBRARY_PATH") {
-                dyld_path = format!("{dyld_path}:{existing}");
+    // 合并在本线程读取 stdout，看门狗持有子进程 / The merge reads stdout on this thread while the watchdog holds the child
+    let child = Arc::new(Mutex::new(child));
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let watchdog = {
+        let child = Arc::clone(&child);
+        let timed_out = Arc::clone(&timed_out);
+        let cancel = child_env.cancel;
+        let deadline = timeout.map(|limit| Instant::now() + limit);
+        let mut position = position;
+        let input = input.clone();
+        std::thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = done_rx.recv_timeout(WAIT_POLL) {
+                if let Some(fraction) = position.as_mut().and_then(|p| read_fraction(p, &input)) {
+                    progress::update(0, fraction);
+                }
+                let expired = deadline.is_some_and(|d| Instant::now() >= d);
+                if expired || cancel.load(Ordering::SeqCst) {
+                    timed_out.store(expired, Ordering::SeqCst);
+                    if let Ok(mut child) = child.lock() {
+                        let _ = child.kill();
+                    }
+                    break;
+                }
             }
-            cmd.env("DYLD_LIBRARY_PATH", dyld_path);
-        }
+        })
+    };
+
+    let merged =
+        crate::merger::merge_stream(BufReader::new(stdout), merged_file, Some(channel_config));
+    let _ = done_tx.send(());
+    let _ = watchdog.join();
+    let mut child = child.lock().map_err(|_| {
+        DecodeError::GStreamerFailed(
+            "gst-launch 状态不可用/gst-launch state unavailable".to_string(),
+        )
+    })?;
+    if merged.is_err() {
+        // 写入失败时终止子进程，避免其阻塞在满管道上 / Kill the child on write failure so it doesn't block on a full pipe
+        let _ = child.kill();
     }
+    let status = child.wait().map_err(|e| {
+        DecodeError::GStreamerFailed(format!(
+            "无法等待 gst-launch/Failed to wait for gst-launch: {e}"
+        ))
+    })?;
+    let tail = stderr
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
 
-    if let Some(scanner) = gst_scanner {
-        cmd.env("GST_PLUGIN_SCANNER", scanner);
+    let cancelled = child_env.cancel.load(Ordering::SeqCst);
+    if !status.success() || merged.is_err() || timed_out.load(Ordering::SeqCst) || cancelled {
+        // 不保留半成品 / Don't leave a partial output behind
+        let _ = std::fs::remove_file(merged_file);
+    }
+    if cancelled {
+        return Err(DecodeError::Interrupted);
+    }
+    if let Some(limit) = timeout.filter(|_| timed_out.load(Ordering::SeqCst)) {
+        return Err(timeout_error(limit, &tail));
+    }
+    if !status.success() {
+        return Err(classify_failure(&status, &tail));
     }
+    let frames = merged?;
+    log::info!("流式写入 {frames} 帧/Streamed {frames} frames");
+    Ok(())
+}
 
-    if std::env::var("MCAT_VERBOSE_GST").is_ok() {
-        cmd.stdout(Stdio::inherit()).stderr(Stdio::inherit());
-    } else {
-        // 丢弃子进程输出，避免在高并发下因管道缓冲阻塞 / Drop child stdio to avoid pipe blocking under high concurrency
-        cmd.stdout(Stdio::null()).stderr(Stdio::null());
+/// 构建子进程命令并设置库路径、插件扫描器与输入 / Build the child command with library path, plugin scanner and input set
+///
+/// 管道从 stdin 读取时，额外返回共享同一文件偏移的句柄，可用于轮询读取位置
+/// When the pipeline reads stdin, also returns a handle sharing the same file offset, for polling the read position
+fn prepare_command(
+    command: &[String],
+    child_env: &ChildEnv,
+    input: &InputSource,
+) -> Result<(Command, Option<File>)> {
+    let mut cmd = Command::new(&command[0]);
+    cmd.args(&command[1..]);
+
+    // fdsrc 从 stdin 读取：打开输入并定位到码流起点 / fdsrc reads stdin: open the input and seek to the stream start
+    let mut position = None;
+    if command.windows(2).any(|w| w == ["fdsrc", "fd=0"]) {
+        let mut file = File::open(&input.path)?;
+        file.seek(SeekFrom::Start(input.offset))?;
+        position = file.try_clone().ok();
+        cmd.stdin(Stdio::from(file));
     }
 
-    let status = cmd.status().map_err(|e| {
+    child_env.apply(&mut cmd);
+    Ok((cmd, position))
+}
+
+/// 由共享的文件偏移计算读取进度 / Read progress from the shared file offset
+fn read_fraction(position: &mut File, input: &InputSource) -> Option<f32> {
+    let len = position.metadata().ok()?.len();
+    let pos = position.stream_position().ok()?;
+    let span = len.checked_sub(input.offset).filter(|&s| s > 0)?;
+    Some((pos.saturating_sub(input.offset) as f64 / span as f64) as f32)
+}
+
+/// 解码一个声道（按策略重试）并发出开始/结束事件
+/// Decode one channel (retrying per the policy), emitting start and finish events
+#[allow(clippy::too_many_arguments)]
+fn run_channel(
+    command: &[String],
+    child_env: &ChildEnv,
+    input: &InputSource,
+    out_path: &Path,
+    policy: RunPolicy,
+    channel_id: usize,
+    name: &str,
+) -> Result<()> {
+    let id = channel_id + 1;
+    say!("正在解码声道 {id}：{name}/Decoding channel {id}：{name}");
+    events::emit(&Event::DecodeStart {
+        input: &input.path,
+        channel: id,
+        name,
+        output: out_path,
+    });
+    let result = run_with_policy(
+        command,
+        child_env,
+        input,
+        &[out_path.to_path_buf()],
+        policy,
+        channel_id,
+    );
+    emit_channel_result(input, channel_id, name, out_path, &result);
+    result
+}
+
+/// 发出声道完成或失败事件 / Emit the channel finished or failed event
+fn emit_channel_result(
+    input: &InputSource,
+    channel_id: usize,
+    name: &str,
+    output: &Path,
+    result: &Result<()>,
+) {
+    let (input, channel) = (&input.path, channel_id + 1);
+    events::emit(&match result {
+        Ok(()) => Event::DecodeDone {
+            input,
+            channel,
+            name,
+            output,
+        },
+        Err(e) => Event::DecodeFailed {
+            input,
+            channel,
+            name,
+            output,
+            error: e.to_string(),
+        },
+    });
+}
+
+/// 运行一条命令，失败时按策略重试 / Run one command, retrying failures per the policy
+fn run_with_policy(
+    command: &[String],
+    child_env: &ChildEnv,
+    input: &InputSource,
+    outputs: &[PathBuf],
+    policy: RunPolicy,
+    slot: usize,
+) -> Result<()> {
+    with_retries(policy, child_env.cancel, outputs, |timeout| {
+        execute_command(command, child_env, input, timeout, slot)
+    })
+}
+
+fn execute_command(
+    command: &[String],
+    child_env: &ChildEnv,
+    input: &InputSource,
+    timeout: Option<Duration>,
+    slot: usize,
+) -> Result<()> {
+    if child_env.cancel.load(Ordering::SeqCst) {
+        return Err(DecodeError::Interrupted);
+    }
+    let (mut cmd, _) = prepare_command(command, child_env, input)?;
+    // stdout 与 stderr 均由读取线程持续排空，避免在高并发下因管道缓冲阻塞
+    // stdout and stderr are both drained continuously by reader threads so the pipes never block under high concurrency
+    cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|e| {
         DecodeError::GStreamerFailed(format!(
             "无法执行 gst-launch/Failed to execute gst-launch: {e}"
         ))
     })?;
+    let stderr = capture_stderr(&mut child);
+    let stdout = watch_progress(&mut child, slot);
+    let outcome = wait_child(&mut child, timeout, child_env.cancel).map_err(|e| {
+        DecodeError::GStreamerFailed(format!(
+            "无法等待 gst-launch/Failed to wait for gst-launch: {e}"
+        ))
+    })?;
+    let tail = stderr
+        .map(|h| h.join().unwrap_or_default())
+        .unwrap_or_default();
 
+    if let Some(handle) = stdout {
+        let _ = handle.join();
+    }
+    let status = match outcome {
+        WaitOutcome::Exited(status) => status,
+        WaitOutcome::TimedOut => return Err(timeout_error(timeout.unwrap_or_default(), &tail)),
+        WaitOutcome::Cancelled => return Err(DecodeError::Interrupted),
+    };
     if !status.success() {
-        let code = status
-            .code()
-            .map_or_else(|| "signal".to_string(), |c| c.to_string());
-        let cmd_line = command.join(" ");
-
-        // 检测是否可能是声道数不匹配的错误 / Detect if this might be channel count mismatch error
-        let suggestion = if cmd_line.contains("out-ch-config") {
-            "\n\n[提示] 如果看到 'Delayed linking failed' 或 'src_N' 相关错误，说明文件的实际声道数与请求的不匹配。\n\
-             请尝试使用较小的声道配置，如 --channels 7.1 或 --channels 5.1\n\
-             或设置环境变量 MCAT_VERBOSE_GST=1 查看详细错误信息。\n\
-             \n\
-             [Tip] If you see 'Delayed linking failed' or 'src_N' related errors, the file's actual channel count doesn't match the requested configuration.\n\
-             Try using a smaller channel configuration like --channels 7.1 or --channels 5.1,\n\
-             or set MCAT_VERBOSE_GST=1 environment variable to see detailed error information."
-                .to_string()
+        return Err(classify_failure(&status, &tail));
+    }
+
+    Ok(())
+}
+
+/// 默认重试次数与首次退避 / Default retry count and first backoff
+const DEFAULT_RETRIES: u32 = 0;
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+/// 等待子进程时的轮询间隔 / Poll interval while waiting on a child
+const WAIT_POLL: Duration = Duration::from_millis(50);
+
+/// 子进程运行策略：超时与重试 / Child run policy: timeout and retries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RunPolicy {
+    timeout: Option<Duration>,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl RunPolicy {
+    /// 读取 MCAT_GST_TIMEOUT（秒，0 或未设置为不限）与 MCAT_GST_RETRIES（默认 0）
+    /// Read MCAT_GST_TIMEOUT (seconds, 0 or unset means none) and MCAT_GST_RETRIES (default 0)
+    fn from_env() -> Self {
+        let timeout = env::var("MCAT_GST_TIMEOUT")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|&s| s > 0)
+            .map(Duration::from_secs);
+        let retries = env::var("MCAT_GST_RETRIES")
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(DEFAULT_RETRIES);
+        Self {
+            timeout,
+            retries,
+            backoff: RETRY_BACKOFF,
+        }
+    }
+}
+
+/// 可重试的失败：仅超时；其他管道错误（如损坏输入导致的数据流错误）每次都会复现
+/// Retryable failures: timeouts only; other pipeline errors (e.g. data stream errors on corrupt input) recur every time
+fn is_transient(error: &DecodeError) -> bool {
+    matches!(error, DecodeError::GStreamerTimeout(_))
+}
+
+/// 按策略运行，重试前删除不完整的输出并指数退避；退避期间可被取消
+/// Run per the policy, removing partial outputs and backing off exponentially before each retry; the backoff can be cancelled
+fn with_retries(
+    policy: RunPolicy,
+    cancel: &AtomicBool,
+    outputs: &[PathBuf],
+    mut run: impl FnMut(Option<Duration>) -> Result<()>,
+) -> Result<()> {
+    let mut attempt = 0;
+    loop {
+        match run(policy.timeout) {
+            Err(e) if attempt < policy.retries && is_transient(&e) => {
+                for output in outputs {
+                    let _ = std::fs::remove_file(output);
+                }
+                let delay = policy.backoff * 2u32.saturating_pow(attempt);
+                attempt += 1;
+                eprintln!(
+                    "[警告] 管道失败，{delay:?} 后重试（第 {attempt}/{} 次）/Pipeline failed, retrying in {delay:?} (attempt {attempt}/{}): {e}",
+                    policy.retries, policy.retries
+                );
+                let deadline = Instant::now() + delay;
+                while Instant::now() < deadline {
+                    if cancel.load(Ordering::SeqCst) {
+                        return Err(DecodeError::Interrupted);
+                    }
+                    std::thread::sleep(WAIT_POLL.min(deadline - Instant::now()));
+                }
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 等待子进程的结果 / How waiting on a child ended
+enum WaitOutcome {
+    Exited(ExitStatus),
+    TimedOut,
+    Cancelled,
+}
+
+/// 轮询等待子进程；超时或取消时终止它 / Poll the child until it exits, killing it on timeout or cancellation
+fn wait_child(
+    child: &mut Child,
+    timeout: Option<Duration>,
+    cancel: &AtomicBool,
+) -> std::io::Result<WaitOutcome> {
+    let deadline = timeout.map(|limit| Instant::now() + limit);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(WaitOutcome::Exited(status));
+        }
+        let outcome = if cancel.load(Ordering::SeqCst) {
+            WaitOutcome::Cancelled
+        } else if deadline.is_some_and(|d| Instant::now() >= d) {
+            WaitOutcome::TimedOut
         } else {
-            String::new()
+            std::thread::sleep(WAIT_POLL);
+            continue;
         };
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(outcome);
+    }
+}
+
+fn timeout_error(limit: Duration, tail: &[String]) -> DecodeError {
+    let mut message = format!(
+        "gst-launch 超过 {limit:?} 未结束，已终止/gst-launch did not finish within {limit:?} and was killed"
+    );
+    if !tail.is_empty() {
+        message.push_str(&format!(
+            "\n--- stderr（最后 {n} 行）/stderr (last {n} lines) ---\n{}",
+            tail.join("\n"),
+            n = tail.len()
+        ));
+    }
+    DecodeError::GStreamerTimeout(message)
+}
+
+/// 保留的 stderr 行数与单行长度上限 / Number of stderr lines kept and per-line length cap
+const STDERR_TAIL_LINES: usize = 20;
+const STDERR_LINE_MAX: usize = 512;
+
+/// 在后台线程中读取子进程 stderr，只保留最后若干行（`MCAT_VERBOSE_GST` 时同时转发到终端）
+/// Read the child's stderr on a background thread, keeping only the last lines (also echoed to the terminal with `MCAT_VERBOSE_GST`)
+fn capture_stderr(child: &mut Child) -> Option<JoinHandle<Vec<String>>> {
+    let stderr = child.stderr.take()?;
+    let verbose = env::var("MCAT_VERBOSE_GST").is_ok();
+    Some(std::thread::spawn(move || {
+        let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+        let mut reader = BufReader::new(stderr);
+        let mut buf = Vec::new();
+        // 按字节读取行，容忍非 UTF-8 输出 / Read lines as bytes to tolerate non-UTF-8 output
+        while matches!(reader.read_until(b'\n', &mut buf), Ok(n) if n > 0) {
+            let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+            buf.clear();
+            if verbose {
+                eprintln!("{line}");
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if tail.len() == STDERR_TAIL_LINES {
+                tail.pop_front();
+            }
+            tail.push_back(line.chars().take(STDERR_LINE_MAX).collect());
+        }
+        tail.into()
+    }))
+}
+
+/// 在后台线程中读取 stdout 的 progressreport 行并上报进度 / Read progressreport lines from stdout on a background thread and report progress
+fn watch_progress(child: &mut Child, slot: usize) -> Option<JoinHandle<()>> {
+    let stdout = child.stdout.take()?;
+    Some(std::thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let mut buf = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut buf), Ok(n) if n > 0) {
+            if let Some(fraction) = progress::parse_progressreport(&String::from_utf8_lossy(&buf)) {
+                progress::update(slot, fraction);
+            }
+            buf.clear();
+        }
+    }))
+}
 
-        return Err(DecodeError::GStreamerFailed(format!(
-            "GStreamer 管道失败 (退出码 {code})/Pipeline failed (exit {code}){suggestion}"
-        )));
+/// 依据 stderr 末尾内容将管道失败归类 / Classify a pipeline failure from the tail of its stderr
+fn classify_failure(status: &ExitStatus, tail: &[String]) -> DecodeError {
+    let code = status
+        .code()
+        .map_or_else(|| "signal".to_string(), |c| c.to_string());
+    let mut message = format!("GStreamer 管道失败 (退出码 {code})/Pipeline failed (exit {code})");
+    if !tail.is_empty() {
+        message.push_str(&format!(
+            "\n--- stderr（最后 {n} 行）/stderr (last {n} lines) ---\n{}",
+            tail.join("\n"),
+            n = tail.len()
+        ));
     }
 
-    Ok(())
+    let has = |needle: &str| tail.iter().any(|l| l.to_ascii_lowercase().contains(needle));
+    if has("delayed linking failed") {
+        message.push_str(
+            "\n\n[提示] 文件的实际声道数与请求的配置不匹配，请尝试较小的声道配置，如 --channels 7.1 或 --channels 5.1。\n\
+             [Tip] The file's actual channel count doesn't match the requested configuration; try a smaller one like --channels 7.1 or --channels 5.1.",
+        );
+        DecodeError::GStreamerDelayedLinking(message)
+    } else if has("no element") || has("could not load plugin") || has("no such element") {
+        message.push_str(
+            "\n\n[提示] 缺少 GStreamer 元素，请运行 doctor 子命令检查插件目录。\n\
+             [Tip] A GStreamer element is missing; run the doctor subcommand to check the plugin directory.",
+        );
+        DecodeError::GStreamerMissingPlugin(message)
+    } else {
+        if tail.is_empty() {
+            message.push_str("\n设置 MCAT_VERBOSE_GST=1 可查看详细输出/Set MCAT_VERBOSE_GST=1 for detailed output");
+        }
+        DecodeError::GStreamerFailed(message)
+    }
+}
+
+/// 测试用的假外部程序 / Fake external programs for tests
+#[cfg(all(test, unix))]
+pub(crate) mod test_scripts {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+
+    /// 写出可执行的 shell 脚本 / Write an executable shell script
+    pub(crate) fn write_script(path: &Path, body: &str) {
+        std::fs::write(path, format!("#!/bin/sh\n{body}\n")).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// 在 `dir` 下写出假 gst-launch-1.0 / Write a fake gst-launch-1.0 under `dir`
+    pub(crate) fn fake_gst_launch(dir: &Path, body: &str) -> PathBuf {
+        let script = dir.join("gst-launch-1.0");
+        write_script(&script, body);
+        script
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(unix)]
+    use test_scripts::fake_gst_launch;
 
     // 文件名后缀：带编号为两位数 1 起始 / Suffix: numbered form is 1-based two-digit
     #[test]
@@ -447,13 +1161,14 @@ mod tests {
     #[test]
     fn eac3_command_uses_ac3_parser() {
         let cmd = build_gstreamer_command(
-            Path::new("in.eac3"),
+            &InputSource::file(Path::new("in.eac3")),
             Path::new("out.wav"),
             2,
             7,
             Path::new("gst-launch-1.0"),
             Path::new("/plugins"),
             AudioFormat::Eac3,
+            true,
         );
         assert!(cmd.iter().any(|a| a == "dlbac3parse"));
         assert!(!cmd.iter().any(|a| a == "dlbtruehdparse"));
@@ -467,32 +1182,542 @@ mod tests {
     #[test]
     fn truehd_command_uses_truehd_parser() {
         let cmd = build_gstreamer_command(
-            Path::new("in.thd"),
+            &InputSource::file(Path::new("in.thd")),
             Path::new("out.wav"),
             0,
             20,
             Path::new("gst-launch-1.0"),
             Path::new("/plugins"),
             AudioFormat::TrueHD,
+            true,
         );
         assert!(cmd.iter().any(|a| a == "dlbtruehdparse"));
         assert!(cmd.iter().any(|a| a == "align-major-sync=false"));
         assert!(cmd.iter().any(|a| a == "out-ch-config=20"));
     }
 
+    // 容器输入在解析器前插入解复用元素并取选中的 pad / Container input inserts the demux element before the parser and takes the selected pad
+    #[test]
+    fn container_command_inserts_demux() {
+        let input = InputSource {
+            path: PathBuf::from("movie.m2ts"),
+            demux: Some(crate::demux::DemuxTrack {
+                element: "tsdemux",
+                pad: "audio_1_1100".to_string(),
+                description: "#4352 TrueHD [eng]".to_string(),
+                container: crate::demux::Container::MpegTs { packet_size: 192 },
+                track_id: 4352,
+            }),
+            offset: 0,
+        };
+        let cmd = build_gstreamer_command(
+            &input,
+            Path::new("out.wav"),
+            0,
+            20,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::TrueHD,
+            true,
+        );
+        let pos = |s: &str| cmd.iter().position(|a| a == s).unwrap();
+        assert!(pos("location=movie.m2ts") < pos("tsdemux"));
+        assert_eq!(cmd[pos("tsdemux") + 1], "name=demux");
+        assert_eq!(cmd[pos("tsdemux") + 2], "demux.audio_1_1100");
+        assert!(pos("demux.audio_1_1100") < pos("dlbtruehdparse"));
+    }
+
+    // 有前导数据时改用 fdsrc 从 stdin 读取 / With leading junk the pipeline reads stdin through fdsrc
+    #[test]
+    fn offset_input_reads_stdin() {
+        let input = InputSource {
+            offset: 1000,
+            ..InputSource::file(Path::new("cut.eac3"))
+        };
+        let cmd = build_gstreamer_command(
+            &input,
+            Path::new("out.wav"),
+            0,
+            2,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            true,
+        );
+        assert!(cmd.windows(2).any(|w| w == ["fdsrc", "fd=0"]));
+        assert!(!cmd
+            .iter()
+            .any(|a| a == "filesrc" || a.starts_with("location=cut")));
+    }
+
+    static NEVER_CANCEL: AtomicBool = AtomicBool::new(false);
+
+    /// 运行假 gst-launch 并返回其失败 / Run a fake gst-launch and return its failure
+    #[cfg(unix)]
+    fn run_fake_gst(dir: &Path, body: &str, timeout: Option<Duration>) -> DecodeError {
+        let script = fake_gst_launch(dir, body);
+        let child_env = ChildEnv {
+            gst_scanner: None,
+            library_dirs: Vec::new(),
+            cancel: &NEVER_CANCEL,
+        };
+        let command = vec![script.to_string_lossy().to_string()];
+        execute_command(
+            &command,
+            &child_env,
+            &InputSource::file(&dir.join("in.ec3")),
+            timeout,
+            0,
+        )
+        .unwrap_err()
+    }
+
+    // 大量 stderr 不阻塞子进程，且只保留最后若干行 / Heavy stderr never blocks the child and only the tail is kept
+    #[cfg(unix)]
+    #[test]
+    fn stderr_tail_is_bounded_and_classified() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run_fake_gst(
+            dir.path(),
+            "i=0; while [ $i -lt 3000 ]; do echo \"noise line $i padding padding padding\" >&2; i=$((i+1)); done\n\
+             echo 'WARNING: from element /GstPipeline:pipeline0/GstDlbAudioDecBin:dec: Delayed linking failed.' >&2\n\
+             exit 1",
+            None,
+        );
+        let DecodeError::GStreamerDelayedLinking(message) = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(message.contains("exit 1"));
+        assert!(message.contains("noise line 2999"));
+        assert!(!message.contains("noise line 2980 "));
+        assert_eq!(message.matches("noise line").count(), STDERR_TAIL_LINES - 1);
+    }
+
+    // 缺少元素归类为缺少插件；无法识别的输出保持通用错误 / Missing elements map to missing plugin; anything else stays generic
+    #[cfg(unix)]
+    #[test]
+    fn missing_plugin_and_generic_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = run_fake_gst(
+            dir.path(),
+            "echo 'WARNING: erroneous pipeline: no element \"dlbac3parse\"' >&2; exit 1",
+            None,
+        );
+        assert!(
+            matches!(err, DecodeError::GStreamerMissingPlugin(ref m) if m.contains("dlbac3parse"))
+        );
+
+        let err = run_fake_gst(
+            dir.path(),
+            "echo 'ERROR: Internal data stream error.' >&2; exit 2",
+            None,
+        );
+        assert!(
+            matches!(err, DecodeError::GStreamerFailed(ref m) if m.contains("exit 2") && m.contains("Internal data stream error"))
+        );
+    }
+
+    // 挂起的子进程在超时后被终止 / A hung child is killed once the timeout expires
+    #[cfg(unix)]
+    #[test]
+    fn timeout_kills_hung_child() {
+        let dir = tempfile::tempdir().unwrap();
+        let started = Instant::now();
+        let err = run_fake_gst(
+            dir.path(),
+            "echo stuck >&2\nexec sleep 30",
+            Some(Duration::from_millis(300)),
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert!(matches!(err, DecodeError::GStreamerTimeout(ref m) if m.contains("stuck")));
+    }
+
+    // 取消后不再启动新进程，运行中的子进程被终止 / After cancellation no new child starts and a running one is killed
+    #[cfg(unix)]
+    #[test]
+    fn cancel_stops_and_kills_children() {
+        static CANCELLED: AtomicBool = AtomicBool::new(true);
+        let dir = tempfile::tempdir().unwrap();
+        let child_env = ChildEnv {
+            gst_scanner: None,
+            library_dirs: Vec::new(),
+            cancel: &CANCELLED,
+        };
+        let err = execute_command(
+            &["/nonexistent/gst-launch-1.0".to_string()],
+            &child_env,
+            &InputSource::file(&dir.path().join("in.ec3")),
+            None,
+            0,
+        )
+        .unwrap_err();
+        assert!(matches!(err, DecodeError::Interrupted));
+
+        let cancel = AtomicBool::new(false);
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(200));
+                cancel.store(true, Ordering::SeqCst);
+            });
+            let outcome = wait_child(&mut child, None, &cancel).unwrap();
+            assert!(matches!(outcome, WaitOutcome::Cancelled));
+        });
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    // 仅重试暂时性失败，且每次重试前删除不完整的输出 / Only transient failures retry, and partial outputs go before each retry
+    #[test]
+    fn retries_transient_failures_and_removes_partials() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let policy = RunPolicy {
+            timeout: None,
+            retries: 2,
+            backoff: Duration::from_millis(1),
+        };
+
+        let mut attempts = 0;
+        let result = with_retries(policy, &NEVER_CANCEL, std::slice::from_ref(&out), |_| {
+            attempts += 1;
+            if attempts > 1 {
+                assert!(!out.exists(), "partial output left before retry");
+            }
+            std::fs::write(&out, b"partial").unwrap();
+            if attempts < 3 {
+                Err(DecodeError::GStreamerTimeout("hung".to_string()))
+            } else {
+                Ok(())
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+
+        // 延迟链接与数据流错误不重试 / Delayed linking and data stream errors don't retry
+        let permanent: [fn() -> DecodeError; 2] = [
+            || DecodeError::GStreamerDelayedLinking("layout".to_string()),
+            || DecodeError::GStreamerFailed("Internal data stream error".to_string()),
+        ];
+        for error in permanent {
+            let mut attempts = 0;
+            let result = with_retries(policy, &NEVER_CANCEL, &[], |_| {
+                attempts += 1;
+                Err(error())
+            });
+            assert!(result.is_err_and(|e| e.to_string() == error().to_string()));
+            assert_eq!(attempts, 1);
+        }
+
+        // 退避期间取消立即返回 / Cancelling during the backoff returns right away
+        static CANCELLED: AtomicBool = AtomicBool::new(true);
+        let slow = RunPolicy {
+            backoff: Duration::from_secs(30),
+            ..policy
+        };
+        let started = Instant::now();
+        let result = with_retries(slow, &CANCELLED, &[], |_| {
+            Err(DecodeError::GStreamerTimeout("hung".to_string()))
+        });
+        assert!(matches!(result, Err(DecodeError::Interrupted)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    // doctor 检查的元素覆盖各种管道实际用到的全部元素
+    // The doctor's element list covers every element the pipelines actually use
+    #[test]
+    fn doctor_checks_every_pipeline_element() {
+        use crate::demux::{Container, DemuxTrack};
+        let launch = Path::new("gst-launch-1.0");
+        let plugins = Path::new("/plugins");
+        let mut inputs = vec![
+            InputSource::file(Path::new("in.eac3")),
+            InputSource {
+                offset: 100,
+                ..InputSource::file(Path::new("cut.eac3"))
+            },
+        ];
+        for container in [
+            Container::Matroska,
+            Container::Mp4,
+            Container::MpegTs { packet_size: 188 },
+        ] {
+            inputs.push(InputSource {
+                path: PathBuf::from("movie"),
+                demux: Some(DemuxTrack {
+                    element: container.element(),
+                    pad: "audio_0".to_string(),
+                    description: String::new(),
+                    container,
+                    track_id: 1,
+                }),
+                offset: 0,
+            });
+        }
+
+        let outputs = [PathBuf::from("out.wav")];
+        let mut commands = Vec::new();
+        for input in &inputs {
+            for format in [AudioFormat::Eac3, AudioFormat::TrueHD] {
+                commands.push(build_gstreamer_command(
+                    input,
+                    &outputs[0],
+                    0,
+                    2,
+                    launch,
+                    plugins,
+                    format,
+                    true,
+                ));
+                commands.push(build_gstreamer_command_auto(
+                    input,
+                    &outputs[0],
+                    0,
+                    launch,
+                    plugins,
+                    format,
+                    true,
+                ));
+                commands.push(build_gstreamer_command_combined(
+                    input, &outputs, 2, launch, plugins, format, true,
+                ));
+                commands.push(build_gstreamer_command_stream(
+                    input, 2, launch, plugins, format,
+                ));
+            }
+        }
+
+        // 元素为源与每个 "!" 之后的首个词（能力串含 "/"）/ Elements are the source and the first word after each "!" (caps contain "/")
+        let mut used = std::collections::BTreeSet::new();
+        for cmd in &commands {
+            let source = cmd.iter().position(|a| a == "--gst-plugin-path").unwrap() + 2;
+            used.insert(cmd[source].as_str());
+            used.extend(
+                cmd.windows(2)
+                    .filter(|w| w[0] == "!" && !w[1].contains('/'))
+                    .map(|w| w[1].as_str()),
+            );
+        }
+        let checked = crate::doctor::REQUIRED_ELEMENTS.into_iter().collect();
+        assert_eq!(used, checked);
+    }
+
     // auto 模式固定使用最高声道配置 20 / Auto mode pins highest channel config 20
     #[test]
     fn auto_command_pins_out_ch_config_20() {
         let cmd = build_gstreamer_command_auto(
-            Path::new("in.eac3"),
+            &InputSource::file(Path::new("in.eac3")),
             Path::new("out.wav"),
             3,
             Path::new("gst-launch-1.0"),
             Path::new("/plugins"),
             AudioFormat::Eac3,
+            true,
         );
         assert!(cmd.iter().any(|a| a == "out-ch-config=20"));
         assert!(cmd.iter().any(|a| a == "d.src_3"));
         assert!(cmd.iter().any(|a| a == "dlbac3parse"));
     }
+
+    // 单管道：只有一个解码器，每个 src_N 各自写入对应文件 / Combined: one decoder, each src_N to its own file
+    #[test]
+    fn combined_command_has_one_branch_per_channel() {
+        let outputs = vec![
+            PathBuf::from("out.01_L.wav"),
+            PathBuf::from("out.02_R.wav"),
+            PathBuf::from("out.03_C.wav"),
+        ];
+        let cmd = build_gstreamer_command_combined(
+            &InputSource::file(Path::new("in.eac3")),
+            &outputs,
+            3,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            true,
+        );
+        assert_eq!(cmd.iter().filter(|a| *a == "dlbaudiodecbin").count(), 1);
+        assert_eq!(cmd.iter().filter(|a| *a == "deinterleave").count(), 1);
+        assert_eq!(cmd.iter().filter(|a| *a == "filesink").count(), 3);
+        for (i, out) in outputs.iter().enumerate() {
+            let pad = cmd.iter().position(|a| *a == format!("d.src_{i}")).unwrap();
+            let sink = cmd
+                .iter()
+                .position(|a| *a == format!("location={}", out.display()))
+                .unwrap();
+            assert!(pad < sink);
+        }
+    }
+
+    // 假 gst-launch 脚本记录参数，验证单管道模式只启动一次并生成完整管道
+    // Fake gst-launch script records its args: combined mode runs once with the full pipeline
+    #[cfg(unix)]
+    #[test]
+    fn combined_decode_runs_fake_gst_launch_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("calls.log");
+        let script = fake_gst_launch(dir.path(), &format!("echo \"$*\" >> '{}'", log.display()));
+
+        let input = dir.path().join("in.eac3");
+        std::fs::write(&input, [0x0B, 0x77]).unwrap();
+        let config = crate::channels::get_config("5.1").unwrap();
+
+        let files = decode(
+            &InputSource::file(&input),
+            None,
+            &script,
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            &config,
+            false,
+            false,
+            PipelineMode::Combined,
+        )
+        .unwrap();
+        assert_eq!(files.len(), 6);
+
+        let calls = std::fs::read_to_string(&log).unwrap();
+        let lines: Vec<&str> = calls.lines().collect();
+        assert_eq!(
+            lines.len(),
+            1,
+            "期望仅一次调用/expected a single invocation"
+        );
+        assert!(lines[0].contains("out-ch-config=7"));
+        for (i, file) in files.iter().enumerate() {
+            assert!(lines[0].contains(&format!("d.src_{i} ")));
+            assert!(lines[0].contains(&format!("location={}", file.display())));
+        }
+    }
+
+    // 流式命令：静默、无 deinterleave、wavenc 输出到 stdout / Stream command: quiet, no deinterleave, wavenc to stdout
+    #[test]
+    fn stream_command_writes_wav_to_stdout() {
+        let cmd = build_gstreamer_command_stream(
+            &InputSource::file(Path::new("in.eac3")),
+            16,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+        );
+        assert_eq!(cmd[1], "-q");
+        assert!(!cmd.iter().any(|a| a == "deinterleave"));
+        assert_eq!(
+            &cmd[cmd.len() - 5..],
+            ["wavenc", "!", "fdsink", "fd=1", "sync=false"]
+        );
+        // stdout 承载 PCM：不加 progressreport，裸码流由 stdin 读取以轮询进度 / stdout carries PCM: no progressreport, raw streams read stdin for position polling
+        assert!(!cmd.iter().any(|a| a == "progressreport"));
+        assert!(cmd.windows(2).any(|w| w == ["fdsrc", "fd=0"]));
+    }
+
+    // 有进度显示时文件输出管道在解码器前插入 progressreport / With progress shown, file-output pipelines insert progressreport ahead of the decoder
+    #[test]
+    fn file_pipelines_report_progress() {
+        let cmd = build_gstreamer_command(
+            &InputSource::file(Path::new("in.eac3")),
+            Path::new("out.wav"),
+            0,
+            2,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            true,
+        );
+        let report = cmd.iter().position(|a| a == "progressreport").unwrap();
+        assert_eq!(cmd[report + 3], "!");
+        assert_eq!(cmd[report + 4], "dlbaudiodecbin");
+
+        // 没有进度显示时不依赖 progressreport / No progress display, no progressreport dependency
+        let cmd = build_gstreamer_command(
+            &InputSource::file(Path::new("in.eac3")),
+            Path::new("out.wav"),
+            0,
+            2,
+            Path::new("gst-launch-1.0"),
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            false,
+        );
+        assert!(!cmd.iter().any(|a| a == "progressreport"));
+    }
+
+    // 共享文件偏移给出读取进度（扣除前导数据）/ The shared file offset gives read progress (net of leading data)
+    #[test]
+    fn read_fraction_follows_shared_offset() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cut.eac3");
+        std::fs::write(&path, vec![0u8; 1100]).unwrap();
+        let input = InputSource {
+            offset: 100,
+            ..InputSource::file(&path)
+        };
+        let mut reader = File::open(&path).unwrap();
+        let mut probe = reader.try_clone().unwrap();
+        reader.seek(SeekFrom::Start(350)).unwrap();
+        assert_eq!(read_fraction(&mut probe, &input), Some(0.25));
+    }
+
+    // 假 gst-launch 向 stdout 输出 WAV 流，流式解码写出合并文件 / Fake gst-launch emits a WAV stream; streamed decode writes the merged file
+    #[cfg(unix)]
+    #[test]
+    fn streamed_decode_merges_fake_gst_stdout() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut w = hound::WavWriter::create(&source, spec).unwrap();
+        for s in [0.1_f32, -0.1, 0.2, -0.2, 0.3, -0.3] {
+            w.write_sample(s).unwrap();
+        }
+        w.finalize().unwrap();
+
+        let script = fake_gst_launch(dir.path(), &format!("cat '{}'", source.display()));
+
+        let input = dir.path().join("in.eac3");
+        std::fs::write(&input, [0x0B, 0x77]).unwrap();
+        let merged = dir.path().join("in.wav");
+        let config = crate::channels::get_config("2.0").unwrap();
+        decode_streamed(
+            &InputSource::file(&input),
+            &merged,
+            &script,
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            &config,
+        )
+        .unwrap();
+
+        let reader = hound::WavReader::open(&merged).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<f32> = reader.into_samples::<f32>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, [0.1, -0.1, 0.2, -0.2, 0.3, -0.3]);
+    }
+
+    // 假 gst-launch 失败时不保留半成品 / A failing fake gst-launch leaves no partial output
+    #[cfg(unix)]
+    #[test]
+    fn streamed_decode_failure_removes_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = fake_gst_launch(dir.path(), "exit 1");
+
+        let input = dir.path().join("in.eac3");
+        std::fs::write(&input, [0x0B, 0x77]).unwrap();
+        let merged = dir.path().join("in.wav");
+        let config = crate::channels::get_config("2.0").unwrap();
+        assert!(decode_streamed(
+            &InputSource::file(&input),
+            &merged,
+            &script,
+            Path::new("/plugins"),
+            AudioFormat::Eac3,
+            &config,
+        )
+        .is_err());
+        assert!(!merged.exists());
+    }
 }
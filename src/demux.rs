@@ -0,0 +1,1164 @@
+use crate::error::{DecodeError, Result};
+use crate::events::say;
+use crate::format::{detect_format, locate_stream, AudioFormat};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// 容器类型 / Container kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Matroska,
+    Mp4,
+    /// MPEG-TS（188 字节包）或 BDAV M2TS（192 字节包，含 4 字节时间戳）
+    /// MPEG-TS (188-byte packets) or BDAV M2TS (192-byte packets with a 4-byte timestamp)
+    MpegTs {
+        packet_size: usize,
+    },
+}
+
+impl Container {
+    /// 对应的 GStreamer 解复用元素 / Matching GStreamer demux element
+    pub(crate) fn element(self) -> &'static str {
+        match self {
+            Container::Matroska => "matroskademux",
+            Container::Mp4 => "qtdemux",
+            Container::MpegTs { .. } => "tsdemux",
+        }
+    }
+}
+
+/// 容器中的一条音轨 / One audio track inside a container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioTrack {
+    /// 轨道号：MKV TrackNumber、MP4 track_ID 或 TS PID，供 `--track` 选择
+    /// Track id: MKV TrackNumber, MP4 track_ID or TS PID, used by `--track`
+    pub id: u32,
+    /// 容器中的编码标识 / Codec identifier as stored in the container
+    pub codec: String,
+    /// 可解码时的格式；容器内的 E-AC3 不读取载荷，JOC 未知 / Decodable format, if any; container E-AC3 payloads are not read, so JOC is unknown
+    pub format: Option<AudioFormat>,
+    pub language: Option<String>,
+    /// 解复用元素上的输出 pad 名 / Output pad name on the demux element
+    pad: String,
+}
+
+impl AudioTrack {
+    pub fn describe(&self) -> String {
+        let lang = self.language.as_deref().unwrap_or("und");
+        format!("#{} {} [{lang}]", self.id, self.codec)
+    }
+}
+
+/// 管道中插入的解复用部分 / Demux stage inserted into the pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DemuxTrack {
+    pub element: &'static str,
+    pub pad: String,
+    pub description: String,
+    /// 读取音轨开头载荷时使用 / Used to read the start of the track payload
+    pub container: Container,
+    pub track_id: u32,
+}
+
+/// 解码输入：文件路径以及（容器输入时）要选取的音轨
+/// Decode input: the file path and, for container inputs, the track to pick
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSource {
+    pub path: PathBuf,
+    pub demux: Option<DemuxTrack>,
+    /// 裸码流中跳过的前导字节数 / Leading bytes skipped in a raw stream
+    pub offset: u64,
+}
+
+impl InputSource {
+    /// 裸码流输入 / Raw elementary stream input
+    pub fn file(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            demux: None,
+            offset: 0,
+        }
+    }
+}
+
+fn demux_err(msg: String) -> DecodeError {
+    DecodeError::FormatDetectionFailed(msg)
+}
+
+/// 根据文件头识别容器；裸码流返回 None / Detect the container from the file header; raw streams return None
+pub fn detect_container(path: &Path) -> Result<Option<Container>> {
+    let mut file =
+        File::open(path).map_err(|e| demux_err(format!("无法打开文件/Cannot open file: {e}")))?;
+    let mut head = Vec::with_capacity(400);
+    file.by_ref().take(400).read_to_end(&mut head)?;
+
+    if head.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return Ok(Some(Container::Matroska));
+    }
+    if head.len() >= 8 && matches!(&head[4..8], b"ftyp" | b"moov" | b"wide" | b"free") {
+        return Ok(Some(Container::Mp4));
+    }
+    for (packet_size, offset) in [(188, 0), (192, 4)] {
+        if (0..3).all(|k| head.get(offset + k * packet_size) == Some(&0x47)) {
+            return Ok(Some(Container::MpegTs { packet_size }));
+        }
+    }
+    Ok(None)
+}
+
+/// 列出容器中的全部音轨（包括不可解码的）/ List every audio track in the container (including undecodable ones)
+pub fn list_audio_tracks(path: &Path, container: Container) -> Result<Vec<AudioTrack>> {
+    let file =
+        File::open(path).map_err(|e| demux_err(format!("无法打开文件/Cannot open file: {e}")))?;
+    let mut reader = BufReader::new(file);
+    match container {
+        Container::Matroska => mkv_tracks(&mut reader),
+        Container::Mp4 => mp4_tracks(&mut reader),
+        Container::MpegTs { packet_size } => ts_tracks(&mut reader, packet_size),
+    }
+}
+
+/// 选择音轨：指定 `--track` 时按轨道号，否则依次优先 TrueHD、E-AC3、AC-3
+/// Pick a track: by id when `--track` is given, otherwise prefer TrueHD, then E-AC3, then AC-3
+pub fn select_track(tracks: &[AudioTrack], requested: Option<u32>) -> Result<&AudioTrack> {
+    if let Some(id) = requested {
+        let track = tracks
+            .iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| demux_err(format!("找不到音轨/No audio track with id {id}")))?;
+        if track.format.is_none() {
+            return Err(demux_err(format!(
+                "音轨不是 AC-3/E-AC3/TrueHD/Track is not AC-3/E-AC3/TrueHD: {}",
+                track.describe()
+            )));
+        }
+        return Ok(track);
+    }
+    [AudioFormat::TrueHD, AudioFormat::Eac3, AudioFormat::Ac3]
+        .iter()
+        .find_map(|f| tracks.iter().find(|t| t.format == Some(*f)))
+        .ok_or_else(|| {
+            demux_err(
+                "容器中没有 AC-3/E-AC3/TrueHD 音轨/No AC-3/E-AC3/TrueHD track in container"
+                    .to_string(),
+            )
+        })
+}
+
+/// 打开输入：容器文件选取音轨并经 GStreamer 解复用，裸码流按文件头检测
+/// Open an input: containers pick a track and demux in GStreamer, raw streams are detected by header
+pub fn open_input(
+    path: &Path,
+    explicit_format: Option<&str>,
+    track: Option<u32>,
+) -> Result<(InputSource, AudioFormat)> {
+    let Some(container) = detect_container(path)? else {
+        if track.is_some() {
+            return Err(demux_err(
+                "--track 仅适用于容器输入/--track only applies to container inputs".to_string(),
+            ));
+        }
+        let (format, offset) = locate_stream(path, explicit_format)?;
+        let source = InputSource {
+            offset,
+            ..InputSource::file(path)
+        };
+        return Ok((source, format));
+    };
+
+    let tracks = list_audio_tracks(path, container)?;
+    let chosen = select_track(&tracks, track)?;
+    let candidates = tracks.iter().filter(|t| t.format.is_some()).count();
+    if track.is_none() && candidates > 1 {
+        say!("容器含多条可解码音轨，可用 --track 选择/Container has several decodable tracks, pick one with --track:");
+        for t in &tracks {
+            say!("  {}", t.describe());
+        }
+    }
+    let format = match explicit_format {
+        Some(f) => detect_format(path, Some(f))?,
+        None => chosen.format.expect("selected track is decodable"),
+    };
+    Ok((
+        InputSource {
+            path: path.to_path_buf(),
+            demux: Some(DemuxTrack {
+                element: container.element(),
+                pad: chosen.pad.clone(),
+                description: chosen.describe(),
+                container,
+                track_id: chosen.id,
+            }),
+            offset: 0,
+        },
+        format,
+    ))
+}
+
+/// 读取所选音轨开头最多 `limit` 字节的码流（去掉容器封装），用于解析码流头
+/// Read up to `limit` bytes from the start of the selected track's bitstream (container framing removed), for header parsing
+pub fn read_track_head(path: &Path, demux: &DemuxTrack, limit: usize) -> Result<Vec<u8>> {
+    let file =
+        File::open(path).map_err(|e| demux_err(format!("无法打开文件/Cannot open file: {e}")))?;
+    let mut reader = BufReader::new(file);
+    let head = match demux.container {
+        Container::Matroska => mkv_track_head(&mut reader, demux.track_id, limit)?,
+        Container::Mp4 => mp4_track_head(&mut reader, demux.track_id, limit)?,
+        Container::MpegTs { packet_size } => {
+            ts_track_head(&mut reader, packet_size, demux.track_id, limit)?
+        }
+    };
+    if head.is_empty() {
+        return Err(demux_err(format!(
+            "音轨中没有数据/No data found for track {}",
+            demux.description
+        )));
+    }
+    Ok(head)
+}
+
+// ---------------------------------------------------------------------------
+// Matroska
+
+const MKV_SEGMENT: u32 = 0x1853_8067;
+const MKV_TRACKS: u32 = 0x1654_AE6B;
+const MKV_CLUSTER: u32 = 0x1F43_B675;
+const MKV_TRACK_ENTRY: u32 = 0xAE;
+const MKV_TRACK_NUMBER: u32 = 0xD7;
+const MKV_TRACK_TYPE: u32 = 0x83;
+const MKV_CODEC_ID: u32 = 0x86;
+const MKV_LANGUAGE: u32 = 0x22_B59C;
+const MKV_LANGUAGE_BCP47: u32 = 0x22_B59D;
+const MKV_BLOCK_GROUP: u32 = 0xA0;
+const MKV_BLOCK: u32 = 0xA1;
+const MKV_SIMPLE_BLOCK: u32 = 0xA3;
+
+/// 读取 EBML 变长整数；`keep_marker` 为 true 时保留长度标记位（用于元素 ID）
+/// Read an EBML variable-length integer; `keep_marker` keeps the length marker (for element IDs)
+fn read_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize, bool)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return None;
+    }
+    let mut value = if keep_marker {
+        u64::from(first)
+    } else {
+        u64::from(first) & (0xFF >> len)
+    };
+    for &b in &data[1..len] {
+        value = (value << 8) | u64::from(b);
+    }
+    // 全 1 的长度表示未知大小 / An all-ones size means unknown size
+    let unknown = !keep_marker && value == (1u64 << (7 * len)) - 1;
+    Some((value, len, unknown))
+}
+
+/// 从文件读取元素头，返回（ID，大小，是否未知大小）/ Read an element header from a file: (id, size, unknown size)
+fn read_element_header<R: Read>(reader: &mut R) -> Option<(u32, u64, bool)> {
+    let mut buf = [0u8; 12];
+    reader.read_exact(&mut buf[..1]).ok()?;
+    let id_len = buf[0].leading_zeros() as usize + 1;
+    if id_len > 4 {
+        return None;
+    }
+    reader.read_exact(&mut buf[1..id_len]).ok()?;
+    let (id, _, _) = read_vint(&buf[..id_len], true)?;
+    reader.read_exact(&mut buf[id_len..=id_len]).ok()?;
+    let size_len = buf[id_len].leading_zeros() as usize + 1;
+    if size_len > 8 {
+        return None;
+    }
+    reader
+        .read_exact(&mut buf[id_len + 1..id_len + size_len])
+        .ok()?;
+    let (size, _, unknown) = read_vint(&buf[id_len..id_len + size_len], false)?;
+    Some((id as u32, size, unknown))
+}
+
+/// 遍历内存中的 EBML 子元素 / Iterate EBML children held in memory
+fn ebml_children(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut out = Vec::new();
+    while let Some((id, id_len, _)) = read_vint(data, true) {
+        let Some((size, size_len, _)) = read_vint(&data[id_len..], false) else {
+            break;
+        };
+        let start = id_len + size_len;
+        let end = start.saturating_add(usize::try_from(size).unwrap_or(usize::MAX));
+        if end > data.len() {
+            break;
+        }
+        out.push((id as u32, &data[start..end]));
+        data = &data[end..];
+    }
+    out
+}
+
+fn ebml_uint(data: &[u8]) -> u64 {
+    data.iter().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+fn ebml_string(data: &[u8]) -> String {
+    String::from_utf8_lossy(data)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn mkv_tracks<R: Read + Seek>(reader: &mut R) -> Result<Vec<AudioTrack>> {
+    let bad = || demux_err("无效的 Matroska 文件/Invalid Matroska file".to_string());
+    // EBML 头 / EBML header
+    let (_, size, _) = read_element_header(reader).ok_or_else(bad)?;
+    reader.seek(SeekFrom::Current(size as i64))?;
+    let (id, _, _) = read_element_header(reader).ok_or_else(bad)?;
+    if id != MKV_SEGMENT {
+        return Err(bad());
+    }
+
+    // 在 Segment 内寻找 Tracks，遇到 Cluster 即停止 / Look for Tracks inside the Segment, stop at the first Cluster
+    while let Some((id, size, unknown)) = read_element_header(reader) {
+        match id {
+            MKV_TRACKS if !unknown => {
+                let mut data = vec![0u8; usize::try_from(size).map_err(|_| bad())?];
+                reader.read_exact(&mut data)?;
+                return Ok(mkv_parse_tracks(&data));
+            }
+            MKV_CLUSTER => break,
+            _ if unknown => break,
+            _ => {
+                reader.seek(SeekFrom::Current(size as i64))?;
+            }
+        }
+    }
+    Err(demux_err(
+        "Matroska 文件中没有 Tracks 元素/No Tracks element in Matroska file".to_string(),
+    ))
+}
+
+/// 依次读取 Cluster 中属于该音轨的 Block 载荷 / Read the payloads of the track's Blocks cluster by cluster
+fn mkv_track_head<R: Read + Seek>(reader: &mut R, track: u32, limit: usize) -> Result<Vec<u8>> {
+    let bad = || demux_err("无效的 Matroska 文件/Invalid Matroska file".to_string());
+    let (_, size, _) = read_element_header(reader).ok_or_else(bad)?;
+    reader.seek(SeekFrom::Current(size as i64))?;
+    let (id, _, _) = read_element_header(reader).ok_or_else(bad)?;
+    if id != MKV_SEGMENT {
+        return Err(bad());
+    }
+
+    // Cluster 与 BlockGroup 的子元素 ID 不与顶层冲突，因此按平铺方式遍历
+    // Cluster and BlockGroup child IDs never clash with top-level ones, so the tree is walked flat
+    let mut out = Vec::new();
+    while out.len() < limit {
+        let Some((id, size, unknown)) = read_element_header(reader) else {
+            break;
+        };
+        match id {
+            MKV_CLUSTER | MKV_BLOCK_GROUP => {}
+            _ if unknown => break,
+            MKV_SIMPLE_BLOCK | MKV_BLOCK => {
+                let mut block = vec![0u8; usize::try_from(size).map_err(|_| bad())?];
+                reader.read_exact(&mut block)?;
+                if let Some(frames) = mkv_block_frames(&block, track) {
+                    out.extend_from_slice(frames);
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(size as i64))?;
+            }
+        }
+    }
+    out.truncate(limit);
+    Ok(out)
+}
+
+/// Block 中属于该音轨的帧数据；连续的帧在去掉 lacing 头后首尾相接
+/// Frame data of a Block for the given track; laced frames are contiguous once the lacing header is removed
+fn mkv_block_frames(block: &[u8], track: u32) -> Option<&[u8]> {
+    let (number, len, _) = read_vint(block, false)?;
+    if number != u64::from(track) {
+        return None;
+    }
+    let flags = *block.get(len + 2)?;
+    let mut pos = len + 3;
+    match flags & 0x06 {
+        0x00 => {}
+        // 固定大小 lacing：仅帧数 / Fixed-size lacing: frame count only
+        0x04 => pos += 1,
+        // Xiph lacing：每个帧长为若干 255 加一个余数 / Xiph lacing: each size is a run of 255s plus a remainder
+        0x02 => {
+            let count = usize::from(*block.get(pos)?);
+            pos += 1;
+            for _ in 0..count {
+                while *block.get(pos)? == 255 {
+                    pos += 1;
+                }
+                pos += 1;
+            }
+        }
+        // EBML lacing：首个帧长与其后的差值均为变长整数 / EBML lacing: the first size and the following deltas are vints
+        _ => {
+            let count = usize::from(*block.get(pos)?);
+            pos += 1;
+            for _ in 0..count {
+                let (_, len, _) = read_vint(block.get(pos..)?, false)?;
+                pos += len;
+            }
+        }
+    }
+    block.get(pos..)
+}
+
+fn mkv_parse_tracks(data: &[u8]) -> Vec<AudioTrack> {
+    let mut tracks = Vec::new();
+    let mut audio_index = 0;
+    for (id, entry) in ebml_children(data) {
+        if id != MKV_TRACK_ENTRY {
+            continue;
+        }
+        let (mut number, mut kind, mut codec, mut language) = (0, 0, String::new(), None);
+        for (id, value) in ebml_children(entry) {
+            match id {
+                MKV_TRACK_NUMBER => number = ebml_uint(value) as u32,
+                MKV_TRACK_TYPE => kind = ebml_uint(value),
+                MKV_CODEC_ID => codec = ebml_string(value),
+                MKV_LANGUAGE | MKV_LANGUAGE_BCP47 => language = Some(ebml_string(value)),
+                _ => {}
+            }
+        }
+        // TrackType 2 为音频 / TrackType 2 is audio
+        if kind != 2 {
+            continue;
+        }
+        let format = match codec.as_str() {
+            "A_EAC3" => Some(AudioFormat::Eac3),
+            "A_AC3" => Some(AudioFormat::Ac3),
+            "A_TRUEHD" => Some(AudioFormat::TrueHD),
+            _ => None,
+        };
+        tracks.push(AudioTrack {
+            id: number,
+            codec,
+            format,
+            language,
+            pad: format!("audio_{audio_index}"),
+        });
+        audio_index += 1;
+    }
+    tracks
+}
+
+// ---------------------------------------------------------------------------
+// MP4 / MOV
+
+/// moov 读入内存的上限 / Upper bound for reading moov into memory
+const MP4_MAX_MOOV: u64 = 64 << 20;
+
+/// 遍历内存中的 ISO BMFF box / Iterate ISO BMFF boxes held in memory
+fn mp4_boxes(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut out = Vec::new();
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let kind = [data[4], data[5], data[6], data[7]];
+        let (header, size) = match size {
+            0 => (8, data.len()),
+            1 if data.len() >= 16 => {
+                let large = u64::from_be_bytes(data[8..16].try_into().unwrap());
+                (16, usize::try_from(large).unwrap_or(usize::MAX))
+            }
+            _ => (8, size),
+        };
+        if size < header || size > data.len() {
+            break;
+        }
+        out.push((kind, &data[header..size]));
+        data = &data[size..];
+    }
+    out
+}
+
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    mp4_boxes(data)
+        .into_iter()
+        .find(|(k, _)| k == kind)
+        .map(|(_, d)| d)
+}
+
+/// 在顶层 box 中寻找 moov（可能位于 mdat 之后）并读入内存
+/// Find moov among top-level boxes (it may follow mdat) and read it into memory
+fn mp4_read_moov<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>> {
+    loop {
+        let mut header = [0u8; 8];
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let size = u64::from(u32::from_be_bytes([
+            header[0], header[1], header[2], header[3],
+        ]));
+        let (header_len, size) = match size {
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large)?;
+                (16, u64::from_be_bytes(large))
+            }
+            0 => break,
+            _ => (8, size),
+        };
+        if size < header_len {
+            break;
+        }
+        let payload = size - header_len;
+        if &header[4..8] == b"moov" {
+            if payload > MP4_MAX_MOOV {
+                break;
+            }
+            let mut data = vec![0u8; payload as usize];
+            reader.read_exact(&mut data)?;
+            return Ok(data);
+        }
+        reader.seek(SeekFrom::Current(payload as i64))?;
+    }
+    Err(demux_err(
+        "MP4 文件中没有 moov/No moov box in MP4 file".to_string(),
+    ))
+}
+
+fn mp4_tracks<R: Read + Seek>(reader: &mut R) -> Result<Vec<AudioTrack>> {
+    Ok(mp4_parse_moov(&mp4_read_moov(reader)?))
+}
+
+/// tkhd 中的 track_ID（版本 0 位于偏移 12，版本 1 位于 20）/ track_ID in tkhd (offset 12 in v0, 20 in v1)
+fn mp4_track_id(trak: &[u8]) -> u32 {
+    mp4_child(trak, b"tkhd")
+        .and_then(|t| {
+            let off = if t.first() == Some(&1) { 20 } else { 12 };
+            t.get(off..off + 4)
+        })
+        .map_or(0, |b| u32::from_be_bytes(b.try_into().unwrap()))
+}
+
+/// full box 中版本/标志之后的大端 u32 表 / Big-endian u32 table following a full box's version/flags and entry count
+fn mp4_u32s(data: &[u8], skip: usize) -> impl Iterator<Item = u32> + '_ {
+    data.get(skip..)
+        .unwrap_or_default()
+        .chunks_exact(4)
+        .map(|c| u32::from_be_bytes(c.try_into().unwrap()))
+}
+
+/// 按样本表（stco/co64、stsc、stsz）依次读取该音轨的块 / Read the track's chunks in order using the sample tables (stco/co64, stsc, stsz)
+fn mp4_track_head<R: Read + Seek>(reader: &mut R, track: u32, limit: usize) -> Result<Vec<u8>> {
+    let moov = mp4_read_moov(reader)?;
+    let stbl = mp4_boxes(&moov)
+        .into_iter()
+        .filter(|(kind, _)| kind == b"trak")
+        .find(|(_, trak)| mp4_track_id(trak) == track)
+        .and_then(|(_, trak)| mp4_child(trak, b"mdia"))
+        .and_then(|m| mp4_child(m, b"minf"))
+        .and_then(|m| mp4_child(m, b"stbl"))
+        .ok_or_else(|| {
+            demux_err(format!(
+                "MP4 中没有音轨的样本表/No sample table for track {track}"
+            ))
+        })?;
+
+    let offsets: Vec<u64> = if let Some(co64) = mp4_child(stbl, b"co64") {
+        co64.get(8..)
+            .unwrap_or_default()
+            .chunks_exact(8)
+            .map(|c| u64::from_be_bytes(c.try_into().unwrap()))
+            .collect()
+    } else {
+        mp4_u32s(mp4_child(stbl, b"stco").unwrap_or_default(), 8)
+            .map(u64::from)
+            .collect()
+    };
+    // stsc：（首个块编号，每块样本数，描述索引）/ stsc: (first chunk, samples per chunk, description index)
+    let runs: Vec<u32> = mp4_u32s(mp4_child(stbl, b"stsc").unwrap_or_default(), 8).collect();
+    let runs: Vec<(u32, u32)> = runs.chunks_exact(3).map(|r| (r[0], r[1])).collect();
+    let stsz = mp4_child(stbl, b"stsz").unwrap_or_default();
+    let fixed_size = mp4_u32s(stsz, 4).next().unwrap_or(0);
+    let sizes: Vec<u32> = mp4_u32s(stsz, 12).collect();
+
+    let mut out = Vec::new();
+    let mut sample = 0usize;
+    for (chunk, &offset) in offsets.iter().enumerate() {
+        if out.len() >= limit {
+            break;
+        }
+        let Some(&(_, per_chunk)) = runs
+            .iter()
+            .rev()
+            .find(|(first, _)| *first as usize <= chunk + 1)
+        else {
+            break;
+        };
+        let per_chunk = per_chunk as usize;
+        let len: u64 = if fixed_size != 0 {
+            u64::from(fixed_size) * per_chunk as u64
+        } else {
+            sizes
+                .iter()
+                .skip(sample)
+                .take(per_chunk)
+                .map(|&s| u64::from(s))
+                .sum()
+        };
+        sample += per_chunk;
+        let len = usize::try_from(len)
+            .unwrap_or(usize::MAX)
+            .min(limit - out.len());
+        reader.seek(SeekFrom::Start(offset))?;
+        let start = out.len();
+        out.resize(start + len, 0);
+        reader.read_exact(&mut out[start..])?;
+    }
+    Ok(out)
+}
+
+fn mp4_parse_moov(moov: &[u8]) -> Vec<AudioTrack> {
+    let mut tracks = Vec::new();
+    let mut audio_index = 0;
+    for (kind, trak) in mp4_boxes(moov) {
+        if &kind != b"trak" {
+            continue;
+        }
+        let Some(mdia) = mp4_child(trak, b"mdia") else {
+            continue;
+        };
+        let is_audio = mp4_child(mdia, b"hdlr").is_some_and(|h| h.get(8..12) == Some(b"soun"));
+        if !is_audio {
+            continue;
+        }
+        let id = mp4_track_id(trak);
+        // mdhd：语言为 3 个 5 位字符 / mdhd: language packed as three 5-bit characters
+        let language = mp4_child(mdia, b"mdhd").and_then(|m| {
+            let off = if m.first() == Some(&1) { 32 } else { 20 };
+            let packed = u16::from_be_bytes(m.get(off..off + 2)?.try_into().unwrap());
+            let chars: String = [10, 5, 0]
+                .iter()
+                .map(|s| char::from(((packed >> s) & 0x1F) as u8 + 0x60))
+                .collect();
+            chars
+                .chars()
+                .all(|c| c.is_ascii_lowercase())
+                .then_some(chars)
+        });
+        // stsd 第一个条目的 4CC / 4CC of the first stsd entry
+        let codec = mp4_child(mdia, b"minf")
+            .and_then(|m| mp4_child(m, b"stbl"))
+            .and_then(|s| mp4_child(s, b"stsd"))
+            .and_then(|s| s.get(12..16))
+            .map(|c| String::from_utf8_lossy(c).to_string())
+            .unwrap_or_default();
+        let format = match codec.as_str() {
+            "ec-3" => Some(AudioFormat::Eac3),
+            "ac-3" => Some(AudioFormat::Ac3),
+            "mlpa" => Some(AudioFormat::TrueHD),
+            _ => None,
+        };
+        tracks.push(AudioTrack {
+            id,
+            codec,
+            format,
+            language,
+            pad: format!("audio_{audio_index}"),
+        });
+        audio_index += 1;
+    }
+    tracks
+}
+
+// ---------------------------------------------------------------------------
+// MPEG-TS / M2TS
+
+/// 寻找 PAT/PMT 时最多读取的包数 / Maximum packets read while looking for PAT/PMT
+const TS_MAX_PACKETS: usize = 20_000;
+
+/// 取出以 payload_unit_start 开头的完整 PSI 段（仅单包段）/ Extract a PSI section starting in this packet (single-packet sections only)
+fn ts_section(packet: &[u8]) -> Option<(u16, &[u8])> {
+    let pid = (u16::from(packet[1] & 0x1F) << 8) | u16::from(packet[2]);
+    let pusi = packet[1] & 0x40 != 0;
+    let adaptation = (packet[3] >> 4) & 0x3;
+    if !pusi || adaptation & 0x1 == 0 {
+        return None;
+    }
+    let mut offset = 4;
+    if adaptation == 0x3 {
+        offset += 1 + usize::from(*packet.get(4)?);
+    }
+    let pointer = usize::from(*packet.get(offset)?);
+    let section = packet.get(offset + 1 + pointer..)?;
+    let length = (usize::from(*section.get(1)? & 0x0F) << 8) | usize::from(*section.get(2)?);
+    // 去掉 CRC32 / Drop the CRC32
+    Some((
+        pid,
+        section
+            .get(..3 + length)?
+            .get(..(3 + length).checked_sub(4)?)?,
+    ))
+}
+
+/// PMT 中的音频流类型 / Audio stream types in a PMT
+fn ts_codec(stream_type: u8, descriptors: &[u8]) -> Option<(String, Option<AudioFormat>)> {
+    let tagged = |tag: u8| {
+        let mut d = descriptors;
+        while d.len() >= 2 {
+            let len = usize::from(d[1]);
+            if d[0] == tag {
+                return true;
+            }
+            d = d.get(2 + len..).unwrap_or_default();
+        }
+        false
+    };
+    let (name, format) = match stream_type {
+        0x81 => ("AC-3", Some(AudioFormat::Ac3)),
+        0x83 => ("TrueHD", Some(AudioFormat::TrueHD)),
+        0x84 | 0x87 | 0xA1 => ("E-AC3", Some(AudioFormat::Eac3)),
+        // DVB：私有 PES 由描述符区分 / DVB: private PES told apart by descriptor
+        0x06 if tagged(0x7A) => ("E-AC3", Some(AudioFormat::Eac3)),
+        0x06 if tagged(0x6A) => ("AC-3", Some(AudioFormat::Ac3)),
+        0x03 | 0x04 => ("MPEG audio", None),
+        0x0F | 0x11 => ("AAC", None),
+        0x80 => ("LPCM", None),
+        0x82 | 0x85 | 0x86 | 0xA2 => ("DTS", None),
+        _ => return None,
+    };
+    Some((name.to_string(), format))
+}
+
+fn ts_language(descriptors: &[u8]) -> Option<String> {
+    let mut d = descriptors;
+    while d.len() >= 2 {
+        let len = usize::from(d[1]);
+        // ISO 639 语言描述符 / ISO 639 language descriptor
+        if d[0] == 0x0A && len >= 3 {
+            return Some(String::from_utf8_lossy(d.get(2..5)?).to_string());
+        }
+        d = d.get(2 + len..)?;
+    }
+    None
+}
+
+fn ts_parse_pmt(program: u16, section: &[u8]) -> Vec<AudioTrack> {
+    let mut tracks = Vec::new();
+    let Some(info_len) = section
+        .get(10..12)
+        .map(|b| (usize::from(b[0] & 0x0F) << 8) | usize::from(b[1]))
+    else {
+        return tracks;
+    };
+    let mut es = section.get(12 + info_len..).unwrap_or_default();
+    while es.len() >= 5 {
+        let stream_type = es[0];
+        let pid = (u16::from(es[1] & 0x1F) << 8) | u16::from(es[2]);
+        let es_len = (usize::from(es[3] & 0x0F) << 8) | usize::from(es[4]);
+        let descriptors = es.get(5..5 + es_len).unwrap_or_default();
+        if let Some((codec, format)) = ts_codec(stream_type, descriptors) {
+            tracks.push(AudioTrack {
+                id: u32::from(pid),
+                codec,
+                format,
+                language: ts_language(descriptors),
+                pad: format!("audio_{program:01x}_{pid:04x}"),
+            });
+        }
+        es = es.get(5 + es_len..).unwrap_or_default();
+    }
+    tracks
+}
+
+fn ts_tracks<R: Read>(reader: &mut R, packet_size: usize) -> Result<Vec<AudioTrack>> {
+    let prefix = packet_size - 188;
+    let mut packet = vec![0u8; packet_size];
+    let mut pmt: Option<(u16, u16)> = None;
+    for _ in 0..TS_MAX_PACKETS {
+        if reader.read_exact(&mut packet).is_err() {
+            break;
+        }
+        let ts = &packet[prefix..];
+        if ts[0] != 0x47 {
+            return Err(demux_err("TS 同步丢失/Lost MPEG-TS sync".to_string()));
+        }
+        let Some((pid, section)) = ts_section(ts) else {
+            continue;
+        };
+        match (pid, pmt) {
+            // PAT：取第一个节目 / PAT: take the first program
+            (0, None) if section.first() == Some(&0x00) => {
+                let mut entries = section.get(8..).unwrap_or_default();
+                while entries.len() >= 4 {
+                    let number = u16::from_be_bytes([entries[0], entries[1]]);
+                    let pmt_pid = (u16::from(entries[2] & 0x1F) << 8) | u16::from(entries[3]);
+                    if number != 0 {
+                        pmt = Some((number, pmt_pid));
+                        break;
+                    }
+                    entries = &entries[4..];
+                }
+            }
+            (pid, Some((program, pmt_pid))) if pid == pmt_pid && section.first() == Some(&0x02) => {
+                return Ok(ts_parse_pmt(program, section));
+            }
+            _ => {}
+        }
+    }
+    Err(demux_err(
+        "未找到 PAT/PMT/No PAT/PMT found in transport stream".to_string(),
+    ))
+}
+
+/// 读取音轨开头时最多检查的包数 / Maximum packets inspected while reading the start of a track
+const TS_HEAD_MAX_PACKETS: usize = 500_000;
+
+/// 拼接该 PID 的 PES 载荷（从第一个 PES 起点开始）/ Concatenate the PID's PES payloads, starting at the first PES start
+fn ts_track_head<R: Read>(
+    reader: &mut R,
+    packet_size: usize,
+    pid: u32,
+    limit: usize,
+) -> Result<Vec<u8>> {
+    let prefix = packet_size - 188;
+    let mut packet = vec![0u8; packet_size];
+    let mut out = Vec::new();
+    let mut started = false;
+    for _ in 0..TS_HEAD_MAX_PACKETS {
+        if out.len() >= limit || reader.read_exact(&mut packet).is_err() {
+            break;
+        }
+        let ts = &packet[prefix..];
+        if ts[0] != 0x47 {
+            return Err(demux_err("TS 同步丢失/Lost MPEG-TS sync".to_string()));
+        }
+        if (u32::from(ts[1] & 0x1F) << 8) | u32::from(ts[2]) != pid {
+            continue;
+        }
+        let adaptation = (ts[3] >> 4) & 0x3;
+        if adaptation & 0x1 == 0 {
+            continue;
+        }
+        let mut offset = 4;
+        if adaptation == 0x3 {
+            offset += 1 + usize::from(ts[4]);
+        }
+        let Some(mut payload) = ts.get(offset..) else {
+            continue;
+        };
+        if ts[1] & 0x40 != 0 {
+            // PES 头：起始码、流 ID、长度、两个标志字节、头数据长度 / PES header: start code, stream id, length, two flag bytes, header data length
+            if payload.len() < 9 || payload[..3] != [0, 0, 1] {
+                continue;
+            }
+            payload = payload
+                .get(9 + usize::from(payload[8])..)
+                .unwrap_or_default();
+            started = true;
+        }
+        if started {
+            out.extend_from_slice(payload);
+        }
+    }
+    out.truncate(limit);
+    Ok(out)
+}
+
+/// 测试用的合成 Matroska 文件 / Synthetic Matroska files for tests
+#[cfg(test)]
+pub(crate) mod test_files {
+    use super::*;
+
+    pub(crate) fn ebml(id: u32, payload: &[u8]) -> Vec<u8> {
+        let id_bytes = id.to_be_bytes();
+        let skip = id_bytes.iter().position(|&b| b != 0).unwrap();
+        let mut out = id_bytes[skip..].to_vec();
+        // 8 字节长度编码 / 8-byte size encoding
+        out.push(0x01);
+        out.extend_from_slice(&(payload.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    pub(crate) fn mkv_track(number: u8, kind: u8, codec: &str, lang: &str) -> Vec<u8> {
+        let mut entry = ebml(MKV_TRACK_NUMBER, &[number]);
+        entry.extend(ebml(MKV_TRACK_TYPE, &[kind]));
+        entry.extend(ebml(MKV_CODEC_ID, codec.as_bytes()));
+        entry.extend(ebml(MKV_LANGUAGE, lang.as_bytes()));
+        ebml(MKV_TRACK_ENTRY, &entry)
+    }
+
+    pub(crate) fn mkv_file() -> Vec<u8> {
+        mkv_file_with(&[0; 16])
+    }
+
+    pub(crate) fn mkv_file_with(cluster: &[u8]) -> Vec<u8> {
+        let mut tracks = mkv_track(1, 1, "V_MPEG4/ISO/AVC", "und");
+        tracks.extend(mkv_track(2, 2, "A_AC3", "eng"));
+        tracks.extend(mkv_track(3, 2, "A_TRUEHD", "eng"));
+        tracks.extend(mkv_track(4, 2, "A_EAC3", "jpn"));
+        let mut segment = ebml(0x1549_A966, &[0; 4]);
+        segment.extend(ebml(MKV_TRACKS, &tracks));
+        segment.extend(ebml(MKV_CLUSTER, cluster));
+        let mut file = ebml(0x1A45_DFA3, &ebml(0x4282, b"matroska"));
+        file.extend(ebml(MKV_SEGMENT, &segment));
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_files::*;
+    use super::*;
+    use crate::probe::test_frames::ac3_frame;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn mp4_trak(id: u32, handler: &[u8; 4], codec: &[u8; 4], tables: &[u8]) -> Vec<u8> {
+        let mut tkhd = vec![0u8; 12];
+        tkhd.extend_from_slice(&id.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 68]);
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0; 13]);
+        // "eng" = 5,14,7
+        let mut mdhd = vec![0u8; 20];
+        mdhd.extend_from_slice(&((5u16 << 10) | (14 << 5) | 7).to_be_bytes());
+        mdhd.extend_from_slice(&[0; 2]);
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(mp4_box(codec, &[0; 28]));
+        let mut stbl = mp4_box(b"stsd", &stsd);
+        stbl.extend_from_slice(tables);
+        let stbl = mp4_box(b"stbl", &stbl);
+        let minf = mp4_box(b"minf", &stbl);
+        let mut mdia = mp4_box(b"mdhd", &mdhd);
+        mdia.extend(mp4_box(b"hdlr", &hdlr));
+        mdia.extend(minf);
+        let mut trak = mp4_box(b"tkhd", &tkhd);
+        trak.extend(mp4_box(b"mdia", &mdia));
+        mp4_box(b"trak", &trak)
+    }
+
+    fn ts_packet(pid: u16, section: &[u8], packet_size: usize) -> Vec<u8> {
+        let mut p = vec![0u8; packet_size - 188];
+        p.extend_from_slice(&[0x47, 0x40 | (pid >> 8) as u8, pid as u8, 0x10, 0x00]);
+        p.extend_from_slice(section);
+        p.resize(packet_size, 0xFF);
+        p
+    }
+
+    fn psi(table_id: u8, id: u16, body: &[u8]) -> Vec<u8> {
+        let length = 5 + body.len() + 4;
+        let mut s = vec![table_id, 0xB0 | (length >> 8) as u8, length as u8];
+        s.extend_from_slice(&id.to_be_bytes());
+        s.extend_from_slice(&[0xC1, 0, 0]);
+        s.extend_from_slice(body);
+        s.extend_from_slice(&[0; 4]);
+        s
+    }
+
+    fn ts_file(packet_size: usize) -> Vec<u8> {
+        let pat = psi(0, 1, &[0x00, 0x01, 0xE1, 0x00]);
+        let mut pmt_body = vec![0xE1, 0x11, 0xF0, 0x00];
+        pmt_body.extend_from_slice(&[0x1B, 0xE1, 0x11, 0xF0, 0x00]);
+        pmt_body.extend_from_slice(&[0x83, 0xE1, 0x00, 0xF0, 0x05, 0x0A, 0x03, b'e', b'n', b'g']);
+        pmt_body.extend_from_slice(&[0x81, 0xE1, 0x01, 0xF0, 0x00]);
+        let pmt = psi(2, 1, &pmt_body);
+        let mut file = ts_packet(0, &pat, packet_size);
+        file.extend(ts_packet(0x100, &pmt, packet_size));
+        file.extend(ts_packet(0x1011, &[], packet_size));
+        file
+    }
+
+    // MKV：列出音轨并默认选择 TrueHD / MKV: lists audio tracks and picks TrueHD by default
+    #[test]
+    fn matroska_tracks_and_default_pick() {
+        let tracks = mkv_tracks(&mut Cursor::new(mkv_file())).unwrap();
+        let ids: Vec<u32> = tracks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [2, 3, 4]);
+        let pick = select_track(&tracks, None).unwrap();
+        assert_eq!(pick.codec, "A_TRUEHD");
+        assert_eq!(pick.pad, "audio_1");
+        assert_eq!(pick.language.as_deref(), Some("eng"));
+        let jpn = select_track(&tracks, Some(4)).unwrap();
+        assert_eq!(
+            (jpn.format, jpn.pad.as_str()),
+            (Some(AudioFormat::Eac3), "audio_2")
+        );
+        assert!(select_track(&tracks, Some(1)).is_err());
+    }
+
+    // MP4：ec-3 音轨及语言解析 / MP4: ec-3 track and language parsing
+    #[test]
+    fn mp4_tracks_skip_video() {
+        let mut moov = mp4_trak(1, b"vide", b"avc1", &[]);
+        moov.extend(mp4_trak(2, b"soun", b"mp4a", &[]));
+        moov.extend(mp4_trak(3, b"soun", b"ec-3", &[]));
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(mp4_box(b"mdat", &[0; 32]));
+        file.extend(mp4_box(b"moov", &moov));
+
+        let tracks = mp4_tracks(&mut Cursor::new(file)).unwrap();
+        assert_eq!(tracks.len(), 2);
+        let pick = select_track(&tracks, None).unwrap();
+        assert_eq!(pick.id, 3);
+        assert_eq!(pick.pad, "audio_1");
+        assert_eq!(pick.language.as_deref(), Some("eng"));
+    }
+
+    // TS 与 M2TS：PAT → PMT → 音频 PID / TS and M2TS: PAT → PMT → audio PIDs
+    #[test]
+    fn transport_stream_tracks() {
+        for packet_size in [188, 192] {
+            let file = ts_file(packet_size);
+            let tracks = ts_tracks(&mut Cursor::new(file), packet_size).unwrap();
+            let ids: Vec<u32> = tracks.iter().map(|t| t.id).collect();
+            assert_eq!(ids, [0x100, 0x101]);
+            let pick = select_track(&tracks, None).unwrap();
+            assert_eq!(pick.format, Some(AudioFormat::TrueHD));
+            assert_eq!(pick.pad, "audio_1_0100");
+            assert_eq!(pick.language.as_deref(), Some("eng"));
+        }
+    }
+
+    /// 带 PES 载荷的 TS 包，用适配域填充到 188 字节 / TS packet with a PES payload, padded to 188 bytes by the adaptation field
+    fn pes_packet(pid: u16, start: bool, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        if start {
+            data.extend_from_slice(&[0, 0, 1, 0xBD, 0, 0, 0x80, 0x80, 5, 0, 0, 0, 0, 0]);
+        }
+        data.extend_from_slice(payload);
+        let stuffing = 184 - 1 - data.len();
+        let mut p = vec![
+            0x47,
+            (u8::from(start) << 6) | (pid >> 8) as u8,
+            pid as u8,
+            0x30,
+        ];
+        p.push(stuffing as u8);
+        if stuffing > 0 {
+            p.push(0);
+            p.resize(5 + stuffing, 0xFF);
+        }
+        p.extend(data);
+        p
+    }
+
+    // 音轨开头的载荷：去掉 Block/lacing、样本表与 PES 封装，只取所选音轨
+    // Track head: Block/lacing, sample-table and PES framing removed, only the selected track kept
+    #[test]
+    fn track_heads_strip_container_framing() {
+        // MKV：无 lacing、Xiph lacing（BlockGroup 内）与固定大小 lacing / MKV: no lacing, Xiph lacing (in a BlockGroup) and fixed-size lacing
+        let mut cluster = ebml(0xE7, &[0]);
+        cluster.extend(ebml(MKV_SIMPLE_BLOCK, &[0x82, 0, 0, 0x80, b'a', b'b']));
+        cluster.extend(ebml(MKV_SIMPLE_BLOCK, &[0x83, 0, 0, 0x80, b'z', b'z']));
+        cluster.extend(ebml(
+            MKV_BLOCK_GROUP,
+            &ebml(MKV_BLOCK, &[0x82, 0, 0, 0x02, 1, 1, b'c', b'd', b'e']),
+        ));
+        cluster.extend(ebml(MKV_SIMPLE_BLOCK, &[0x82, 0, 0, 0x84, 1, b'f', b'g']));
+        let file = mkv_file_with(&cluster);
+        let head = mkv_track_head(&mut Cursor::new(&file), 2, 1024).unwrap();
+        assert_eq!(head, b"abcdefg");
+        assert_eq!(
+            mkv_track_head(&mut Cursor::new(&file), 2, 3).unwrap(),
+            b"abc"
+        );
+
+        // MP4：两个块，每块两个样本，其间夹着其他数据 / MP4: two chunks of two samples with other data between them
+        let mut mdat = b"ab".to_vec();
+        mdat.extend_from_slice(b"cde");
+        mdat.extend_from_slice(b"xxxx");
+        mdat.extend_from_slice(b"fg");
+        mdat.extend_from_slice(b"h");
+        let mdat_start = 16 + 8;
+        let mut stco = vec![0, 0, 0, 0, 0, 0, 0, 2];
+        stco.extend_from_slice(&(mdat_start as u32).to_be_bytes());
+        stco.extend_from_slice(&(mdat_start as u32 + 9).to_be_bytes());
+        let stsc = [[0, 0, 0, 0, 0, 0, 0, 1], [0, 0, 0, 1, 0, 0, 0, 2]].concat();
+        let stsc = [stsc, vec![0, 0, 0, 1]].concat();
+        let mut stsz = vec![0u8; 8];
+        stsz.extend_from_slice(&4u32.to_be_bytes());
+        for size in [2u32, 3, 2, 1] {
+            stsz.extend_from_slice(&size.to_be_bytes());
+        }
+        let mut tables = mp4_box(b"stco", &stco);
+        tables.extend(mp4_box(b"stsc", &stsc));
+        tables.extend(mp4_box(b"stsz", &stsz));
+        let mut file = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        file.extend(mp4_box(b"mdat", &mdat));
+        file.extend(mp4_box(b"moov", &mp4_trak(3, b"soun", b"ec-3", &tables)));
+        let head = mp4_track_head(&mut Cursor::new(&file), 3, 1024).unwrap();
+        assert_eq!(head, b"abcdefgh");
+
+        // TS：PES 起点之前的数据被丢弃，其他 PID 被忽略 / TS: data before the first PES start is dropped and other PIDs are ignored
+        let mut file = ts_file(188);
+        file.extend(pes_packet(0x100, false, b"junk"));
+        file.extend(pes_packet(0x100, true, b"abc"));
+        file.extend(pes_packet(0x101, true, b"zzz"));
+        file.extend(pes_packet(0x100, false, b"def"));
+        let head = ts_track_head(&mut Cursor::new(&file), 188, 0x100, 1024).unwrap();
+        assert_eq!(head, b"abcdef");
+    }
+
+    // auto 模式可从容器音轨读出原生布局 / Auto mode reads the native layout from a container track
+    #[test]
+    fn native_layout_from_matroska_track() {
+        use crate::probe::test_frames::eac3_frame;
+        let mut cluster = Vec::new();
+        for _ in 0..4 {
+            let mut block = vec![0x84, 0, 0, 0x80];
+            block.extend(eac3_frame(0, 0, 1536, None));
+            block.extend(eac3_frame(1, 0, 512, None));
+            cluster.extend(ebml(MKV_SIMPLE_BLOCK, &block));
+        }
+        let file = mkv_file_with(&cluster);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("movie.mkv");
+        std::fs::write(&path, file).unwrap();
+
+        let (source, format) = open_input(&path, None, Some(4)).unwrap();
+        let config = crate::probe::native_config(&source, format).unwrap();
+        assert_eq!(config.name, "7.1");
+    }
+
+    // 容器检测与 open_input 的裸码流回退 / Container detection and open_input's raw-stream fallback
+    #[test]
+    fn detects_containers_and_falls_back_to_raw() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases: [(&str, Vec<u8>, Option<Container>); 4] = [
+            ("a.mkv", mkv_file(), Some(Container::Matroska)),
+            (
+                "a.m2ts",
+                ts_file(192),
+                Some(Container::MpegTs { packet_size: 192 }),
+            ),
+            (
+                "a.ts",
+                ts_file(188),
+                Some(Container::MpegTs { packet_size: 188 }),
+            ),
+            ("a.ac3", (0..4).flat_map(|_| ac3_frame()).collect(), None),
+        ];
+        for (name, bytes, expected) in cases {
+            let path = dir.path().join(name);
+            std::fs::write(&path, bytes).unwrap();
+            assert_eq!(detect_container(&path).unwrap(), expected, "{name}");
+        }
+
+        let (source, format) = open_input(&dir.path().join("a.m2ts"), None, None).unwrap();
+        assert_eq!(format, AudioFormat::TrueHD);
+        let demux = source.demux.unwrap();
+        assert_eq!(
+            (demux.element, demux.pad.as_str()),
+            ("tsdemux", "audio_1_0100")
+        );
+
+        let (source, format) = open_input(&dir.path().join("a.ac3"), None, None).unwrap();
+        assert_eq!((source.demux, format), (None, AudioFormat::Ac3));
+        assert!(open_input(&dir.path().join("a.ac3"), None, Some(2)).is_err());
+    }
+}
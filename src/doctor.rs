@@ -0,0 +1,231 @@
+use crate::decoder::ChildEnv;
+use crate::tools;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 解码管道用到的 GStreamer 元素（与 decoder 中的管道构建保持一致，由测试核对）
+/// GStreamer elements the decode pipelines rely on (kept in step with the pipeline builders in decoder, checked by a test)
+pub(crate) const REQUIRED_ELEMENTS: [&str; 15] = [
+    "filesrc",
+    "fdsrc",
+    "matroskademux",
+    "qtdemux",
+    "tsdemux",
+    "queue",
+    "dlbac3parse",
+    "dlbtruehdparse",
+    "progressreport",
+    "dlbaudiodecbin",
+    "deinterleave",
+    "audioconvert",
+    "wavenc",
+    "filesink",
+    "fdsink",
+];
+
+/// 单项检查结果 / Result of one check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, passed: bool, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            passed,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// 运行全部检查（会打印考虑过的工具位置）/ Run every check (printing the tool locations considered)
+pub fn run_checks(cli_base: Option<&Path>) -> Vec<Check> {
+    let candidates = tools::considered_candidates(cli_base);
+    let picked = candidates
+        .iter()
+        .position(tools::ToolCandidate::is_complete);
+
+    println!("工具位置/Tool locations:");
+    for (i, candidate) in candidates.iter().enumerate() {
+        let mark = if Some(i) == picked {
+            "=>"
+        } else if candidate.is_complete() {
+            " +"
+        } else {
+            "  "
+        };
+        println!(
+            "{mark} [{}] {} | {}",
+            candidate.origin,
+            candidate.gst_launch.display(),
+            candidate.gst_plugins.display()
+        );
+    }
+    println!();
+
+    let mut checks = Vec::new();
+    let Some(found) = picked.map(|i| &candidates[i]) else {
+        checks.push(Check::new(
+            "dolby-tools",
+            false,
+            "未找到完整的工具目录/No complete tool location found",
+        ));
+        return checks;
+    };
+    checks.push(Check::new(
+        "dolby-tools",
+        true,
+        format!("{} ({})", found.origin, found.gst_launch.display()),
+    ));
+
+    let child_env = ChildEnv::new(&found.gst_launch, &found.gst_plugins);
+    checks.push(match &child_env.gst_scanner {
+        Some(scanner) => Check::new("gst-plugin-scanner", true, scanner.display().to_string()),
+        None => Check::new(
+            "gst-plugin-scanner",
+            false,
+            "libexec 下未找到/Not found under libexec",
+        ),
+    });
+
+    let gst_inspect = found.gst_launch.with_file_name("gst-inspect-1.0");
+    for element in REQUIRED_ELEMENTS {
+        checks.push(inspect_element(
+            &gst_inspect,
+            &found.gst_plugins,
+            &child_env,
+            element,
+        ));
+    }
+
+    checks.push(check_flac());
+    checks
+}
+
+/// 用 gst-inspect-1.0 确认元素可加载 / Confirm an element loads via gst-inspect-1.0
+fn inspect_element(
+    gst_inspect: &Path,
+    gst_plugins: &Path,
+    child_env: &ChildEnv,
+    element: &str,
+) -> Check {
+    let mut cmd = Command::new(gst_inspect);
+    cmd.arg("--gst-plugin-path")
+        .arg(gst_plugins)
+        .arg(element)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    child_env.apply(&mut cmd);
+    match cmd.status() {
+        Ok(status) if status.success() => Check::new(element, true, "gst-inspect-1.0 OK"),
+        Ok(status) => Check::new(
+            element,
+            false,
+            format!(
+                "gst-inspect-1.0 未找到该元素/element not found (exit {})",
+                status
+                    .code()
+                    .map_or_else(|| "signal".to_string(), |c| c.to_string())
+            ),
+        ),
+        Err(e) => Check::new(
+            element,
+            false,
+            format!("无法运行/Cannot run {}: {e}", gst_inspect.display()),
+        ),
+    }
+}
+
+/// flac 命令行；启用内置编码器时仅为可选 / flac CLI; optional when the built-in encoder is enabled
+fn check_flac() -> Check {
+    let version = Command::new("flac")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    match version {
+        Some(v) => Check::new("flac", true, v),
+        None if cfg!(feature = "native-flac") => Check::new(
+            "flac",
+            true,
+            "未找到，使用内置编码器/Not found, using the built-in encoder",
+        ),
+        None => Check::new("flac", false, "未找到 flac 命令/flac command not found"),
+    }
+}
+
+/// 渲染通过/失败表 / Render the pass/fail table
+pub fn render_table(checks: &[Check]) -> String {
+    let width = checks
+        .iter()
+        .map(|c| c.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Check".len());
+    let mut out = format!("{:<width$}  Result  Detail\n", "Check");
+    for check in checks {
+        let result = if check.passed { "PASS" } else { "FAIL" };
+        out.push_str(&format!(
+            "{:<width$}  {result:<6}  {}\n",
+            check.name, check.detail
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::decoder::test_scripts::write_script;
+
+    // 伪造的 dolby-tools：gst-inspect-1.0 只认识 wavenc 之外的元素 / Fake dolby-tools whose gst-inspect-1.0 knows every element but wavenc
+    #[cfg(unix)]
+    #[test]
+    fn reports_each_element_and_picked_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("dolby-tools");
+        std::fs::create_dir_all(base.join("gstreamer/bin")).unwrap();
+        std::fs::create_dir_all(base.join("gstreamer/libexec/gstreamer-1.0")).unwrap();
+        std::fs::create_dir_all(base.join("gst-plugins")).unwrap();
+        write_script(&base.join("gstreamer/bin/gst-launch-1.0"), "exit 0");
+        write_script(
+            &base.join("gstreamer/bin/gst-inspect-1.0"),
+            "[ \"$3\" = wavenc ] && exit 1\nexit 0",
+        );
+        std::fs::write(
+            base.join("gstreamer/libexec/gstreamer-1.0/gst-plugin-scanner"),
+            b"",
+        )
+        .unwrap();
+
+        let checks = run_checks(Some(&base));
+        let names: Vec<&str> = checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names[..2], ["dolby-tools", "gst-plugin-scanner"]);
+        assert_eq!(names[2..names.len() - 1], REQUIRED_ELEMENTS);
+        assert_eq!(names.last(), Some(&"flac"));
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect();
+        assert!(failed.starts_with(&["wavenc"]));
+
+        let table = render_table(&checks);
+        assert!(table.contains("wavenc              FAIL"));
+        assert!(table.contains("dlbac3parse         PASS"));
+    }
+
+    // 位置不完整时只报告一项失败 / An incomplete location reports a single failure
+    #[test]
+    fn missing_tools_fail() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = run_checks(Some(dir.path()));
+        assert_eq!(checks.len(), 1);
+        assert!(!checks[0].passed);
+    }
+}
@@ -17,11 +17,26 @@ pub enum DecodeError {
     #[error("GStreamer execution failed: {0}")]
     GStreamerFailed(String),
 
+    #[error("GStreamer delayed linking failed: {0}")]
+    GStreamerDelayedLinking(String),
+
+    #[error("GStreamer plugin missing: {0}")]
+    GStreamerMissingPlugin(String),
+
+    #[error("GStreamer timed out: {0}")]
+    GStreamerTimeout(String),
+
+    #[error("Interrupted")]
+    Interrupted,
+
     #[error("Audio merge failed: {0}")]
     MergeFailed(String),
 
     #[error("FLAC conversion failed: {0}")]
     FlacConversionFailed(String),
+
+    #[error("WavPack conversion failed: {0}")]
+    WavPackConversionFailed(String),
 }
 
 pub type Result<T> = std::result::Result<T, DecodeError>;
@@ -0,0 +1,222 @@
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// 启用 JSON 事件流：stdout 只输出事件，文本信息改写到 stderr
+/// Enable the JSON event stream: stdout carries only events and the text messages move to stderr
+pub fn enable_json() {
+    JSON.store(true, Ordering::SeqCst);
+}
+
+pub fn json_enabled() -> bool {
+    JSON.load(Ordering::SeqCst)
+}
+
+/// 文本信息：通常写到 stdout，JSON 模式下写到 stderr；进度条显示时先将其隐藏
+/// Text message: stdout normally, stderr in JSON mode; an active progress bar is hidden while it prints
+macro_rules! say {
+    ($($arg:tt)*) => {
+        $crate::progress::suspend(|| {
+            if $crate::events::json_enabled() {
+                eprintln!($($arg)*);
+            } else {
+                println!($($arg)*);
+            }
+        })
+    };
+}
+pub(crate) use say;
+
+/// 批处理中单个文件的结果 / Outcome of one file in the batch summary
+#[derive(Debug, Serialize)]
+pub struct FileOutcome<'a> {
+    pub input: &'a Path,
+    pub status: &'a str,
+    /// 失败原因，仅 `failed` 时有值 / Failure reason, only set when `failed`
+    pub error: Option<&'a str>,
+}
+
+/// `--json` 模式下每行一个的事件；`event` 字段为类型名，字段名保持稳定
+/// One event per line in `--json` mode; the `event` field names the type and field names are stable
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    /// 选定的解码后端与工具路径 / Selected decoding backend and tool paths
+    Tools {
+        backend: &'a str,
+        gst_launch: Option<&'a Path>,
+        gst_plugins: Option<&'a Path>,
+    },
+    /// 找不到解码工具 / The decoding tools could not be found
+    ToolsFailed {
+        error: String,
+    },
+    FileStart {
+        index: usize,
+        total: usize,
+        input: &'a Path,
+    },
+    Format {
+        input: &'a Path,
+        /// 与 --format 取值一致 / Matches the --format values
+        format: &'a str,
+        track: Option<&'a str>,
+        offset: u64,
+    },
+    Layout {
+        input: &'a Path,
+        layout: &'a str,
+        channels: usize,
+    },
+    /// `channel` 从 1 开始 / `channel` is 1-based
+    DecodeStart {
+        input: &'a Path,
+        channel: usize,
+        name: &'a str,
+        output: &'a Path,
+    },
+    DecodeDone {
+        input: &'a Path,
+        channel: usize,
+        name: &'a str,
+        output: &'a Path,
+    },
+    DecodeFailed {
+        input: &'a Path,
+        channel: usize,
+        name: &'a str,
+        output: &'a Path,
+        error: String,
+    },
+    /// auto 模式试探出的声道数 / Channel count found by auto-mode trial decoding
+    ChannelsDetected {
+        input: &'a Path,
+        channels: usize,
+    },
+    /// 合并完成；流式模式下 `inputs` 为空 / Merge finished; `inputs` is empty in stream mode
+    Merge {
+        input: &'a Path,
+        inputs: &'a [std::path::PathBuf],
+        output: &'a Path,
+        streamed: bool,
+    },
+    /// `codec` 为 flac、wavpack 或 stems / `codec` is flac, wavpack or stems
+    EncodeDone {
+        codec: &'a str,
+        input: &'a Path,
+        output: &'a Path,
+    },
+    EncodeFailed {
+        codec: &'a str,
+        input: &'a Path,
+        error: String,
+    },
+    Adm {
+        output: &'a Path,
+    },
+    /// 删除的中间文件 / A removed intermediate file
+    Cleanup {
+        path: &'a Path,
+    },
+    Warning {
+        input: Option<&'a Path>,
+        message: String,
+    },
+    FileDone {
+        input: &'a Path,
+    },
+    /// 已完成而跳过（--skip-existing 或 --resume）/ Skipped as already done (--skip-existing or --resume)
+    FileSkipped {
+        input: &'a Path,
+    },
+    FileFailed {
+        input: &'a Path,
+        error: String,
+    },
+    Summary {
+        total: usize,
+        completed: usize,
+        failed: usize,
+        skipped: usize,
+        interrupted: bool,
+        files: Vec<FileOutcome<'a>>,
+    },
+}
+
+/// 序列化为一行 JSON / Serialize to one JSON line
+fn to_line(event: &Event) -> Option<String> {
+    match serde_json::to_string(event) {
+        Ok(line) => Some(line),
+        Err(e) => {
+            log::warn!("无法序列化事件/Failed to serialize event: {e}");
+            None
+        }
+    }
+}
+
+/// JSON 模式下向 stdout 写出一个事件；否则不做任何事
+/// Write one event to stdout in JSON mode; a no-op otherwise
+pub fn emit(event: &Event) {
+    if !json_enabled() {
+        return;
+    }
+    if let Some(line) = to_line(event) {
+        // 整行一次写入，避免并行声道的事件交错 / Write the whole line at once so parallel channels never interleave
+        let mut out = std::io::stdout().lock();
+        let _ = writeln!(out, "{line}");
+        let _ = out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // 事件名与字段名是对外契约 / Event and field names are part of the public contract
+    #[test]
+    fn events_serialize_with_stable_names() {
+        let input = PathBuf::from("movie.ec3");
+        let output = PathBuf::from("movie.01_L.wav");
+        let line = to_line(&Event::DecodeDone {
+            input: &input,
+            channel: 1,
+            name: "L",
+            output: &output,
+        })
+        .unwrap();
+        assert_eq!(
+            line,
+            r#"{"event":"decode_done","input":"movie.ec3","channel":1,"name":"L","output":"movie.01_L.wav"}"#
+        );
+
+        let line = to_line(&Event::Summary {
+            total: 2,
+            completed: 1,
+            failed: 0,
+            skipped: 1,
+            interrupted: false,
+            files: vec![
+                FileOutcome {
+                    input: &input,
+                    status: "done",
+                    error: None,
+                },
+                FileOutcome {
+                    input: &output,
+                    status: "skipped",
+                    error: None,
+                },
+            ],
+        })
+        .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["event"], "summary");
+        assert_eq!(value["completed"], 1);
+        assert_eq!(value["skipped"], 1);
+        assert_eq!(value["files"][1]["status"], "skipped");
+    }
+}
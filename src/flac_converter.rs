@@ -1,6 +1,7 @@
 use crate::channels::ChannelConfig;
 use crate::error::{DecodeError, Result};
 use std::path::Path;
+#[cfg(not(feature = "native-flac"))]
 use std::process::Command;
 
 /// 验证是否可以转码为 FLAC / Verify if conversion to FLAC is possible
@@ -15,6 +16,7 @@ pub fn check_flac_compatibility(channels: u16) -> Result<()> {
 }
 
 /// 检查系统是否安装了 flac 命令行工具 / Check if flac CLI tool is available
+#[cfg(not(feature = "native-flac"))]
 fn check_flac_command() -> Result<()> {
     match Command::new("flac").arg("--version").output() {
         Ok(output) if output.status.success() => Ok(()),
@@ -25,19 +27,43 @@ fn check_flac_command() -> Result<()> {
     }
 }
 
+/// 将 [-1.0, 1.0] 的浮点样本转换为 24-bit 整数（越界先 clamp）
+/// Convert a float sample in [-1.0, 1.0] to a 24-bit integer (clamping out-of-range values first)
+pub(crate) fn to_24bit(sample: f32) -> i32 {
+    // 范围：-8388607 到 8388607 / Range: -8388607 to 8388607
+    (sample.clamp(-1.0, 1.0) * 8_388_607.0) as i32
+}
+
+/// FLAC 的 Vorbis 注释：声道布局（如已知）与来源说明
+/// Vorbis comments for FLAC: the channel layout (when known) and a provenance note
+fn flac_tags(channel_config: Option<&ChannelConfig>) -> Vec<String> {
+    let mut tags = Vec::new();
+    if let Some(config) = channel_config {
+        if config.name != "auto" && !config.names.is_empty() {
+            // 记录实际的声道布局，例如 "L R C LFE Ls Rs"（源自杜比）
+            // Record actual channel layout, e.g. "L R C LFE Ls Rs" (sourced from Dolby)
+            let layout_str = config.names.join(" ");
+            tags.push(format!("CHANNEL_LAYOUT={layout_str} (Sourced from Dolby)"));
+        }
+    }
+    tags.push("COMMENT=Converted by MacinConvert-Atmos-Tool".to_string());
+    tags
+}
+
 /// 将 32-bit Float WAV 转换为 24-bit Integer WAV
 /// Convert 32-bit Float WAV to 24-bit Integer WAV
+#[cfg(not(feature = "native-flac"))]
 fn convert_to_24bit_wav(input_path: &Path, output_path: &Path) -> Result<()> {
-    let reader = hound::WavReader::open(input_path).map_err(|e| {
+    let read_err = |e: std::io::Error| {
         DecodeError::FlacConversionFailed(format!("无法读取 WAV 文件 / Cannot read WAV file: {e}"))
-    })?;
-
-    let spec = reader.spec();
+    };
+    // 支持 RF64/BW64 输入 / RF64/BW64 input is supported
+    let mut reader = crate::wav::WavReader::open(input_path).map_err(read_err)?;
 
     // 创建输出规格（24-bit PCM）/ Create output spec (24-bit PCM)
     let output_spec = hound::WavSpec {
-        channels: spec.channels,
-        sample_rate: spec.sample_rate,
+        channels: reader.channels(),
+        sample_rate: reader.sample_rate(),
         bits_per_sample: 24,
         sample_format: hound::SampleFormat::Int,
     };
@@ -48,29 +74,17 @@ fn convert_to_24bit_wav(input_path: &Path, output_path: &Path) -> Result<()> {
         ))
     })?;
 
-    // 读取 32-bit Float 样本并转换为 24-bit Integer
-    // Read 32-bit Float samples and convert to 24-bit Integer
-    let samples: Vec<f32> = reader
-        .into_samples::<f32>()
-        .collect::<std::result::Result<Vec<_>, _>>()
-        .map_err(|e| {
-            DecodeError::FlacConversionFailed(format!(
-                "读取 WAV 样本失败 / Failed to read WAV samples: {e}"
-            ))
-        })?;
-
-    for sample in samples {
-        // 限制在 [-1.0, 1.0] 范围内 / Clamp to [-1.0, 1.0]
-        let clamped = sample.clamp(-1.0, 1.0);
-        // 转换为 24-bit（用 32-bit 整数表示）/ Convert to 24-bit (represented as 32-bit int)
-        // 范围：-8388608 到 8388607 / Range: -8388608 to 8388607
-        let sample_i32 = (clamped * 8388607.0) as i32;
-
-        writer.write_sample(sample_i32).map_err(|e| {
-            DecodeError::FlacConversionFailed(format!(
-                "写入 WAV 样本失败 / Failed to write WAV sample: {e}"
-            ))
-        })?;
+    // 分块读取 32-bit Float 样本并转换为 24-bit Integer
+    // Read 32-bit Float samples in chunks and convert to 24-bit Integer
+    let mut samples = Vec::new();
+    while reader.read_frames(&mut samples, 8192).map_err(read_err)? > 0 {
+        for &sample in &samples {
+            writer.write_sample(to_24bit(sample)).map_err(|e| {
+                DecodeError::FlacConversionFailed(format!(
+                    "写入 WAV 样本失败 / Failed to write WAV sample: {e}"
+                ))
+            })?;
+        }
     }
 
     writer.finalize().map_err(|e| {
@@ -87,6 +101,7 @@ pub fn convert_batch(
     wav_path: &Path,
     flac_path: &Path,
     channel_config: Option<&ChannelConfig>,
+    level: u8,
 ) -> Result<()> {
     log::info!(
         "开始转码为 FLAC / Starting FLAC conversion: {} → {}",
@@ -94,7 +109,7 @@ pub fn convert_batch(
         flac_path.display()
     );
 
-    wav_to_flac_with_config(wav_path, flac_path, channel_config)?;
+    wav_to_flac_with_config(wav_path, flac_path, channel_config, level)?;
 
     log::info!(
         "FLAC 转码完成 / FLAC conversion completed: {}",
@@ -104,39 +119,96 @@ pub fn convert_batch(
 }
 
 /// 将 WAV 文件转码为 FLAC，带声道配置信息 / Convert WAV to FLAC with channel configuration
+/// `level` 为压缩等级 0–8 / `level` is the compression level 0–8
 fn wav_to_flac_with_config(
     wav_path: &Path,
     flac_path: &Path,
     channel_config: Option<&ChannelConfig>,
+    level: u8,
 ) -> Result<()> {
-    // 打开 WAV 文件验证格式 / Open WAV file to verify format
-    let wav_reader = hound::WavReader::open(wav_path).map_err(|e| {
+    // 打开 WAV 文件验证格式（支持 RF64/BW64）/ Open WAV file to verify format (RF64/BW64 supported)
+    let wav_reader = crate::wav::WavReader::open(wav_path).map_err(|e| {
         DecodeError::FlacConversionFailed(format!(
             "无法打开 WAV 文件 / Cannot open WAV file: {}: {e}",
             wav_path.display()
         ))
     })?;
 
-    let wav_spec = wav_reader.spec();
-
     // 验证 WAV 格式 / Verify WAV format
-    if wav_spec.sample_rate != 48000 {
+    if wav_reader.sample_rate() != 48000 {
         return Err(DecodeError::FlacConversionFailed(format!(
             "只支持 48kHz 采样率，但 WAV 是 {} Hz / Only 48kHz supported, but WAV is {} Hz",
-            wav_spec.sample_rate, wav_spec.sample_rate
+            wav_reader.sample_rate(),
+            wav_reader.sample_rate()
         )));
     }
 
-    if wav_spec.bits_per_sample != 32 {
+    if wav_reader.bits_per_sample() != 32 || !wav_reader.is_float() {
         return Err(DecodeError::FlacConversionFailed(format!(
-            "只支持 32-bit 采样位深，但 WAV 是 {} bit / Only 32-bit supported, but WAV is {} bit",
-            wav_spec.bits_per_sample, wav_spec.bits_per_sample
+            "只支持 32-bit Float 采样，但 WAV 是 {} bit / Only 32-bit float supported, but WAV is {} bit",
+            wav_reader.bits_per_sample(),
+            wav_reader.bits_per_sample()
         )));
     }
 
     // 检查声道数限制 / Check channel limit
-    check_flac_compatibility(wav_spec.channels)?;
+    check_flac_compatibility(wav_reader.channels())?;
+
+    let tags = flac_tags(channel_config);
+    #[cfg(feature = "native-flac")]
+    let result = encode_native(wav_reader, flac_path, &tags, level);
+    #[cfg(not(feature = "native-flac"))]
+    let result = encode_external(wav_path, flac_path, &tags, level);
 
+    if result.is_err() {
+        let _ = std::fs::remove_file(flac_path);
+    }
+    result
+}
+
+/// 内置编码器：直接读取浮点样本编码为 24-bit FLAC，无需临时文件
+/// Built-in encoder: reads float samples directly into a 24-bit FLAC, no temporary file needed
+#[cfg(feature = "native-flac")]
+fn encode_native(
+    mut wav_reader: crate::wav::WavReader,
+    flac_path: &Path,
+    tags: &[String],
+    level: u8,
+) -> Result<()> {
+    const READ_FRAMES: usize = 8192;
+    let flac_err = |e: std::io::Error| {
+        DecodeError::FlacConversionFailed(format!("FLAC 编码失败/FLAC encoding failed: {e}"))
+    };
+
+    let file = std::fs::File::create(flac_path).map_err(flac_err)?;
+    let mut encoder = crate::flac_encoder::FlacEncoder::new(
+        std::io::BufWriter::new(file),
+        wav_reader.channels(),
+        wav_reader.sample_rate(),
+        24,
+        level,
+        tags,
+    )
+    .map_err(flac_err)?;
+
+    let mut floats = Vec::new();
+    let mut ints = Vec::new();
+    while wav_reader
+        .read_frames(&mut floats, READ_FRAMES)
+        .map_err(flac_err)?
+        > 0
+    {
+        ints.clear();
+        ints.extend(floats.iter().map(|&s| to_24bit(s)));
+        encoder.write_interleaved(&ints).map_err(flac_err)?;
+    }
+    encoder.finish().map_err(flac_err)?;
+    Ok(())
+}
+
+/// 外部 flac 命令：先转为 24-bit 临时 WAV 再编码 / External flac command: convert to a temporary 24-bit WAV first, then encode
+#[cfg(not(feature = "native-flac"))]
+fn encode_external(wav_path: &Path, flac_path: &Path, tags: &[String], level: u8) -> Result<()> {
     // 检查 flac 命令是否可用 / Check if flac command is available
     check_flac_command()?;
 
@@ -146,28 +218,15 @@ fn wav_to_flac_with_config(
 
     convert_to_24bit_wav(wav_path, &temp_wav_path)?;
 
-    // 使用 flac 命令行工具进行转码，最大压缩率，并添加声道布局元数据
-    // Use flac CLI with maximum compression and add channel layout metadata
+    // 使用 flac 命令行工具进行转码，并添加声道布局元数据
+    // Use the flac CLI and add channel layout metadata
     let mut cmd = Command::new("flac");
-    cmd.arg("-8") // 最大压缩率 / Maximum compression
+    cmd.arg(format!("-{}", level.min(8))) // 压缩等级 / Compression level
         .arg("--silent"); // 静默输出 / Silent output
-
-    // 添加实际的声道布局名称（如果可用）/ Add actual channel layout names if available
-    if let Some(config) = channel_config {
-        if config.name != "auto" && !config.names.is_empty() {
-            // 记录实际的声道布局，例如 "L R C LFE Ls Rs"（源自杜比）
-            // Record actual channel layout, e.g. "L R C LFE Ls Rs" (sourced from Dolby)
-            let layout_str = config.names.join(" ");
-            cmd.arg("--tag")
-                .arg(format!("CHANNEL_LAYOUT={layout_str} (Sourced from Dolby)"));
-        }
+    for tag in tags {
+        cmd.arg("--tag").arg(tag);
     }
-
-    cmd.arg("--tag")
-        .arg("COMMENT=Converted by MacinConvert-Atmos-Tool")
-        .arg("-o")
-        .arg(flac_path)
-        .arg(&temp_wav_path);
+    cmd.arg("-o").arg(flac_path).arg(&temp_wav_path);
 
     let output = cmd.output().map_err(|e| {
         DecodeError::FlacConversionFailed(format!(
@@ -217,6 +276,7 @@ mod tests {
     }
 
     // 32-bit Float → 24-bit Int 转换：clamp、位深、样本数 / Conversion: clamp, bit depth, count
+    #[cfg(not(feature = "native-flac"))]
     #[test]
     fn convert_to_24bit_clamps_and_sets_format() {
         let dir = tempfile::tempdir().unwrap();
@@ -245,7 +305,7 @@ mod tests {
         let wav = dir.path().join("a.wav");
         let flac = dir.path().join("a.flac");
         write_f32_wav(&wav, &[0.0, 0.1], 44100, 1);
-        assert!(wav_to_flac_with_config(&wav, &flac, None).is_err());
+        assert!(wav_to_flac_with_config(&wav, &flac, None, 8).is_err());
     }
 
     // 前置校验：非 32-bit 位深报错 / Pre-check: non-32-bit depth errors
@@ -265,7 +325,7 @@ mod tests {
         w.write_sample(0_i16).unwrap();
         w.write_sample(1_i16).unwrap();
         w.finalize().unwrap();
-        assert!(wav_to_flac_with_config(&wav, &flac, None).is_err());
+        assert!(wav_to_flac_with_config(&wav, &flac, None, 8).is_err());
     }
 
     // 前置校验：声道数 >8 报错（在调用 flac 之前）/ Pre-check: >8 channels errors before flac
@@ -276,6 +336,38 @@ mod tests {
         let flac = dir.path().join("a.flac");
         // 9 声道、2 帧、48kHz、32-bit / 9ch, 2 frames, 48kHz, 32-bit
         write_f32_wav(&wav, &[0.0; 18], 48000, 9);
-        assert!(wav_to_flac_with_config(&wav, &flac, None).is_err());
+        assert!(wav_to_flac_with_config(&wav, &flac, None, 8).is_err());
+    }
+
+    // 内置编码器：RF64 输入逐位往返，并写入声道布局标签 / Built-in encoder: bit-exact round trip from RF64 input with the layout tag
+    #[cfg(feature = "native-flac")]
+    #[test]
+    fn native_flac_round_trips_and_tags_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let wav = dir.path().join("a.wav");
+        let flac = dir.path().join("a.flac");
+        let samples: Vec<f32> = (0..6000)
+            .map(|i| ((i as f32) * 0.01).sin() * 0.8)
+            .chain([1.5, -2.0])
+            .collect();
+        let mut w = crate::wav::WavWriter::create(&wav, 2, 48000, 0x3).unwrap();
+        for s in &samples {
+            w.write_sample(*s).unwrap();
+        }
+        w.finalize().unwrap();
+        crate::wav::convert_to_bw64(&wav).unwrap();
+
+        let config = crate::channels::get_config("2.0").unwrap();
+        convert_batch(&wav, &flac, Some(&config), 5).unwrap();
+
+        let mut reader = claxon::FlacReader::open(&flac).unwrap();
+        assert_eq!(
+            reader.get_tag("CHANNEL_LAYOUT").next(),
+            Some("L R (Sourced from Dolby)")
+        );
+        assert_eq!(reader.streaminfo().bits_per_sample, 24);
+        let decoded: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        let expected: Vec<i32> = samples.iter().map(|&s| to_24bit(s)).collect();
+        assert_eq!(decoded, expected);
     }
 }
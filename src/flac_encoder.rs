@@ -0,0 +1,618 @@
+//! 纯 Rust FLAC 编码器：固定预测器 + Rice 残差编码，可选立体声去相关
+//! Pure-Rust FLAC encoder: fixed predictors + Rice-coded residuals, with optional stereo decorrelation
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// 压缩等级对应的参数 / Parameters derived from a compression level
+#[derive(Debug, Clone, Copy)]
+struct LevelParams {
+    block_size: usize,
+    max_fixed_order: usize,
+    max_partition_order: u32,
+    stereo_decorrelation: bool,
+}
+
+impl LevelParams {
+    /// 0（最快）到 8（最小），与 flac 命令行的等级大致对应 / 0 (fastest) to 8 (smallest), roughly matching the flac CLI levels
+    fn for_level(level: u8) -> Self {
+        let level = level.min(8);
+        Self {
+            block_size: if level < 3 { 1152 } else { 4096 },
+            max_fixed_order: if level == 0 { 2 } else { 4 },
+            max_partition_order: match level {
+                0..=2 => 3,
+                3..=5 => 5,
+                _ => u32::from(level),
+            },
+            stereo_decorrelation: level >= 1,
+        }
+    }
+}
+
+/// 最高 Rice 参数（4 位参数编码，15 为转义）/ Highest Rice parameter (4-bit parameter coding, 15 is the escape)
+const MAX_RICE_PARAM: u32 = 14;
+
+/// 写入 FLAC 流；样本为有符号整数，位深 4–24
+/// Writes a FLAC stream; samples are signed integers at 4–24 bits
+pub struct FlacEncoder<W: Write + Seek> {
+    out: W,
+    channels: usize,
+    sample_rate: u32,
+    bits_per_sample: u32,
+    params: LevelParams,
+    /// 待编码的交错样本 / Interleaved samples waiting to be encoded
+    pending: Vec<i32>,
+    frame_number: u64,
+    total_frames: u64,
+    min_frame_size: u32,
+    max_frame_size: u32,
+    md5: md5::Context,
+}
+
+impl<W: Write + Seek> FlacEncoder<W> {
+    /// 写入流头部与元数据；`tags` 为 Vorbis 注释（`KEY=value`）
+    /// Write the stream marker and metadata; `tags` are Vorbis comments (`KEY=value`)
+    pub fn new(
+        mut out: W,
+        channels: u16,
+        sample_rate: u32,
+        bits_per_sample: u32,
+        level: u8,
+        tags: &[String],
+    ) -> io::Result<Self> {
+        if !(1..=8).contains(&channels) || !(4..=24).contains(&bits_per_sample) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "FLAC 仅支持 1–8 声道、4–24 位/FLAC supports 1–8 channels at 4–24 bits",
+            ));
+        }
+        let params = LevelParams::for_level(level);
+
+        out.write_all(b"fLaC")?;
+        // STREAMINFO 先写占位，结束时回填 / STREAMINFO is a placeholder, back-filled on finish
+        out.write_all(&[0x00, 0x00, 0x00, 34])?;
+        out.write_all(&[0u8; 34])?;
+
+        let vendor = concat!("MacinConvert-Atmos-Tool ", env!("CARGO_PKG_VERSION"));
+        let mut comment = Vec::new();
+        comment.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+        comment.extend_from_slice(vendor.as_bytes());
+        comment.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+        for tag in tags {
+            comment.extend_from_slice(&(tag.len() as u32).to_le_bytes());
+            comment.extend_from_slice(tag.as_bytes());
+        }
+        // 最后一个元数据块：VORBIS_COMMENT（类型 4）/ Last metadata block: VORBIS_COMMENT (type 4)
+        let len = comment.len() as u32;
+        out.write_all(&[0x80 | 4, (len >> 16) as u8, (len >> 8) as u8, len as u8])?;
+        out.write_all(&comment)?;
+
+        Ok(Self {
+            out,
+            channels: usize::from(channels),
+            sample_rate,
+            bits_per_sample,
+            params,
+            pending: Vec::with_capacity(params.block_size * usize::from(channels)),
+            frame_number: 0,
+            total_frames: 0,
+            min_frame_size: u32::MAX,
+            max_frame_size: 0,
+            md5: md5::Context::new(),
+        })
+    }
+
+    /// 写入交错样本（长度须为声道数的整数倍）/ Write interleaved samples (length must be a multiple of the channel count)
+    pub fn write_interleaved(&mut self, samples: &[i32]) -> io::Result<()> {
+        let block_len = self.params.block_size * self.channels;
+        for chunk in samples.chunks(block_len) {
+            let room = block_len - self.pending.len();
+            let take = room.min(chunk.len());
+            self.pending.extend_from_slice(&chunk[..take]);
+            if self.pending.len() == block_len {
+                self.flush_block()?;
+            }
+            self.pending.extend_from_slice(&chunk[take..]);
+        }
+        Ok(())
+    }
+
+    /// 编码剩余样本并回填 STREAMINFO / Encode remaining samples and back-fill STREAMINFO
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            self.flush_block()?;
+        }
+        let mut info = BitWriter::default();
+        info.write(self.params.block_size as u64, 16);
+        info.write(self.params.block_size as u64, 16);
+        let (min, max) = if self.frame_number == 0 {
+            (0, 0)
+        } else {
+            (self.min_frame_size, self.max_frame_size)
+        };
+        info.write(u64::from(min), 24);
+        info.write(u64::from(max), 24);
+        info.write(u64::from(self.sample_rate), 20);
+        info.write(self.channels as u64 - 1, 3);
+        info.write(u64::from(self.bits_per_sample) - 1, 5);
+        info.write(self.total_frames, 36);
+        let mut bytes = info.into_bytes();
+        bytes.extend_from_slice(&self.md5.compute().0);
+
+        self.out.seek(SeekFrom::Start(8))?;
+        self.out.write_all(&bytes)?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        let frames = self.pending.len() / self.channels;
+        let bytes_per_sample = self.bits_per_sample.div_ceil(8) as usize;
+        for &s in &self.pending {
+            self.md5.consume(&s.to_le_bytes()[..bytes_per_sample]);
+        }
+
+        let mut channels: Vec<Vec<i64>> = (0..self.channels)
+            .map(|ch| {
+                self.pending[ch..]
+                    .iter()
+                    .step_by(self.channels)
+                    .map(|&s| i64::from(s))
+                    .collect()
+            })
+            .collect();
+
+        let bits = self.bits_per_sample;
+        // 立体声：在 独立 / 左-侧 / 侧-右 / 中-侧 中取估计最小者
+        // Stereo: pick the smallest estimate among independent / left-side / side-right / mid-side
+        let mut assignment = self.channels as u64 - 1;
+        let mut subframes = Vec::with_capacity(self.channels);
+        if self.channels == 2 && self.params.stereo_decorrelation {
+            let (left, right) = (&channels[0], &channels[1]);
+            let side: Vec<i64> = left.iter().zip(right).map(|(l, r)| l - r).collect();
+            let mid: Vec<i64> = left.iter().zip(right).map(|(l, r)| (l + r) >> 1).collect();
+            let l = Subframe::choose(left, bits, &self.params);
+            let r = Subframe::choose(right, bits, &self.params);
+            let s = Subframe::choose(&side, bits + 1, &self.params);
+            let m = Subframe::choose(&mid, bits, &self.params);
+            let options = [
+                (l.bits + r.bits, 1u64),
+                (l.bits + s.bits, 0b1000),
+                (s.bits + r.bits, 0b1001),
+                (m.bits + s.bits, 0b1010),
+            ];
+            let (_, best) = options.iter().min_by_key(|(b, _)| *b).copied().unwrap();
+            assignment = best;
+            match best {
+                0b1000 => subframes.extend([(l, left.clone(), bits), (s, side, bits + 1)]),
+                0b1001 => subframes.extend([(s, side, bits + 1), (r, right.clone(), bits)]),
+                0b1010 => subframes.extend([(m, mid, bits), (s, side, bits + 1)]),
+                _ => subframes.extend([(l, left.clone(), bits), (r, right.clone(), bits)]),
+            }
+        } else {
+            for samples in channels.drain(..) {
+                let choice = Subframe::choose(&samples, bits, &self.params);
+                subframes.push((choice, samples, bits));
+            }
+        }
+
+        let mut w = BitWriter::default();
+        w.write(0b1111_1111_1111_1000, 16); // 同步码，固定块长 / sync code, fixed block size
+        w.write(0b0111, 4); // 块长取自帧头末尾 16 位 / block size from a 16-bit field at the end of the header
+        w.write(sample_rate_code(self.sample_rate), 4);
+        w.write(assignment, 4);
+        w.write(sample_size_code(bits), 3);
+        w.write(0, 1);
+        write_utf8_number(&mut w, self.frame_number);
+        w.write(frames as u64 - 1, 16);
+        let crc8 = crc8(w.bytes());
+        w.write(u64::from(crc8), 8);
+
+        for (choice, samples, sample_bits) in &subframes {
+            choice.write(&mut w, samples, *sample_bits);
+        }
+        w.align();
+        let crc16 = crc16(w.bytes());
+        w.write(u64::from(crc16), 16);
+
+        let frame = w.into_bytes();
+        self.out.write_all(&frame)?;
+        self.min_frame_size = self.min_frame_size.min(frame.len() as u32);
+        self.max_frame_size = self.max_frame_size.max(frame.len() as u32);
+        self.frame_number += 1;
+        self.total_frames += frames as u64;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// 子帧编码方式 / Subframe coding choice
+#[derive(Debug, Clone, Copy)]
+enum Method {
+    Constant,
+    Verbatim,
+    Fixed { order: usize, partition_order: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Subframe {
+    method: Method,
+    /// 估计的编码位数 / Estimated encoded size in bits
+    bits: u64,
+}
+
+impl Subframe {
+    fn choose(samples: &[i64], sample_bits: u32, params: &LevelParams) -> Self {
+        if samples.iter().all(|&s| s == samples[0]) {
+            return Self {
+                method: Method::Constant,
+                bits: 8 + u64::from(sample_bits),
+            };
+        }
+        let mut best = Self {
+            method: Method::Verbatim,
+            bits: 8 + samples.len() as u64 * u64::from(sample_bits),
+        };
+        let max_order = params.max_fixed_order.min(samples.len() - 1);
+        for order in 0..=max_order {
+            let residual = fixed_residual(samples, order);
+            let (partition_order, rice_bits) =
+                best_partitioning(&residual, samples.len(), order, params.max_partition_order);
+            let bits = 8 + order as u64 * u64::from(sample_bits) + rice_bits;
+            if bits < best.bits {
+                best = Self {
+                    method: Method::Fixed {
+                        order,
+                        partition_order,
+                    },
+                    bits,
+                };
+            }
+        }
+        best
+    }
+
+    fn write(&self, w: &mut BitWriter, samples: &[i64], sample_bits: u32) {
+        let bits = sample_bits as usize;
+        match self.method {
+            Method::Constant => {
+                w.write(0b0000_0000, 8);
+                w.write_signed(samples[0], bits);
+            }
+            Method::Verbatim => {
+                w.write(0b0000_0010, 8);
+                for &s in samples {
+                    w.write_signed(s, bits);
+                }
+            }
+            Method::Fixed {
+                order,
+                partition_order,
+            } => {
+                w.write(0b0001_0000 | (order as u64) << 1, 8);
+                for &s in &samples[..order] {
+                    w.write_signed(s, bits);
+                }
+                let residual = fixed_residual(samples, order);
+                write_residual(w, &residual, samples.len(), order, partition_order);
+            }
+        }
+    }
+}
+
+/// 固定预测器残差（前 `order` 个样本为预热，不计入）/ Fixed-predictor residual (the first `order` warm-up samples are excluded)
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    samples
+        .windows(order + 1)
+        .map(|w| match order {
+            0 => w[0],
+            1 => w[1] - w[0],
+            2 => w[2] - 2 * w[1] + w[0],
+            3 => w[3] - 3 * w[2] + 3 * w[1] - w[0],
+            _ => w[4] - 4 * w[3] + 6 * w[2] - 4 * w[1] + w[0],
+        })
+        .collect()
+}
+
+fn zigzag(r: i64) -> u64 {
+    ((r << 1) ^ (r >> 63)) as u64
+}
+
+/// 在给定总和与个数时选择 Rice 参数并返回估计位数 / Pick a Rice parameter for a given sum and count, returning the estimated bits
+fn rice_param(sum: u64, count: u64) -> (u32, u64) {
+    (0..=MAX_RICE_PARAM)
+        .map(|k| (k, count * (u64::from(k) + 1) + (sum >> k)))
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+
+/// 每个分区的样本范围（第一个分区扣除预热样本）/ Sample range of each partition (the first excludes warm-up samples)
+fn partition_ranges(block_size: usize, order: usize, partition_order: u32) -> Vec<(usize, usize)> {
+    let per = block_size >> partition_order;
+    (0..1usize << partition_order)
+        .map(|p| {
+            let start = if p == 0 { 0 } else { p * per - order };
+            (start, (p + 1) * per - order)
+        })
+        .collect()
+}
+
+/// 选择分区阶数，返回（阶数，残差估计位数）/ Choose the partition order, returning (order, estimated residual bits)
+fn best_partitioning(
+    residual: &[i64],
+    block_size: usize,
+    order: usize,
+    max_partition_order: u32,
+) -> (u32, u64) {
+    let encoded: Vec<u64> = residual.iter().map(|&r| zigzag(r)).collect();
+    let mut best = (0, u64::MAX);
+    for partition_order in 0..=max_partition_order {
+        let per = block_size >> partition_order;
+        if !block_size.is_multiple_of(1 << partition_order) || per <= order {
+            break;
+        }
+        let mut bits = 2 + 4;
+        for (start, end) in partition_ranges(block_size, order, partition_order) {
+            let sum = encoded[start..end].iter().sum();
+            bits += 4 + rice_param(sum, (end - start) as u64).1;
+        }
+        if bits < best.1 {
+            best = (partition_order, bits);
+        }
+    }
+    best
+}
+
+fn write_residual(
+    w: &mut BitWriter,
+    residual: &[i64],
+    block_size: usize,
+    order: usize,
+    partition_order: u32,
+) {
+    w.write(0b00, 2); // 4 位 Rice 参数 / 4-bit Rice parameters
+    w.write(u64::from(partition_order), 4);
+    let encoded: Vec<u64> = residual.iter().map(|&r| zigzag(r)).collect();
+    for (start, end) in partition_ranges(block_size, order, partition_order) {
+        let part = &encoded[start..end];
+        let (k, _) = rice_param(part.iter().sum(), part.len() as u64);
+        w.write(u64::from(k), 4);
+        for &u in part {
+            w.write_unary(u >> k);
+            w.write(u & ((1 << k) - 1), k as usize);
+        }
+    }
+}
+
+fn sample_rate_code(rate: u32) -> u64 {
+    match rate {
+        88_200 => 0b0001,
+        176_400 => 0b0010,
+        192_000 => 0b0011,
+        8_000 => 0b0100,
+        16_000 => 0b0101,
+        22_050 => 0b0110,
+        24_000 => 0b0111,
+        32_000 => 0b1000,
+        44_100 => 0b1001,
+        48_000 => 0b1010,
+        96_000 => 0b1011,
+        _ => 0b0000, // 取自 STREAMINFO / taken from STREAMINFO
+    }
+}
+
+fn sample_size_code(bits: u32) -> u64 {
+    match bits {
+        8 => 0b001,
+        12 => 0b010,
+        16 => 0b100,
+        20 => 0b101,
+        24 => 0b110,
+        _ => 0b000, // 取自 STREAMINFO / taken from STREAMINFO
+    }
+}
+
+/// 帧号使用类 UTF-8 变长编码 / Frame numbers use a UTF-8-like variable-length code
+fn write_utf8_number(w: &mut BitWriter, n: u64) {
+    if n < 0x80 {
+        w.write(n, 8);
+        return;
+    }
+    let extra = match n {
+        0..=0x7FF => 1,
+        0x800..=0xFFFF => 2,
+        0x1_0000..=0x1F_FFFF => 3,
+        0x20_0000..=0x3FF_FFFF => 4,
+        0x400_0000..=0x7FFF_FFFF => 5,
+        _ => 6,
+    };
+    let lead_marker = (0xFF00u64 >> (extra + 1)) & 0xFF;
+    w.write(lead_marker | (n >> (6 * extra)), 8);
+    for i in (0..extra).rev() {
+        w.write(0x80 | ((n >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &b| {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &b| {
+        crc ^= u16::from(b) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// 高位在前的位写入器 / MSB-first bit writer
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    used: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: usize) {
+        let mut remaining = bits;
+        while remaining > 0 {
+            // 累加器中剩余不足 8 位，每次最多追加 32 位 / Fewer than 8 bits stay in the accumulator, so append at most 32 at a time
+            let take = remaining.min(32);
+            let part = (value >> (remaining - take)) & ((1u64 << take) - 1);
+            self.acc = (self.acc << take) | part;
+            self.used += take;
+            remaining -= take;
+            while self.used >= 8 {
+                self.used -= 8;
+                self.bytes.push((self.acc >> self.used) as u8);
+            }
+            self.acc &= (1u64 << self.used) - 1;
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: usize) {
+        self.write(value as u64 & ((1u64 << bits) - 1), bits);
+    }
+
+    fn write_unary(&mut self, zeros: u64) {
+        let mut zeros = zeros;
+        while zeros >= 32 {
+            self.write(0, 32);
+            zeros -= 32;
+        }
+        self.write(1, zeros as usize + 1);
+    }
+
+    fn align(&mut self) {
+        if self.used > 0 {
+            self.write(0, 8 - self.used);
+        }
+    }
+
+    /// 已完整写出的字节 / Bytes written so far (whole bytes only)
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn encode(samples: &[i32], channels: u16, bits: u32, level: u8, tags: &[String]) -> Vec<u8> {
+        let mut enc =
+            FlacEncoder::new(Cursor::new(Vec::new()), channels, 48000, bits, level, tags).unwrap();
+        enc.write_interleaved(samples).unwrap();
+        enc.finish().unwrap().into_inner()
+    }
+
+    fn decode(bytes: &[u8]) -> (claxon::metadata::StreamInfo, Vec<i32>) {
+        let mut reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        let info = reader.streaminfo();
+        let samples = reader.samples().map(|s| s.unwrap()).collect();
+        (info, samples)
+    }
+
+    // 合成信号：正弦 + 噪声 + 静音段 + 满幅，覆盖常量、固定预测与原样子帧
+    // Synthetic signal: sine + noise + silence + full scale, covering constant, fixed and verbatim subframes
+    fn synth(frames: usize, channels: usize) -> Vec<i32> {
+        let mut seed = 0x1234_5678u32;
+        (0..frames * channels)
+            .map(|i| {
+                let (frame, ch) = (i / channels, i % channels);
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                match frame / 3000 {
+                    0 => ((frame as f64 * 0.01 * (ch + 1) as f64).sin() * 8_000_000.0) as i32,
+                    1 => 0,
+                    2 => (seed >> 8) as i32 - (1 << 23),
+                    _ => {
+                        if frame % 2 == 0 {
+                            8_388_607
+                        } else {
+                            -8_388_608
+                        }
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // 各等级与声道数下逐位无损往返 / Bit-exact round trip across levels and channel counts
+    #[test]
+    fn round_trip_is_bit_exact() {
+        for (channels, level) in [(1u16, 0u8), (2, 5), (2, 8), (6, 8), (8, 3)] {
+            let samples = synth(10_007, usize::from(channels));
+            let bytes = encode(&samples, channels, 24, level, &[]);
+            let (info, decoded) = decode(&bytes);
+            assert_eq!(info.channels, u32::from(channels));
+            assert_eq!(info.bits_per_sample, 24);
+            assert_eq!(info.samples, Some(10_007));
+            assert_eq!(decoded, samples, "channels={channels} level={level}");
+        }
+    }
+
+    // 高等级压缩得更小，且 MD5 与样本一致 / Higher levels compress smaller and the MD5 matches the samples
+    #[test]
+    fn higher_level_is_smaller_and_md5_matches() {
+        let samples: Vec<i32> = (0..48_000)
+            .flat_map(|i| {
+                let v = ((i as f64 * 0.003).sin() * 4_000_000.0) as i32;
+                [v, v / 2]
+            })
+            .collect();
+        let fast = encode(&samples, 2, 24, 0, &[]);
+        let best = encode(&samples, 2, 24, 8, &[]);
+        assert!(best.len() < fast.len());
+
+        let mut md5 = md5::Context::new();
+        for s in &samples {
+            md5.consume(&s.to_le_bytes()[..3]);
+        }
+        let (info, _) = decode(&best);
+        assert_eq!(info.md5sum, md5.compute().0);
+    }
+
+    // Vorbis 注释可被解码器读取 / Vorbis comments are readable by a decoder
+    #[test]
+    fn writes_vorbis_comments() {
+        let tags = vec!["CHANNEL_LAYOUT=L R C LFE Ls Rs".to_string()];
+        let bytes = encode(&[0; 12], 6, 24, 5, &tags);
+        let reader = claxon::FlacReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            reader.get_tag("CHANNEL_LAYOUT").next(),
+            Some("L R C LFE Ls Rs")
+        );
+    }
+
+    #[test]
+    fn utf8_frame_numbers() {
+        let mut w = BitWriter::default();
+        write_utf8_number(&mut w, 0x7F);
+        write_utf8_number(&mut w, 0x80);
+        write_utf8_number(&mut w, 0x1234);
+        assert_eq!(w.into_bytes(), [0x7F, 0xC2, 0x80, 0xE1, 0x88, 0xB4]);
+    }
+}
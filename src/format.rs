@@ -1,65 +1,208 @@
 use crate::error::{DecodeError, Result};
-use std::io::Read;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AudioFormat {
+    /// 传统 AC-3（bsid ≤ 8），不含全景声 / Legacy AC-3 (bsid ≤ 8), never Atmos
+    Ac3,
+    /// E-AC3（bsid 11–16），不含 JOC 扩展 / E-AC3 (bsid 11–16) without the JOC extension
     Eac3,
+    /// 携带 JOC（全景声）扩展的 E-AC3 / E-AC3 carrying the JOC (Atmos) extension
+    Eac3Joc,
     TrueHD,
 }
 
-pub fn detect_format(file_path: &Path, explicit_format: Option<&str>) -> Result<AudioFormat> {
-    // If format is explicitly specified, use it / 如果明确指定了格式，使用它
-    if let Some(format_str) = explicit_format {
-        return match format_str.to_lowercase().as_str() {
-            "eac3" => Ok(AudioFormat::Eac3),
-            "truehd" => Ok(AudioFormat::TrueHD),
-            _ => Err(DecodeError::FormatDetectionFailed(format!(
-                "未知格式/Unknown format: {format_str}"
-            ))),
-        };
+impl AudioFormat {
+    /// 与 --format 取值一致的名称 / Name matching the --format values
+    pub const fn name(self) -> &'static str {
+        match self {
+            AudioFormat::Ac3 => "ac3",
+            AudioFormat::Eac3 => "eac3",
+            AudioFormat::Eac3Joc => "eac3-joc",
+            AudioFormat::TrueHD => "truehd",
+        }
     }
 
-    // Auto-detect from file header / 从文件头自动检测
-    let mut file = std::fs::File::open(file_path).map_err(|e| {
-        DecodeError::FormatDetectionFailed(format!("无法打开文件/Cannot open file: {e}"))
-    })?;
+    /// 非全景声格式的提示；TrueHD 与 E-AC3+JOC 返回 None
+    /// Warning for non-Atmos formats; TrueHD and E-AC3+JOC return None
+    pub fn non_atmos_warning(self) -> Option<&'static str> {
+        match self {
+            AudioFormat::Ac3 | AudioFormat::Eac3 => Some(
+                "输入不含全景声（JOC）扩展，顶部声道将为上混结果或静音/Input has no Atmos (JOC) extension; height channels will be upmixed or silent",
+            ),
+            AudioFormat::Eac3Joc | AudioFormat::TrueHD => None,
+        }
+    }
+}
+
+/// 默认同步字扫描窗口（字节）/ Default sync-word scan window (bytes)
+pub const DEFAULT_SCAN_WINDOW: usize = 64 * 1024;
+
+/// 候选同步字之后须连续有效的帧数 / Consecutive valid frames required after a candidate sync word
+const CONFIRM_FRAMES: usize = 3;
+
+/// 单帧最大字节数（E-AC3 4096，TrueHD 访问单元 8190）/ Largest frame in bytes (E-AC3 4096, TrueHD access unit 8190)
+const MAX_FRAME_BYTES: usize = 8192;
+
+/// 扫描窗口：环境变量 MCAT_SCAN_WINDOW（字节）或默认值 / Scan window: env MCAT_SCAN_WINDOW (bytes) or the default
+pub fn scan_window() -> usize {
+    std::env::var("MCAT_SCAN_WINDOW")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .filter(|&n| n >= 1)
+        .unwrap_or(DEFAULT_SCAN_WINDOW)
+}
 
-    let mut header = [0u8; 10];
-    file.read_exact(&mut header).map_err(|e| {
-        DecodeError::FormatDetectionFailed(format!("无法读取文件头/Cannot read file header: {e}"))
-    })?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncKind {
+    Ac3,
+    TrueHD,
+}
 
-    // Check for E-AC3 sync word (0x0B77) / 检查 E-AC3 同步字
-    if header[0] == 0x0B && header[1] == 0x77 {
-        return Ok(AudioFormat::Eac3);
+fn parse_explicit(format_str: &str) -> Result<AudioFormat> {
+    match format_str.to_lowercase().as_str() {
+        "ac3" => Ok(AudioFormat::Ac3),
+        "eac3" => Ok(AudioFormat::Eac3),
+        "eac3-joc" => Ok(AudioFormat::Eac3Joc),
+        "truehd" => Ok(AudioFormat::TrueHD),
+        _ => Err(DecodeError::FormatDetectionFailed(format!(
+            "未知格式/Unknown format: {format_str}"
+        ))),
     }
+}
 
-    // Check for TrueHD sync word (0xF8726FBA) / 检查 TrueHD 同步字
-    if header.len() >= 4 && &header[0..4] == b"\xf8\x72\x6f\xba" {
-        return Ok(AudioFormat::TrueHD);
+/// TrueHD 访问单元长度：头部 12 位，以 16 位字计 / TrueHD access unit length: 12 header bits, counted in 16-bit words
+fn truehd_unit_bytes(data: &[u8]) -> Option<usize> {
+    let words = (usize::from(*data.first()? & 0x0F) << 8) | usize::from(*data.get(1)?);
+    (words >= 4).then_some(words * 2)
+}
+
+/// 从 `start` 起须有连续 CONFIRM_FRAMES 个有效帧；到达文件末尾时至少一帧
+/// Require CONFIRM_FRAMES consecutive valid frames from `start`; at end of file at least one
+fn confirm_chain(
+    buf: &[u8],
+    start: usize,
+    eof: bool,
+    frame_len: impl Fn(&[u8]) -> Option<usize>,
+) -> bool {
+    let mut pos = start;
+    for _ in 0..CONFIRM_FRAMES {
+        if eof && pos == buf.len() && pos > start {
+            return true;
+        }
+        let Some(len) = buf.get(pos..).and_then(&frame_len) else {
+            return false;
+        };
+        pos += len;
+        if pos > buf.len() {
+            return false;
+        }
     }
+    true
+}
 
-    // Check for TrueHD anywhere in first 10 bytes (sometimes offset) / 在前 10 字节中任何地方检查 TrueHD
-    for i in 0..=6 {
-        if header[i] == 0xF8
-            && header[i + 1] == 0x72
-            && header[i + 2] == 0x6F
-            && header[i + 3] == 0xBA
+/// 在前 `window` 字节中寻找经连续帧确认的同步位置 / Find a sync position in the first `window` bytes, confirmed by consecutive frames
+fn find_sync(
+    buf: &[u8],
+    window: usize,
+    eof: bool,
+    only: Option<SyncKind>,
+) -> Option<(SyncKind, usize)> {
+    let want = |kind| only.is_none_or(|k| k == kind);
+    for i in 0..window.min(buf.len()) {
+        let rest = &buf[i..];
+        if want(SyncKind::Ac3)
+            && rest.starts_with(&[0x0B, 0x77])
+            && confirm_chain(buf, i, eof, crate::probe::ac3_frame_bytes)
         {
-            return Ok(AudioFormat::TrueHD);
+            return Some((SyncKind::Ac3, i));
         }
+        // 主同步位于访问单元头之后 4 字节处 / The major sync follows the 4-byte access unit header
+        if want(SyncKind::TrueHD)
+            && i >= 4
+            && rest.starts_with(&[0xF8, 0x72, 0x6F, 0xBA])
+            && crate::probe::is_major_sync(rest)
+            && confirm_chain(buf, i - 4, eof, truehd_unit_bytes)
+        {
+            return Some((SyncKind::TrueHD, i - 4));
+        }
+    }
+    None
+}
+
+pub fn detect_format(file_path: &Path, explicit_format: Option<&str>) -> Result<AudioFormat> {
+    locate_stream(file_path, explicit_format).map(|(format, _)| format)
+}
+
+/// 检测格式并返回码流起始偏移（跳过前导数据）
+/// Detect the format and return the byte offset where the stream starts (past any leading junk)
+pub fn locate_stream(
+    file_path: &Path,
+    explicit_format: Option<&str>,
+) -> Result<(AudioFormat, u64)> {
+    // If format is explicitly specified, use it / 如果明确指定了格式，使用它
+    let explicit = explicit_format.map(parse_explicit).transpose()?;
+
+    let file = std::fs::File::open(file_path);
+    let mut file = match (file, explicit) {
+        (Ok(f), _) => f,
+        // 显式格式无需读取文件 / An explicit format does not need the file
+        (Err(_), Some(format)) => return Ok((format, 0)),
+        (Err(e), None) => {
+            return Err(DecodeError::FormatDetectionFailed(format!(
+                "无法打开文件/Cannot open file: {e}"
+            )))
+        }
+    };
+
+    let window = scan_window();
+    let limit = window + CONFIRM_FRAMES * MAX_FRAME_BYTES;
+    let mut buf = Vec::with_capacity(limit);
+    (&mut file)
+        .take(limit as u64)
+        .read_to_end(&mut buf)
+        .map_err(|e| {
+            DecodeError::FormatDetectionFailed(format!(
+                "无法读取文件头/Cannot read file header: {e}"
+            ))
+        })?;
+    let eof = buf.len() < limit;
+
+    let only = explicit.map(|f| match f {
+        AudioFormat::TrueHD => SyncKind::TrueHD,
+        _ => SyncKind::Ac3,
+    });
+    let Some((kind, offset)) = find_sync(&buf, window, eof, only) else {
+        if let Some(format) = explicit {
+            return Ok((format, 0));
+        }
+        return Err(DecodeError::FormatDetectionFailed(
+            "无法检测音频格式，请用 --format 指定/Could not detect audio format. Specify with --format"
+                .to_string(),
+        ));
+    };
+    if offset > 0 {
+        log::info!("跳过 {offset} 字节前导数据/Skipping {offset} bytes of leading data");
     }
+    let offset = offset as u64;
 
-    Err(DecodeError::FormatDetectionFailed(
-        "无法检测音频格式，请用 --format 指定/Could not detect audio format. Specify with --format"
-            .to_string(),
-    ))
+    let format = match (explicit, kind) {
+        (Some(format), _) => format,
+        (None, SyncKind::TrueHD) => AudioFormat::TrueHD,
+        // 按 bsid 与 JOC 区分 AC-3/E-AC3 / Tell AC-3/E-AC3 apart by bsid and JOC
+        (None, SyncKind::Ac3) => {
+            file.seek(SeekFrom::Start(offset))?;
+            crate::probe::classify_ac3(&mut std::io::BufReader::new(file))?
+        }
+    };
+    Ok((format, offset))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::probe::test_frames::*;
     use std::io::Write;
 
     // 写一个临时文件并返回其句柄 / Write a temp file with given bytes and return the handle
@@ -74,6 +217,11 @@ mod tests {
     #[test]
     fn explicit_format_is_case_insensitive() {
         let dummy = Path::new("/nonexistent");
+        assert_eq!(detect_format(dummy, Some("ac3")).unwrap(), AudioFormat::Ac3);
+        assert_eq!(
+            detect_format(dummy, Some("EAC3-JOC")).unwrap(),
+            AudioFormat::Eac3Joc
+        );
         for s in ["eac3", "EAC3", "Eac3"] {
             assert_eq!(detect_format(dummy, Some(s)).unwrap(), AudioFormat::Eac3);
         }
@@ -90,25 +238,67 @@ mod tests {
         assert!(matches!(err, DecodeError::FormatDetectionFailed(_)));
     }
 
-    // E-AC3 同步字 0x0B77 被识别 / E-AC3 sync word detected
+    // AC-3 帧（bsid 8）从偏移 0 起识别 / AC-3 frames (bsid 8) detected at offset 0
     #[test]
-    fn detects_eac3_sync_word() {
-        let f = temp_with_bytes(&[0x0B, 0x77, 0, 0, 0, 0, 0, 0, 0, 0]);
-        assert_eq!(detect_format(f.path(), None).unwrap(), AudioFormat::Eac3);
+    fn detects_ac3_frames() {
+        let data: Vec<u8> = (0..4).flat_map(|_| ac3_frame()).collect();
+        let f = temp_with_bytes(&data);
+        assert_eq!(
+            locate_stream(f.path(), None).unwrap(),
+            (AudioFormat::Ac3, 0)
+        );
     }
 
-    // TrueHD 同步字位于开头 / TrueHD sync word at start
+    // TrueHD 访问单元：偏移指向访问单元头而非主同步 / TrueHD access units: the offset points at the unit header, not the major sync
     #[test]
-    fn detects_truehd_sync_word_at_start() {
-        let f = temp_with_bytes(&[0xF8, 0x72, 0x6F, 0xBA, 0, 0, 0, 0, 0, 0]);
-        assert_eq!(detect_format(f.path(), None).unwrap(), AudioFormat::TrueHD);
+    fn detects_truehd_access_units() {
+        let mut data = truehd_unit(true, true);
+        for _ in 0..3 {
+            data.extend(truehd_unit(false, true));
+        }
+        let f = temp_with_bytes(&data);
+        assert_eq!(
+            locate_stream(f.path(), None).unwrap(),
+            (AudioFormat::TrueHD, 0)
+        );
     }
 
-    // TrueHD 同步字带前缀偏移仍被识别 / TrueHD sync word at an offset still detected
+    // 跳过含伪同步字的前导数据 / Leading junk containing fake sync words is skipped
     #[test]
-    fn detects_truehd_sync_word_at_offset() {
-        let f = temp_with_bytes(&[0x00, 0x00, 0xF8, 0x72, 0x6F, 0xBA, 0, 0, 0, 0]);
-        assert_eq!(detect_format(f.path(), None).unwrap(), AudioFormat::TrueHD);
+    fn skips_leading_junk_with_fake_syncs() {
+        let mut data = vec![0u8; 1000];
+        data[10..12].copy_from_slice(&[0x0B, 0x77]);
+        data[100..104].copy_from_slice(&[0xF8, 0x72, 0x6F, 0xBA]);
+        // 截断的半帧 / A truncated partial frame
+        data[500..700].copy_from_slice(&eac3_frame(0, 0, 1536, Some(16))[..200]);
+        for _ in 0..4 {
+            data.extend(eac3_frame(0, 0, 1536, Some(16)));
+        }
+        let f = temp_with_bytes(&data);
+        assert_eq!(
+            locate_stream(f.path(), None).unwrap(),
+            (AudioFormat::Eac3Joc, 1000)
+        );
+    }
+
+    // 同步字只有一帧且未到文件末尾时不算数；窗口外的同步字被忽略
+    // A sync word followed by one frame and no file end does not count; syncs past the window are ignored
+    #[test]
+    fn needs_consecutive_frames_within_window() {
+        let mut data = eac3_frame(0, 0, 1536, None);
+        data.extend(vec![0u8; 4000]);
+        assert_eq!(find_sync(&data, 64, false, None), None);
+
+        let mut data = vec![0u8; 300];
+        for _ in 0..4 {
+            data.extend(eac3_frame(0, 0, 1536, None));
+        }
+        assert_eq!(find_sync(&data, 200, true, None), None);
+        assert_eq!(
+            find_sync(&data, 400, true, None),
+            Some((SyncKind::Ac3, 300))
+        );
+        assert_eq!(find_sync(&data, 400, true, Some(SyncKind::TrueHD)), None);
     }
 
     // 无任何同步字返回错误 / No sync word returns error
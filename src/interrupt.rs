@@ -0,0 +1,124 @@
+use crate::error::{DecodeError, Result};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// 中断后的退出码（128 + SIGINT）/ Exit code after an interrupt (128 + SIGINT)
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static OUTPUTS: OutputTracker = OutputTracker::new();
+
+/// 安装 Ctrl-C 处理：首次只置标志，由等待循环终止子进程；再次按下立即退出
+/// Install the Ctrl-C handler: the first press only sets the flag and the wait loops kill the children; a second press exits immediately
+pub fn install() {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+        eprintln!(
+            "\n[中断] 正在终止子进程并清理不完整的输出（再按一次 Ctrl-C 立即退出）/[Interrupted] Stopping child processes and cleaning partial outputs (press Ctrl-C again to exit now)"
+        );
+    });
+    if let Err(e) = result {
+        log::warn!("无法安装 Ctrl-C 处理/Failed to install Ctrl-C handler: {e}");
+    }
+}
+
+/// 全局中断标志 / The global interrupt flag
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// 已中断时返回错误，用于阶段之间的检查 / Return an error once interrupted, for checks between stages
+pub fn check() -> Result<()> {
+    if is_interrupted() {
+        return Err(DecodeError::Interrupted);
+    }
+    Ok(())
+}
+
+/// 记录当前输入正在写出的文件 / Record a file the current input is writing
+pub fn track_output(path: PathBuf) {
+    OUTPUTS.track(path);
+}
+
+/// 开始处理新输入：之前的输出已完成，不再追踪 / A new input starts: earlier outputs are complete and no longer tracked
+pub fn begin_input() {
+    OUTPUTS.clear();
+}
+
+/// 目前为止的输出已完成（续跑已记录），中断时保留 / Outputs so far are complete (recorded for resume) and kept on interrupt
+pub fn settle_outputs() {
+    OUTPUTS.clear();
+}
+
+/// 删除当前输入的不完整输出，返回已删除的文件 / Delete the current input's partial outputs, returning the removed files
+pub fn remove_partial_outputs() -> Vec<PathBuf> {
+    OUTPUTS.remove_all()
+}
+
+/// 当前输入写出的文件集合 / The set of files written for the current input
+struct OutputTracker {
+    paths: Mutex<Vec<PathBuf>>,
+}
+
+impl OutputTracker {
+    const fn new() -> Self {
+        Self {
+            paths: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn track(&self, path: PathBuf) {
+        if let Ok(mut paths) = self.paths.lock() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut paths) = self.paths.lock() {
+            paths.clear();
+        }
+    }
+
+    fn remove_all(&self) -> Vec<PathBuf> {
+        let paths = self
+            .paths
+            .lock()
+            .map(|mut p| std::mem::take(&mut *p))
+            .unwrap_or_default();
+        paths
+            .into_iter()
+            .filter(|p| std::fs::remove_file(p).is_ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 只删除实际存在的已追踪文件，且不重复追踪 / Only existing tracked files are removed, and paths are tracked once
+    #[test]
+    fn tracker_removes_existing_outputs_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let written = dir.path().join("movie.01_L.wav");
+        let missing = dir.path().join("movie.02_R.wav");
+        std::fs::write(&written, b"partial").unwrap();
+
+        let tracker = OutputTracker::new();
+        tracker.track(written.clone());
+        tracker.track(written.clone());
+        tracker.track(missing);
+        assert_eq!(tracker.remove_all(), std::slice::from_ref(&written));
+        assert!(!written.exists());
+        assert!(tracker.remove_all().is_empty());
+    }
+}
@@ -1,47 +1,152 @@
+mod adm;
+mod backend;
 mod channels;
 mod cli;
 mod decoder;
+mod demux;
+mod doctor;
 mod error;
+mod events;
 mod flac_converter;
+#[cfg(feature = "native-flac")]
+mod flac_encoder;
 mod format;
+mod interrupt;
 mod merger;
+mod probe;
+mod progress;
+mod resume;
+mod stems;
 mod tools;
+mod wav;
+mod wavpack_converter;
 
 use clap::Parser;
+use events::{say, Event};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 #[derive(Debug)]
 struct InputPlan {
     inputs: Vec<PathBuf>,
+    /// 每个输入相对扫描根目录的子目录，批处理输出时镜像到 --output 下
+    /// Each input's subdirectory relative to its scanned root, mirrored under --output in batch mode
+    subdirs: Vec<PathBuf>,
     merge: bool,
     cleanup: bool,
     channels_str: String,
 }
 
-/// Collect candidate audio files in a directory (non-recursive),
-/// using header-based detection for E-AC3/TrueHD. Sorted by mtime.
-/// 基于文件头在指定目录（不递归）收集候选音频，按修改时间排序。
-fn collect_candidates_in_dir(dir: &Path) -> Vec<(PathBuf, SystemTime)> {
-    let mut candidates = Vec::new();
-    if let Ok(rd) = std::fs::read_dir(dir) {
+/// Whether a file looks like a decodable input: header-based detection of E-AC3/TrueHD and containers holding such a track.
+/// 文件是否像可解码的输入：基于文件头检测 E-AC3/TrueHD 及带此类音轨的容器。
+fn is_candidate(path: &Path) -> bool {
+    std::fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.len() >= 4)
+        && crate::demux::open_input(path, None, None).is_ok()
+}
+
+/// Collect candidate audio files in a directory (descending into subdirectories when `recursive`),
+/// using header-based detection for E-AC3/TrueHD and containers holding such a track. Sorted by mtime.
+/// 基于文件头在指定目录收集候选音频（含带 E-AC3/TrueHD 音轨的容器；`recursive` 时进入子目录），按修改时间排序。
+fn collect_candidates_in_dir(dir: &Path, recursive: bool) -> Vec<(PathBuf, SystemTime)> {
+    fn walk(dir: &Path, recursive: bool, out: &mut Vec<(PathBuf, SystemTime)>) {
+        let Ok(rd) = std::fs::read_dir(dir) else {
+            return;
+        };
         for entry in rd.flatten() {
             let p = entry.path();
-            if p.is_file() {
-                if let Ok(meta) = entry.metadata() {
-                    if meta.len() >= 4 && crate::format::detect_format(&p, None).is_ok() {
-                        if let Ok(mtime) = meta.modified() {
-                            candidates.push((p, mtime));
-                        }
-                    }
+            // 不跟随目录符号链接，避免循环 / Directory symlinks are not followed, avoiding cycles
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                if recursive {
+                    walk(&p, recursive, out);
+                }
+            } else if is_candidate(&p) {
+                if let Ok(mtime) = entry.metadata().and_then(|m| m.modified()) {
+                    out.push((p, mtime));
                 }
             }
         }
     }
+
+    let mut candidates = Vec::new();
+    walk(dir, recursive, &mut candidates);
     candidates.sort_by_key(|c| c.1);
     candidates
 }
 
+/// The leading part of a glob pattern without wildcards, used as the root for mirroring.
+/// glob 模式中不含通配符的前导部分，作为镜像输出的根目录。
+fn glob_root(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Subdirectory of `path` relative to `root` (empty when outside it).
+/// `path` 所在目录相对 `root` 的子目录（不在其下时为空）。
+fn subdir_of(path: &Path, root: &Path) -> PathBuf {
+    path.parent()
+        .and_then(|parent| parent.strip_prefix(root).ok())
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+/// Expand `--input` values: files are kept as given, directories are scanned (recursively with `--recursive`)
+/// and glob patterns are matched; scanned and matched files go through header-based detection.
+/// 展开 `--input`：文件原样保留，目录被扫描（`--recursive` 时递归），glob 模式被匹配；扫描与匹配到的文件经文件头检测筛选。
+fn expand_inputs(
+    paths: &[PathBuf],
+    recursive: bool,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Box<dyn std::error::Error>> {
+    let mut inputs: Vec<PathBuf> = Vec::new();
+    let mut subdirs = Vec::new();
+    let mut push = |input: PathBuf, subdir: PathBuf| {
+        if !inputs.contains(&input) {
+            inputs.push(input);
+            subdirs.push(subdir);
+        }
+    };
+
+    for path in paths {
+        if path.is_dir() {
+            for (input, _) in collect_candidates_in_dir(path, recursive) {
+                let subdir = subdir_of(&input, path);
+                push(input, subdir);
+            }
+            continue;
+        }
+        let pattern = path.to_string_lossy();
+        if path.exists() || !pattern.contains(['*', '?', '[']) {
+            // 不存在的普通路径也保留，稍后按缺失文件跳过 / Missing plain paths are kept and skipped later as missing files
+            push(path.clone(), PathBuf::new());
+            continue;
+        }
+        let root = glob_root(&pattern);
+        let mut matched = Vec::new();
+        for entry in glob::glob(&pattern)
+            .map_err(|e| format!("无效的 glob 模式/Invalid glob pattern {pattern}: {e}"))?
+            .flatten()
+        {
+            if entry.is_dir() {
+                matched.extend(collect_candidates_in_dir(&entry, recursive));
+            } else if is_candidate(&entry) {
+                if let Ok(mtime) = std::fs::metadata(&entry).and_then(|m| m.modified()) {
+                    matched.push((entry, mtime));
+                }
+            }
+        }
+        if matched.is_empty() {
+            eprintln!("[警告] 没有匹配的输入文件/No input files match: {pattern}");
+        }
+        matched.sort_by_key(|c| c.1);
+        for (input, _) in matched {
+            let subdir = subdir_of(&input, &root);
+            push(input, subdir);
+        }
+    }
+    Ok((inputs, subdirs))
+}
+
 /// Resolve inputs for normal or lazy mode. In lazy mode, scan current dir first,
 /// then fallback to the executable's directory when empty. Also enforce merge/cleanup=true.
 /// 解析普通/懒人模式输入；懒人模式先扫描当前目录，若为空回退到可执行文件目录；并强制开启合并与清理。
@@ -49,31 +154,45 @@ fn resolve_inputs(args: &crate::cli::Args) -> Result<InputPlan, Box<dyn std::err
     let mut merge = args.flags.merge;
     let mut cleanup = args.flags.cleanup;
 
-    // If not lazy and an input is provided, return directly
-    if !args.flags.lazy {
-        if let Some(inp) = args.input.clone() {
-            return Ok(InputPlan {
-                inputs: vec![inp],
-                merge,
-                cleanup,
-                channels_str: args.channels.clone(),
-            });
+    // If not lazy and inputs are provided, expand them directly / 非懒人模式且给出输入时直接展开
+    if !args.flags.lazy && !args.input.is_empty() {
+        let (inputs, subdirs) = expand_inputs(&args.input, args.flags.recursive)?;
+        if inputs.is_empty() {
+            eprintln!("[错误] 未找到可用输入文件/No suitable input file found");
+            return Err("未找到可用输入文件/No suitable input file found".into());
+        }
+        if inputs.len() > 1 {
+            say!(
+                "将按顺序处理 {} 个文件/Processing {} files sequentially",
+                inputs.len(),
+                inputs.len()
+            );
         }
+        return Ok(InputPlan {
+            inputs,
+            subdirs,
+            merge,
+            cleanup,
+            channels_str: args.channels.clone(),
+        });
     }
 
     // Lazy mode path
-    println!("已启用懒人模式/Lazy mode enabled");
+    say!("已启用懒人模式/Lazy mode enabled");
     merge = true;
     cleanup = true;
 
-    // 仅在当前目录收集候选文件（不递归）/Current directory only, non-recursive
-    let mut candidates = collect_candidates_in_dir(Path::new("."));
+    // 在当前目录收集候选文件（--recursive 时递归）/Current directory, recursive with --recursive
+    let recursive = args.flags.recursive;
+    let mut root = PathBuf::from(".");
+    let mut candidates = collect_candidates_in_dir(&root, recursive);
 
     // 若当前目录没有结果，回退到可执行文件所在目录/If empty, fallback to exe dir
     if candidates.is_empty() {
         if let Ok(exe) = std::env::current_exe() {
             if let Some(exe_dir) = exe.parent() {
-                candidates = collect_candidates_in_dir(exe_dir);
+                root = exe_dir.to_path_buf();
+                candidates = collect_candidates_in_dir(&root, recursive);
             }
         }
     }
@@ -84,7 +203,8 @@ fn resolve_inputs(args: &crate::cli::Args) -> Result<InputPlan, Box<dyn std::err
     }
 
     let inputs = candidates.into_iter().map(|(p, _)| p).collect::<Vec<_>>();
-    println!(
+    let subdirs = inputs.iter().map(|p| subdir_of(p, &root)).collect();
+    say!(
         "将按顺序处理 {} 个文件/Processing {} files sequentially",
         inputs.len(),
         inputs.len()
@@ -93,12 +213,29 @@ fn resolve_inputs(args: &crate::cli::Args) -> Result<InputPlan, Box<dyn std::err
     // 懒人模式固定为 9.1.6/Lazy mode forces 9.1.6
     Ok(InputPlan {
         inputs,
+        subdirs,
         merge,
         cleanup,
         channels_str: "9.1.6".to_string(),
     })
 }
 
+/// `--adm` 写在保留下来的合并 WAV 上：需要合并，且与无损编码同用时需要 --keep-wav
+/// `--adm` goes on the kept merged WAV: it needs merging, and --keep-wav alongside a lossless encode
+fn check_adm_flags(args: &cli::Args, plan: &InputPlan) -> Result<(), Box<dyn std::error::Error>> {
+    let flags = &args.flags;
+    if !flags.adm {
+        return Ok(());
+    }
+    if !plan.merge && args.pipeline != decoder::PipelineMode::Stream {
+        return Err("--adm 需要 --merge/--adm needs --merge".into());
+    }
+    if (flags.flac || flags.wavpack || flags.stems) && !flags.keep_wav {
+        return Err("--adm 与 --flac/--wavpack/--stems 同用时需要 --keep-wav，否则 WAV 会在写入 ADM 前被删除/--adm with --flac/--wavpack/--stems needs --keep-wav, otherwise the WAV is removed before ADM is written".into());
+    }
+    Ok(())
+}
+
 /// Prepare a batch output directory when multiple inputs are given.
 /// 多文件输入时，准备批量输出目录（--output 必须是目录）。
 fn prepare_batch_output_dir(
@@ -124,10 +261,11 @@ fn prepare_batch_output_dir(
     Ok(None)
 }
 
-/// Compute per-file output base path considering batch directory.
-/// 结合批量目录计算单文件输出基路径。
+/// Compute per-file output base path considering batch directory; `subdir` mirrors the input tree under it.
+/// 结合批量目录计算单文件输出基路径；`subdir` 在其下镜像输入目录结构。
 fn output_base_for(
     input: &Path,
+    subdir: &Path,
     batch_output_dir: Option<&Path>,
     output_opt: Option<&PathBuf>,
 ) -> Option<PathBuf> {
@@ -136,146 +274,1150 @@ fn output_base_for(
             .file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("output");
-        return Some(dir.join(stem));
+        return Some(dir.join(subdir).join(stem));
     }
     output_opt.cloned()
 }
 
-#[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
+    interrupt::install();
 
     let args = cli::Args::parse();
+    run(&args)
+}
 
-    // Parse inputs and flags (handles lazy mode) / 解析输入与开关（含懒人模式）
-    let plan = resolve_inputs(&args)?;
+/// Select the decoding backend: the mock backend when `MCAT_DECODER_BACKEND=mock`, gst-launch otherwise.
+/// 选择解码后端：`MCAT_DECODER_BACKEND=mock` 时使用模拟后端，否则使用 gst-launch。
+fn select_backend(
+    args: &cli::Args,
+) -> Result<Box<dyn backend::DecoderBackend>, Box<dyn std::error::Error>> {
+    if std::env::var("MCAT_DECODER_BACKEND").is_ok_and(|v| v.eq_ignore_ascii_case("mock")) {
+        say!("使用模拟解码后端/Using mock decoder backend");
+        events::emit(&Event::Tools {
+            backend: "mock",
+            gst_launch: None,
+            gst_plugins: None,
+        });
+        return Ok(Box::new(backend::MockBackend::default()));
+    }
 
-    // Per-file parallel by default unless --single / 默认保持每文件内并行，除非 --single
-    let single = args.flags.single;
+    // Locate Dolby tools / 定位 Dolby 工具
+    let (gst_launch, gst_plugins) =
+        tools::locate_tools(args.dolby_tools.as_deref()).inspect_err(|e| {
+            events::emit(&Event::ToolsFailed {
+                error: e.to_string(),
+            });
+        })?;
+    say!("找到 GStreamer 工具/Found GStreamer tools");
+    events::emit(&Event::Tools {
+        backend: "gst-launch",
+        gst_launch: Some(&gst_launch),
+        gst_plugins: Some(&gst_plugins),
+    });
+    Ok(Box::new(backend::GstLaunchBackend {
+        gst_launch,
+        gst_plugins,
+    }))
+}
+
+fn run(args: &cli::Args) -> Result<(), Box<dyn std::error::Error>> {
+    // Sync scan window override via CLI / 通过 CLI 覆盖同步字扫描窗口
+    if let Some(w) = args.scan_window {
+        std::env::set_var("MCAT_SCAN_WINDOW", w.to_string());
+    }
+
+    if let Some(cli::Command::Probe {
+        input,
+        format,
+        json,
+    }) = &args.command
+    {
+        let report = probe::probe(input, format.as_deref())?;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}", report.to_text());
+        }
+        return Ok(());
+    }
+
+    if let Some(cli::Command::Doctor) = &args.command {
+        let checks = doctor::run_checks(args.dolby_tools.as_deref());
+        print!("{}", doctor::render_table(&checks));
+        if checks.iter().all(|c| c.passed) {
+            println!("全部检查通过/All checks passed");
+            return Ok(());
+        }
+        eprintln!("存在未通过的检查/Some checks failed");
+        std::process::exit(1);
+    }
+
+    // JSON 事件流：stdout 只输出事件 / JSON event stream: stdout carries only events
+    if args.flags.json {
+        events::enable_json();
+    }
+
+    // Parse inputs and flags (handles lazy mode) / 解析输入与开关（含懒人模式）
+    let plan = resolve_inputs(args).map_err(|e| abort_batch(&[], e))?;
+    check_adm_flags(args, &plan).map_err(|e| abort_batch(&plan.inputs, e))?;
 
     // Concurrency override via CLI / 通过 CLI 覆盖并发度
     if let Some(j) = args.jobs {
         std::env::set_var("MCAT_MAX_PAR", j.to_string());
     }
+    // Timeout/retry overrides via CLI / 通过 CLI 覆盖超时与重试
+    if let Some(t) = args.timeout {
+        std::env::set_var("MCAT_GST_TIMEOUT", t.to_string());
+    }
+    if let Some(r) = args.retries {
+        std::env::set_var("MCAT_GST_RETRIES", r.to_string());
+    }
 
-    // Locate Dolby tools / 定位 Dolby 工具
-    let (gst_launch, gst_plugins) = tools::locate_tools(args.dolby_tools.as_deref())?;
-    println!("找到 GStreamer 工具/Found GStreamer tools");
+    let backend = select_backend(args).map_err(|e| abort_batch(&plan.inputs, e))?;
+    process_inputs(args, &plan, backend.as_ref())
+}
+
+/// 批处理中单个输入的结果 / Outcome of one input in a batch
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FileStatus {
+    Done,
+    /// 失败原因 / Failure reason
+    Failed(String),
+    Skipped,
+    Interrupted,
+    Pending,
+}
+
+impl FileStatus {
+    const fn label(&self) -> &'static str {
+        match self {
+            Self::Done => "完成/done",
+            Self::Failed(_) => "失败/failed",
+            Self::Skipped => "跳过/skipped",
+            Self::Interrupted => "中断/interrupted",
+            Self::Pending => "未开始/not started",
+        }
+    }
+
+    /// JSON 事件中的稳定名称 / Stable name used in JSON events
+    const fn key(&self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::Failed(_) => "failed",
+            Self::Skipped => "skipped",
+            Self::Interrupted => "interrupted",
+            Self::Pending => "pending",
+        }
+    }
+
+    fn error(&self) -> Option<&str> {
+        match self {
+            Self::Failed(reason) => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+fn count_status(statuses: &[FileStatus], key: &str) -> usize {
+    statuses.iter().filter(|s| s.key() == key).count()
+}
+
+/// Summarise which inputs of the batch finished, failed (with the reason) or were skipped.
+/// 汇总批处理中哪些输入已完成、失败（含原因）或被跳过。
+fn batch_summary(inputs: &[PathBuf], statuses: &[FileStatus]) -> String {
+    let done = count_status(statuses, "done");
+    let failed = count_status(statuses, "failed");
+    let skipped = count_status(statuses, "skipped");
+    let mut out = format!(
+        "批处理摘要：完成 {done}/{n}，失败 {failed}，跳过 {skipped}/Batch summary: {done}/{n} completed, {failed} failed, {skipped} skipped\n",
+        n = inputs.len()
+    );
+    for (input, status) in inputs.iter().zip(statuses) {
+        match status.error() {
+            Some(reason) => out.push_str(&format!(
+                "  [{}] {}: {reason}\n",
+                status.label(),
+                input.display()
+            )),
+            None => out.push_str(&format!("  [{}] {}\n", status.label(), input.display())),
+        }
+    }
+    out
+}
+
+/// Emit the batch summary event.
+/// 发出批处理摘要事件。
+fn emit_summary(inputs: &[PathBuf], statuses: &[FileStatus]) {
+    events::emit(&Event::Summary {
+        total: inputs.len(),
+        completed: count_status(statuses, "done"),
+        failed: count_status(statuses, "failed"),
+        skipped: count_status(statuses, "skipped"),
+        interrupted: interrupt::is_interrupted(),
+        files: inputs
+            .iter()
+            .zip(statuses)
+            .map(|(input, status)| events::FileOutcome {
+                input,
+                status: status.key(),
+                error: status.error(),
+            })
+            .collect(),
+    });
+}
+
+/// 批处理开始前失败：发出全部未开始的摘要后原样返回错误
+/// The batch failed before starting: emit a summary with every input pending and return the error unchanged
+fn abort_batch(
+    inputs: &[PathBuf],
+    error: Box<dyn std::error::Error>,
+) -> Box<dyn std::error::Error> {
+    emit_summary(inputs, &vec![FileStatus::Pending; inputs.len()]);
+    error
+}
+
+/// Shared settings for every input of one batch.
+/// 同一批处理中所有输入共用的设置。
+struct Batch<'a> {
+    args: &'a cli::Args,
+    plan: &'a InputPlan,
+    backend: &'a dyn backend::DecoderBackend,
+    options: backend::DecodeOptions,
+    channel_config: channels::ChannelConfig,
+    batch_output_dir: Option<PathBuf>,
+    /// --resume 的状态文件 / State file for --resume
+    resume: Option<resume::ResumeState>,
+    /// 影响输出的设置，写入状态以便设置变化时重新处理 / Settings that shape the outputs, stored so a change reprocesses the input
+    settings: String,
+}
+
+/// Decode, merge, encode and clean up every planned input with the given backend.
+/// 使用给定后端依次解码、合并、编码并清理计划中的每个输入。
+fn process_inputs(
+    args: &cli::Args,
+    plan: &InputPlan,
+    backend: &dyn backend::DecoderBackend,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut statuses = vec![FileStatus::Pending; plan.inputs.len()];
+    let result = run_batch(args, plan, backend, &mut statuses);
+    // 任何退出路径都发出摘要事件 / Every exit path emits the summary event
+    progress::finish();
+    emit_summary(&plan.inputs, &statuses);
+    // 未使用 --keep-going 时返回首个失败 / Without --keep-going the first failure is returned
+    result?;
+    if interrupt::is_interrupted() {
+        eprint!("{}", batch_summary(&plan.inputs, &statuses));
+        std::process::exit(interrupt::EXIT_INTERRUPTED);
+    }
+    let failed = count_status(&statuses, "failed");
+    if plan.inputs.len() > 1 || failed > 0 {
+        say!("{}", batch_summary(&plan.inputs, &statuses).trim_end());
+    }
+    if failed > 0 {
+        return Err(format!("{failed} 个文件处理失败/{failed} file(s) failed").into());
+    }
+    say!("完成!/Done!");
+    Ok(())
+}
+
+/// 按顺序处理各输入并把结果写入 `statuses` / Process the inputs in order, recording each outcome in `statuses`
+fn run_batch(
+    args: &cli::Args,
+    plan: &InputPlan,
+    backend: &dyn backend::DecoderBackend,
+    statuses: &mut [FileStatus],
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Per-file parallel by default unless --single / 默认保持每文件内并行，除非 --single
+    let options = backend::DecodeOptions {
+        single: args.flags.single,
+        no_numbers: args.no_numbers,
+        pipeline: args.pipeline,
+    };
     let channel_config = channels::get_config(&plan.channels_str)?;
 
     // Prepare batch output directory if needed / 如有需要，准备批处理输出目录
     let batch_output_dir = prepare_batch_output_dir(&plan.inputs, args.output.as_ref())?;
+    // 状态文件放在输出目录：批处理目录、--output 所在目录或首个输入所在目录
+    // The state file lives in the output directory: the batch dir, the --output parent or the first input's dir
+    let resume = args.flags.resume.then(|| {
+        let dir = batch_output_dir
+            .clone()
+            .or_else(|| {
+                args.output
+                    .as_ref()
+                    .and_then(|o| o.parent())
+                    .map(Path::to_path_buf)
+            })
+            .or_else(|| {
+                plan.inputs
+                    .first()
+                    .and_then(|i| i.parent())
+                    .map(Path::to_path_buf)
+            })
+            .filter(|d| !d.as_os_str().is_empty())
+            .unwrap_or_else(|| PathBuf::from("."));
+        let state = resume::ResumeState::load(&dir);
+        say!("续跑状态/Resume state: {}", state.path().display());
+        state
+    });
+    let settings = format!(
+        "{}|{:?}|{}|{:?}|{:?}|{}|{}",
+        plan.channels_str,
+        args.pipeline,
+        args.no_numbers,
+        args.track,
+        args.format,
+        args.flac_level,
+        args.flags.adm
+    );
+    let batch = Batch {
+        args,
+        plan,
+        backend,
+        options,
+        channel_config,
+        batch_output_dir,
+        resume,
+        settings,
+    };
 
+    if !args.flags.no_progress {
+        progress::start(plan.inputs.len());
+    }
     for (idx, input) in plan.inputs.iter().enumerate() {
+        if interrupt::is_interrupted() {
+            break;
+        }
         if !input.exists() {
             eprintln!(
                 "[警告] 跳过不存在的文件/Skip missing file: {}",
                 input.display()
             );
+            events::emit(&Event::Warning {
+                input: Some(input),
+                message: "跳过不存在的文件/Skip missing file".to_string(),
+            });
+            statuses[idx] = FileStatus::Skipped;
+            progress::file_done();
             continue;
         }
-        println!(
+        if batch.already_done(input, &plan.subdirs[idx]) {
+            say!("已完成，跳过/Already done, skipping: {}", input.display());
+            events::emit(&Event::FileSkipped { input });
+            statuses[idx] = FileStatus::Skipped;
+            progress::file_done();
+            continue;
+        }
+        say!(
             "[{} / {}] 处理文件/Processing file: {}",
             idx + 1,
             plan.inputs.len(),
             input.display()
         );
+        events::emit(&Event::FileStart {
+            index: idx + 1,
+            total: plan.inputs.len(),
+            input,
+        });
+
+        interrupt::begin_input();
+        match batch.process_file(input, &plan.subdirs[idx]) {
+            Ok(()) => {
+                statuses[idx] = FileStatus::Done;
+                events::emit(&Event::FileDone { input });
+                progress::file_done();
+            }
+            // 中断：删除当前输入的不完整输出 / Interrupted: remove the current input's partial outputs
+            Err(e) if interrupt::is_interrupted() => {
+                statuses[idx] = FileStatus::Interrupted;
+                events::emit(&Event::FileFailed {
+                    input,
+                    error: e.to_string(),
+                });
+                for removed in interrupt::remove_partial_outputs() {
+                    eprintln!(
+                        "已删除不完整的输出/Removed partial output: {}",
+                        removed.display()
+                    );
+                }
+                break;
+            }
+            Err(e) => {
+                events::emit(&Event::FileFailed {
+                    input,
+                    error: e.to_string(),
+                });
+                statuses[idx] = FileStatus::Failed(e.to_string());
+                if !args.flags.keep_going {
+                    return Err(e);
+                }
+                // --keep-going：记录原因后继续下一个文件 / --keep-going: record the reason and move on to the next file
+                eprintln!("[错误] {}: {e}", input.display());
+                progress::file_done();
+            }
+        }
+    }
+
+    Ok(())
+}
 
-        // Detect audio format / 检测音频格式
-        let audio_format = format::detect_format(input, args.format.as_deref())?;
-        println!("检测到格式/Detected format: {audio_format:?}");
+impl Batch<'_> {
+    /// The merged WAV path of an input (its directory is created when needed).
+    /// 输入对应的合并 WAV 路径。
+    fn merged_file_for(&self, input: &Path, subdir: &Path) -> PathBuf {
+        if let Some(dir) = &self.batch_output_dir {
+            let stem = input
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("output");
+            dir.join(subdir).join(stem).with_extension("wav")
+        } else if let Some(output) = &self.args.output {
+            output.with_extension("wav")
+        } else {
+            input.with_extension("wav")
+        }
+    }
+
+    /// The files a finished run leaves behind for an input; empty when they can't be known up front (auto layout without merging).
+    /// 处理完成后输入留下的文件；无法预先确定时（未合并的 auto 布局）为空。
+    fn final_outputs(&self, input: &Path, subdir: &Path) -> Vec<PathBuf> {
+        let flags = &self.args.flags;
+        let streamed = self.args.pipeline == decoder::PipelineMode::Stream;
+        if self.plan.merge || streamed {
+            let merged = self.merged_file_for(input, subdir);
+            let mut outputs = Vec::new();
+            if flags.flac {
+                outputs.push(merged.with_extension("flac"));
+            }
+            if flags.wavpack {
+                outputs.push(merged.with_extension("wv"));
+            }
+            if flags.stems {
+                outputs.push(merged.with_extension("stems.json"));
+            }
+            if flags.keep_wav || outputs.is_empty() {
+                outputs.push(merged);
+            }
+            return outputs;
+        }
+        if self.channel_config.id == u32::MAX {
+            return Vec::new();
+        }
+        let base = output_base_for(
+            input,
+            subdir,
+            self.batch_output_dir.as_deref(),
+            self.args.output.as_ref(),
+        )
+        .unwrap_or_else(|| input.to_path_buf());
+        self.channel_config
+            .names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let suffix = decoder::channel_output_suffix(name, i, self.args.no_numbers);
+                base.with_extension(&suffix[1..])
+            })
+            .collect()
+    }
+
+    /// Whether an input can be skipped: finished per the resume state, or (--skip-existing) all its outputs exist.
+    /// 输入是否可跳过：续跑状态记录为已完成，或（--skip-existing）其全部输出已存在。
+    fn already_done(&self, input: &Path, subdir: &Path) -> bool {
+        let outputs = self.final_outputs(input, subdir);
+        // 输出仍在才算完成，因此新增的编码选项会继续处理 / Only done while the outputs remain, so newly requested encodes still run
+        if self.stage_done(input, resume::Stage::Done, &outputs) {
+            return true;
+        }
+        self.args.flags.skip_existing && !outputs.is_empty() && outputs.iter().all(|p| p.exists())
+    }
+
+    /// With --resume: the stage is recorded for this input and its outputs are still there.
+    /// 续跑时：该输入已记录此阶段且其输出仍在。
+    fn stage_done(&self, input: &Path, stage: resume::Stage, outputs: &[PathBuf]) -> bool {
+        self.resume
+            .as_ref()
+            .is_some_and(|state| state.has(input, &self.settings, stage))
+            && outputs.iter().all(|p| p.exists())
+    }
+
+    /// With --resume: an encode stage that is already done is reported and skipped.
+    /// 续跑时已完成的编码阶段：提示并跳过。
+    fn skip_done_stage(&self, input: &Path, stage: resume::Stage, output: &Path) -> bool {
+        let done = self.stage_done(input, stage, &[output.to_path_buf()]);
+        if done {
+            say!("续跑：已完成/Resuming: already done: {}", output.display());
+        }
+        done
+    }
+
+    /// With --resume: record a finished stage; its outputs are then kept on interrupt.
+    /// A state file that can't be written only costs the ability to resume, so it is a warning.
+    /// 续跑时记录已完成的阶段；此后中断时保留其输出。状态文件写入失败只影响续跑，因此仅警告。
+    fn record_stage(&self, input: &Path, stage: resume::Stage, decoded: &[PathBuf]) {
+        if let Some(state) = &self.resume {
+            match state.record(input, stage, decoded) {
+                Ok(()) => interrupt::settle_outputs(),
+                Err(e) => eprintln!("[警告] 无法写入续跑状态/Cannot write resume state: {e}"),
+            }
+        }
+    }
+
+    /// Process one input: detect, decode, merge, encode and clean up.
+    /// 处理单个输入：检测、解码、合并、编码与清理。
+    #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
+    fn process_file(&self, input: &Path, subdir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(state) = &self.resume {
+            state.begin(input, &self.settings);
+        }
+
+        // Detect container/audio format / 检测容器与音频格式
+        let (source, audio_format) =
+            demux::open_input(input, self.args.format.as_deref(), self.args.track)?;
+        if let Some(demux) = &source.demux {
+            say!("使用音轨/Using track: {}", demux.description);
+        }
+        if source.offset > 0 {
+            say!(
+                "跳过 {} 字节前导数据/Skipping {} bytes of leading data",
+                source.offset,
+                source.offset
+            );
+        }
+        say!("检测到格式/Detected format: {audio_format:?}");
+        events::emit(&Event::Format {
+            input,
+            format: audio_format.name(),
+            track: source.demux.as_ref().map(|d| d.description.as_str()),
+            offset: source.offset,
+        });
+        // 容器中的 E-AC3 未读取载荷，无法判断 JOC / Container E-AC3 payloads are not read, so JOC is unknown there
+        let joc_known = source.demux.is_none() || audio_format == format::AudioFormat::Ac3;
+        if let Some(warning) = audio_format.non_atmos_warning().filter(|_| joc_known) {
+            eprintln!("[警告] {warning}");
+            events::emit(&Event::Warning {
+                input: Some(input),
+                message: warning.to_string(),
+            });
+        }
+
+        // auto：由码流头（容器输入为所选音轨开头的载荷）确定原生布局；无法读取或映射时回退到逐声道试探
+        // auto: take the native layout from the bitstream headers (the selected track's first payload for containers); fall back to trial decoding when they can't be read or mapped
+        let file_config = if self.channel_config.id == u32::MAX {
+            match probe::native_config(&source, audio_format) {
+                Ok(config) => {
+                    say!("原生声道布局/Native channel layout: {}", config.name);
+                    config
+                }
+                Err(e) => {
+                    let message = format!("无法确定原生布局，改为逐声道试探/Cannot determine native layout, falling back to trial decoding: {e}");
+                    eprintln!("[警告] {message}");
+                    events::emit(&Event::Warning {
+                        input: Some(input),
+                        message,
+                    });
+                    self.channel_config.clone()
+                }
+            }
+        } else {
+            self.channel_config.clone()
+        };
+        // ADM 需要已知布局，在解码前检查 / ADM needs a known layout, checked before decoding
+        if self.args.flags.adm {
+            adm::AdmLayout::from_config(&file_config)?;
+        }
+        // auto 回退时声道数由试探得出，不发出布局事件 / On the auto fallback the count comes from trial decoding, so no layout event
+        if file_config.id != u32::MAX {
+            events::emit(&Event::Layout {
+                input,
+                layout: &file_config.name,
+                channels: file_config.names.len(),
+            });
+        }
 
         // Decode audio / 解码音频（按文件顺序）
-        // 批处理时使用输出目录 + 输入基名作为 base；单文件保持原有行为 / in batch, use output dir + input stem as base; single-file keeps original semantics
-        let per_file_base =
-            output_base_for(input, batch_output_dir.as_deref(), args.output.as_ref());
-        let decoded_files = decoder::decode(
+        // 批处理时使用输出目录 + 输入子目录 + 输入基名作为 base；单文件保持原有行为 / in batch, use output dir + input subdir + input stem as base; single-file keeps original semantics
+        let per_file_base = output_base_for(
             input,
-            per_file_base.as_ref(),
-            &gst_launch,
-            &gst_plugins,
-            audio_format,
-            &channel_config,
-            single,
-            args.no_numbers,
-        )?;
-        println!(
-            "已解码 {} 个声道文件/Decoded {} channel files",
-            decoded_files.len(),
-            decoded_files.len()
+            subdir,
+            self.batch_output_dir.as_deref(),
+            self.args.output.as_ref(),
         );
+        let merged_file = self.merged_file_for(input, subdir);
+        if let Some(dir) = merged_file
+            .parent()
+            .filter(|_| self.batch_output_dir.is_some())
+        {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        // 续跑：已合并时跳过解码，已解码时复用声道文件 / Resume: skip decoding once merged, reuse the channel files once decoded
+        let merged_done = self.stage_done(
+            input,
+            resume::Stage::Merged,
+            std::slice::from_ref(&merged_file),
+        );
+        let recorded = self
+            .resume
+            .as_ref()
+            .map(|state| state.decoded(input))
+            .unwrap_or_default();
+
+        // 流式模式直接写出合并文件，不产生单声道文件 / Stream mode writes the merged file directly, no mono files
+        let streamed = self.args.pipeline == decoder::PipelineMode::Stream;
+        let decoded_files = if merged_done {
+            say!("续跑：已合并，跳过解码/Resuming: already merged, skipping decode");
+            recorded
+        } else if streamed {
+            self.backend
+                .decode_streamed(&source, audio_format, &file_config, &merged_file)?;
+            Vec::new()
+        } else if !recorded.is_empty() && self.stage_done(input, resume::Stage::Decoded, &recorded)
+        {
+            say!(
+                "续跑：复用 {} 个已解码的声道文件/Resuming: reusing {} decoded channel files",
+                recorded.len(),
+                recorded.len()
+            );
+            recorded
+        } else {
+            let files = self.backend.decode(
+                &source,
+                audio_format,
+                &file_config,
+                per_file_base.as_ref(),
+                self.options,
+            )?;
+            say!(
+                "已解码 {} 个声道文件/Decoded {} channel files",
+                files.len(),
+                files.len()
+            );
+            self.record_stage(input, resume::Stage::Decoded, &files);
+            files
+        };
+
+        interrupt::check()?;
 
         // Merge channels if requested / 如果需要合并声道
-        if plan.merge {
-            let merged_file = if let Some(dir) = &batch_output_dir {
-                let stem = input
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("output");
-                dir.join(stem).with_extension("wav")
-            } else if let Some(output) = &args.output {
-                output.with_extension("wav")
+        if self.plan.merge || streamed {
+            if merged_done {
+                say!(
+                    "续跑：已合并至 {}/Resuming: already merged to {}",
+                    merged_file.display(),
+                    merged_file.display()
+                );
+            } else if streamed {
+                say!(
+                    "已流式写入 {}/Streamed merged output to {}",
+                    merged_file.display(),
+                    merged_file.display()
+                );
             } else {
-                input.with_extension("wav")
-            };
+                interrupt::track_output(merged_file.clone());
+                merger::merge_channels(&decoded_files, &merged_file, Some(&file_config))?;
+                say!(
+                    "已将声道合并至 {}/Merged channels to {}",
+                    merged_file.display(),
+                    merged_file.display()
+                );
+            }
+            events::emit(&Event::Merge {
+                input,
+                inputs: &decoded_files,
+                output: &merged_file,
+                streamed,
+            });
+            self.record_stage(input, resume::Stage::Merged, &[]);
 
-            merger::merge_channels(&decoded_files, &merged_file, Some(&channel_config))?;
-            println!(
-                "已将声道合并至 {}/Merged channels to {}",
-                merged_file.display(),
-                merged_file.display()
-            );
+            // 只有所有请求的无损编码都成功后才删除 WAV / The WAV is only removed once every requested lossless encode succeeded
+            let mut keep_merged = self.args.flags.keep_wav
+                || !(self.args.flags.flac || self.args.flags.wavpack || self.args.flags.stems);
+
+            interrupt::check()?;
 
             // Convert to FLAC if requested / 如果需要转码为 FLAC
-            if args.flags.flac {
+            if self.args.flags.flac
+                && !self.skip_done_stage(
+                    input,
+                    resume::Stage::Flac,
+                    &merged_file.with_extension("flac"),
+                )
+            {
                 // 检查声道数限制 / Check channel limit for FLAC
-                let num_channels = channel_config.names.len() as u16;
+                let num_channels = file_config.names.len() as u16;
                 if let Err(e) = flac_converter::check_flac_compatibility(num_channels) {
                     eprintln!("[警告] FLAC 转码失败/FLAC conversion warning: {e}");
+                    events::emit(&Event::EncodeFailed {
+                        codec: "flac",
+                        input: &merged_file,
+                        error: e.to_string(),
+                    });
+                    keep_merged = true;
                 } else {
                     // 构建 FLAC 输出路径 / Build FLAC output path
                     let flac_file = merged_file.with_extension("flac");
+                    interrupt::track_output(flac_file.clone());
 
                     // 执行转码 / Perform conversion
                     match flac_converter::convert_batch(
                         &merged_file,
                         &flac_file,
-                        Some(&channel_config),
+                        Some(&file_config),
+                        self.args.flac_level,
                     ) {
                         Ok(()) => {
-                            println!(
+                            say!(
                                 "FLAC 转码完成/FLAC conversion completed: {}",
                                 flac_file.display()
                             );
-
-                            // 删除原始 WAV 文件（如果不保留）/ Delete original WAV (if not keeping)
-                            if !args.flags.keep_wav {
-                                std::fs::remove_file(&merged_file)?;
-                                println!(
-                                    "已删除原始 WAV 文件/Removed original WAV: {}",
-                                    merged_file.display()
-                                );
-                            }
+                            events::emit(&Event::EncodeDone {
+                                codec: "flac",
+                                input: &merged_file,
+                                output: &flac_file,
+                            });
+                            self.record_stage(input, resume::Stage::Flac, &[]);
                         }
                         Err(e) => {
+                            events::emit(&Event::EncodeFailed {
+                                codec: "flac",
+                                input: &merged_file,
+                                error: e.to_string(),
+                            });
                             eprintln!(
-                                "[错误] FLAC 转码失败/FLAC conversion failed: {e}. 保留原始 WAV 文件/Keeping original WAV."
-                            );
+                            "[错误] FLAC 转码失败/FLAC conversion failed: {e}. 保留原始 WAV 文件/Keeping original WAV."
+                        );
+                            keep_merged = true;
                         }
                     }
                 }
             }
 
+            interrupt::check()?;
+
+            // Convert to WavPack if requested / 如果需要转码为 WavPack
+            if self.args.flags.wavpack
+                && !self.skip_done_stage(
+                    input,
+                    resume::Stage::Wavpack,
+                    &merged_file.with_extension("wv"),
+                )
+            {
+                let wv_file = merged_file.with_extension("wv");
+                interrupt::track_output(wv_file.clone());
+                match wavpack_converter::convert_batch(&merged_file, &wv_file, Some(&file_config)) {
+                    Ok(()) => {
+                        say!(
+                            "WavPack 转码完成/WavPack conversion completed: {}",
+                            wv_file.display()
+                        );
+                        events::emit(&Event::EncodeDone {
+                            codec: "wavpack",
+                            input: &merged_file,
+                            output: &wv_file,
+                        });
+                        self.record_stage(input, resume::Stage::Wavpack, &[]);
+                    }
+                    Err(e) => {
+                        events::emit(&Event::EncodeFailed {
+                            codec: "wavpack",
+                            input: &merged_file,
+                            error: e.to_string(),
+                        });
+                        eprintln!(
+                        "[错误] WavPack 转码失败/WavPack conversion failed: {e}. 保留原始 WAV 文件/Keeping original WAV."
+                    );
+                        keep_merged = true;
+                    }
+                }
+            }
+
+            interrupt::check()?;
+
+            // Split into FLAC stems if requested / 如果需要拆分为 FLAC 分轨
+            if self.args.flags.stems
+                && !self.skip_done_stage(
+                    input,
+                    resume::Stage::Stems,
+                    &merged_file.with_extension("stems.json"),
+                )
+            {
+                match stems::export_stems(&merged_file, &file_config, self.args.flac_level) {
+                    Ok(manifest) => {
+                        say!(
+                            "FLAC 分轨导出完成/FLAC stems exported: {}",
+                            manifest.display()
+                        );
+                        events::emit(&Event::EncodeDone {
+                            codec: "stems",
+                            input: &merged_file,
+                            output: &manifest,
+                        });
+                        self.record_stage(input, resume::Stage::Stems, &[]);
+                    }
+                    Err(e) => {
+                        events::emit(&Event::EncodeFailed {
+                            codec: "stems",
+                            input: &merged_file,
+                            error: e.to_string(),
+                        });
+                        eprintln!(
+                        "[错误] FLAC 分轨导出失败/FLAC stem export failed: {e}. 保留原始 WAV 文件/Keeping original WAV."
+                    );
+                        keep_merged = true;
+                    }
+                }
+            }
+
+            interrupt::check()?;
+
+            // 删除原始 WAV 文件（如果不保留）/ Delete original WAV (if not keeping)
+            if !keep_merged {
+                std::fs::remove_file(&merged_file)?;
+                say!(
+                    "已删除原始 WAV 文件/Removed original WAV: {}",
+                    merged_file.display()
+                );
+                events::emit(&Event::Cleanup { path: &merged_file });
+            }
+
+            // 启动时已确保 WAV 会被保留 / Startup checks ensure the WAV is kept
+            if self.args.flags.adm {
+                merger::write_adm_metadata(&merged_file, &file_config)?;
+                say!(
+                    "已写入 ADM 元数据（BW64）/Wrote ADM metadata (BW64): {}",
+                    merged_file.display()
+                );
+                events::emit(&Event::Adm {
+                    output: &merged_file,
+                });
+            }
+
             // Cleanup discrete files if requested / 如果需要清理分离的文件
-            if plan.cleanup {
-                for file in &decoded_files {
+            if self.plan.cleanup {
+                // 续跑时部分文件可能已被删除 / On resume some files may already be gone
+                for file in decoded_files.iter().filter(|f| f.exists()) {
                     std::fs::remove_file(file)?;
-                    println!("已删除 {}/Removed {}", file.display(), file.display());
+                    say!("已删除 {}/Removed {}", file.display(), file.display());
+                    events::emit(&Event::Cleanup { path: file });
                 }
             }
         }
+        self.record_stage(input, resume::Stage::Done, &[]);
+        Ok(())
     }
+}
 
-    println!("完成!/Done!");
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use crate::probe::test_frames::ac3_frame;
+
+    // 摘要列出每个输入的状态与完成数 / The summary lists every input's status and the completed count
+    #[test]
+    fn batch_summary_lists_each_input() {
+        let inputs = [
+            PathBuf::from("a.ec3"),
+            PathBuf::from("b.ec3"),
+            PathBuf::from("c.ec3"),
+        ];
+        let summary = batch_summary(
+            &inputs,
+            &[
+                FileStatus::Done,
+                FileStatus::Interrupted,
+                FileStatus::Pending,
+            ],
+        );
+        assert!(summary.contains("1/3 completed, 0 failed, 0 skipped"));
+        assert!(summary.contains("[完成/done] a.ec3"));
+        assert!(summary.contains("[中断/interrupted] b.ec3"));
+        assert!(summary.contains("[未开始/not started] c.ec3"));
+
+        let summary = batch_summary(
+            &inputs,
+            &[
+                FileStatus::Failed("bad sync".to_string()),
+                FileStatus::Skipped,
+                FileStatus::Done,
+            ],
+        );
+        assert!(summary.contains("1/3 completed, 1 failed, 1 skipped"));
+        assert!(summary.contains("[失败/failed] a.ec3: bad sync"));
+    }
+
+    // --adm 的冲突开关在启动时即被拒绝 / Conflicting --adm flags are rejected at startup
+    #[test]
+    fn adm_flags_are_checked_upfront() {
+        let check = |argv: &[&str]| {
+            let args = cli::Args::parse_from(argv);
+            let plan = InputPlan {
+                inputs: vec![PathBuf::from("movie.ec3")],
+                subdirs: vec![PathBuf::new()],
+                merge: args.flags.merge,
+                cleanup: false,
+                channels_str: args.channels.clone(),
+            };
+            check_adm_flags(&args, &plan).is_ok()
+        };
+        assert!(check(&["mcat", "--merge", "--adm"]));
+        assert!(check(&["mcat", "--pipeline", "stream", "--adm"]));
+        assert!(check(&["mcat", "--merge", "--adm", "--flac", "--keep-wav"]));
+        assert!(!check(&["mcat", "--adm"]));
+        assert!(!check(&["mcat", "--merge", "--adm", "--flac"]));
+        assert!(!check(&["mcat", "--merge", "--adm", "--stems"]));
+        assert!(!check(&["mcat", "--merge", "--adm", "--wavpack"]));
+    }
+    // --keep-going：损坏的文件不会中断其余文件，但结果仍为失败
+    // --keep-going: a corrupt file doesn't stop the rest, but the run still fails
+    #[test]
+    fn keep_going_continues_past_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let corrupt = dir.path().join("a.ac3");
+        let good = dir.path().join("b.ac3");
+        std::fs::write(&corrupt, b"not a bitstream").unwrap();
+        std::fs::write(&good, (0..4).flat_map(|_| ac3_frame()).collect::<Vec<u8>>()).unwrap();
+        let plan = InputPlan {
+            inputs: vec![corrupt, good.clone()],
+            subdirs: vec![PathBuf::new(), PathBuf::new()],
+            merge: true,
+            cleanup: true,
+            channels_str: "2.0".to_string(),
+        };
+
+        let args = cli::Args::parse_from(["mcat", "--no-progress"]);
+        assert!(process_inputs(&args, &plan, &MockBackend::default()).is_err());
+        assert!(!good.with_extension("wav").exists());
+
+        let args = cli::Args::parse_from(["mcat", "--no-progress", "--keep-going"]);
+        assert!(process_inputs(&args, &plan, &MockBackend::default()).is_err());
+        assert!(good.with_extension("wav").exists());
+    }
+
+    // 目录按需递归、glob 匹配经文件头筛选，输出在 --output 下镜像输入目录结构
+    // Directories recurse on request, glob matches go through header detection, and outputs mirror the input tree under --output
+    #[test]
+    fn expands_directories_and_globs_and_mirrors_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("in");
+        let stream = (0..4).flat_map(|_| ac3_frame()).collect::<Vec<u8>>();
+        std::fs::create_dir_all(root.join("season1")).unwrap();
+        std::fs::write(root.join("a.ac3"), &stream).unwrap();
+        std::fs::write(root.join("season1/b.ac3"), &stream).unwrap();
+        std::fs::write(root.join("season1/notes.ac3"), b"not audio").unwrap();
+
+        let (inputs, _) = expand_inputs(std::slice::from_ref(&root), false).unwrap();
+        assert_eq!(inputs, [root.join("a.ac3")]);
+
+        let pattern = root.join("**/*.ac3");
+        let (mut inputs, mut subdirs) = expand_inputs(&[pattern], false).unwrap();
+        let mut pairs: Vec<_> = inputs.drain(..).zip(subdirs.drain(..)).collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            [
+                (root.join("a.ac3"), PathBuf::new()),
+                (root.join("season1/b.ac3"), PathBuf::from("season1")),
+            ]
+        );
+
+        let out = dir.path().join("out");
+        let args = cli::Args::parse_from([
+            "mcat",
+            "--input",
+            root.to_str().unwrap(),
+            "--recursive",
+            "--channels",
+            "2.0",
+            "--merge",
+            "--output",
+            out.to_str().unwrap(),
+            "--no-progress",
+        ]);
+        let plan = resolve_inputs(&args).unwrap();
+        assert_eq!(plan.inputs.len(), 2);
+        process_inputs(&args, &plan, &MockBackend::default()).unwrap();
+        assert!(out.join("a.wav").exists());
+        assert!(out.join("season1/b.wav").exists());
+        assert!(out.join("season1/b.01_L.wav").exists());
+    }
+
+    /// 统计解码调用次数的模拟后端 / Mock backend counting decode calls
+    #[derive(Default)]
+    struct CountingBackend {
+        mock: MockBackend,
+        decodes: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CountingBackend {
+        fn decodes(&self) -> usize {
+            self.decodes.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl backend::DecoderBackend for CountingBackend {
+        fn decode(
+            &self,
+            input: &demux::InputSource,
+            format: format::AudioFormat,
+            layout: &channels::ChannelConfig,
+            output_base: Option<&PathBuf>,
+            options: backend::DecodeOptions,
+        ) -> error::Result<Vec<PathBuf>> {
+            self.decodes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.mock
+                .decode(input, format, layout, output_base, options)
+        }
+
+        fn decode_streamed(
+            &self,
+            input: &demux::InputSource,
+            format: format::AudioFormat,
+            layout: &channels::ChannelConfig,
+            merged_file: &Path,
+        ) -> error::Result<()> {
+            self.decodes
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.mock
+                .decode_streamed(input, format, layout, merged_file)
+        }
+    }
+
+    // 按命令行参数对输入跑完整流程，输入不存在时写一段 AC-3 码流
+    // Run the whole flow on the input for the given arguments, writing an AC-3 stream when it is missing
+    fn run_mock(input: &Path, backend: &dyn backend::DecoderBackend, extra: &[&str]) {
+        if !input.exists() {
+            std::fs::write(input, (0..4).flat_map(|_| ac3_frame()).collect::<Vec<u8>>()).unwrap();
+        }
+        let mut argv = vec!["mcat", "--input", input.to_str().unwrap(), "--no-progress"];
+        argv.extend_from_slice(extra);
+        let args = cli::Args::parse_from(argv);
+        let plan = resolve_inputs(&args).unwrap();
+        process_inputs(&args, &plan, backend).unwrap();
+    }
+
+    // --skip-existing 仅在全部输出存在时跳过 / --skip-existing only skips when every output exists
+    #[test]
+    fn skip_existing_checks_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.ac3");
+        let backend = CountingBackend::default();
+        run_mock(&input, &backend, &["-c", "2.0", "--merge"]);
+        run_mock(
+            &input,
+            &backend,
+            &["-c", "2.0", "--merge", "--skip-existing"],
+        );
+        assert_eq!(backend.decodes(), 1);
+
+        std::fs::remove_file(dir.path().join("movie.wav")).unwrap();
+        run_mock(
+            &input,
+            &backend,
+            &["-c", "2.0", "--merge", "--skip-existing"],
+        );
+        assert_eq!(backend.decodes(), 2);
+    }
+
+    // --resume 复用已完成的阶段，完成后整个输入被跳过 / --resume reuses finished stages and skips the input once done
+    #[test]
+    fn resume_reuses_completed_stages() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.ac3");
+        let backend = CountingBackend::default();
+        run_mock(&input, &backend, &["-c", "2.0", "--resume"]);
+        assert!(dir.path().join(resume::STATE_FILE).exists());
+        assert_eq!(backend.decodes(), 1);
+
+        // 已解码的声道文件被复用，只补做合并 / The decoded channel files are reused and only the merge runs
+        run_mock(&input, &backend, &["-c", "2.0", "--resume", "--merge"]);
+        assert_eq!(backend.decodes(), 1);
+        assert!(dir.path().join("movie.wav").exists());
+
+        run_mock(&input, &backend, &["-c", "2.0", "--resume", "--merge"]);
+        assert_eq!(backend.decodes(), 1);
+
+        // 布局变化使记录作废 / A layout change invalidates the record
+        run_mock(&input, &backend, &["-c", "5.1", "--resume", "--merge"]);
+        assert_eq!(backend.decodes(), 2);
+    }
+
+    // 换音轨使记录作废并重新解码 / A track change invalidates the record and decodes again
+    #[test]
+    fn resume_redecodes_when_track_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.mkv");
+        std::fs::write(&input, demux::test_files::mkv_file()).unwrap();
+        let backend = CountingBackend::default();
+        let track = |n: &'static str| ["-c", "2.0", "--resume", "--track", n];
+        run_mock(&input, &backend, &track("2"));
+        run_mock(&input, &backend, &track("2"));
+        assert_eq!(backend.decodes(), 1);
+        run_mock(&input, &backend, &track("4"));
+        assert_eq!(backend.decodes(), 2);
+    }
+
+    // 解码 → 合并 → FLAC → 清理：只留下 FLAC，样本与测试音一致
+    // Decode → merge → FLAC → cleanup: only the FLAC remains and its samples match the tones
+    #[cfg(feature = "native-flac")]
+    #[test]
+    fn mock_decode_merge_flac_cleanup() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.ac3");
+        run_mock(
+            &input,
+            &MockBackend::default(),
+            &["--channels", "5.1", "--merge", "--cleanup", "--flac"],
+        );
+
+        let flac = input.with_extension("flac");
+        assert!(flac.exists());
+        assert!(!input.with_extension("wav").exists());
+        assert!(!dir.path().join("movie.01_L.wav").exists());
+
+        let mut reader = claxon::FlacReader::open(&flac).unwrap();
+        assert_eq!(reader.streaminfo().channels, 6);
+        let samples: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+        let mock = MockBackend::default();
+        assert_eq!(samples.len(), 6 * mock.frames as usize);
+        for channel in 0..6 {
+            let expected = flac_converter::to_24bit(mock.sample(channel, 100));
+            assert_eq!(samples[100 * 6 + channel], expected);
+        }
+    }
+
+    // 流式管道直接写出合并 WAV，不产生单声道文件 / Stream pipeline writes the merged WAV without mono files
+    #[test]
+    fn mock_stream_pipeline_writes_merged_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.ac3");
+        run_mock(
+            &input,
+            &MockBackend::default(),
+            &["--channels", "7.1", "--pipeline", "stream"],
+        );
+
+        let reader = hound::WavReader::open(input.with_extension("wav")).unwrap();
+        assert_eq!(reader.spec().channels, 8);
+        let samples: Vec<f32> = reader.into_samples().map(|s| s.unwrap()).collect();
+        assert_eq!(samples[8 * 10 + 7], MockBackend::default().sample(7, 10));
+        assert!(!dir.path().join("movie.01_L.wav").exists());
+    }
+
+    // auto 模式从码流头得到 2.0 并按声道名命名文件 / Auto mode takes 2.0 from the headers and names files by channel
+    #[test]
+    fn mock_auto_uses_native_layout_names() {
+        let dir = tempfile::tempdir().unwrap();
+        run_mock(
+            &dir.path().join("movie.ac3"),
+            &MockBackend::default(),
+            &["--channels", "auto"],
+        );
+        assert!(dir.path().join("movie.01_L.wav").exists());
+        assert!(dir.path().join("movie.02_R.wav").exists());
+        assert!(!dir.path().join("movie.03_C.wav").exists());
+    }
 }
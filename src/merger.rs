@@ -1,16 +1,23 @@
-use crate::channels::ChannelConfig;
+use crate::adm::AdmLayout;
+use crate::channels::{channel_mask, ChannelConfig};
 use crate::error::{DecodeError, Result};
-use std::fs::OpenOptions;
-use std::io::{Read, Seek, SeekFrom, Write};
+use crate::wav::WavWriter;
+use std::io::Read;
 use std::path::Path;
 
+/// 每次从各声道读取的帧数；峰值内存与时长无关 / Frames read from each channel per chunk; peak memory does not depend on duration
+const MERGE_CHUNK_FRAMES: usize = 8192;
+
+/// 单声道样本源 / Mono sample source
+type SampleSource<'a> = Box<dyn Iterator<Item = Result<f32>> + 'a>;
+
 pub fn merge_channels(
     channel_files: &[std::path::PathBuf],
     output_file: &Path,
     config: Option<&ChannelConfig>,
 ) -> Result<()> {
-    // 目前使用 hound 库的简单方案/For now, we'll use a simple approach via hound library
-    // 将所有单声道 WAV 合成为多声道 WAV/This will combine all mono WAV files into a multi-channel WAV
+    // 分块读取所有单声道 WAV 并边读边交错写出，内存占用与时长无关
+    // Read all mono WAVs in chunks and write interleaved frames as we go; memory does not grow with duration
 
     if channel_files.is_empty() {
         return Err(DecodeError::MergeFailed(
@@ -37,6 +44,7 @@ pub fn merge_channels(
 
     let spec = first_reader.spec();
     let num_frames = first_reader.len() as usize;
+    let mut readers = vec![first_reader];
 
     // Verify all files have the same format / 验证所有文件具有相同的格式
     for file in &channel_files[1..] {
@@ -61,59 +69,154 @@ pub fn merge_channels(
                 "帧数不匹配/Frame count mismatch".to_string(),
             ));
         }
+        readers.push(reader);
     }
 
-    // Read all channel data / 读取所有声道数据
-    let mut all_channels = Vec::new();
-    for file in channel_files {
-        let reader = hound::WavReader::open(file).map_err(|e| {
-            DecodeError::MergeFailed(format!("无法读取 WAV 文件/Cannot read WAV file: {e}"))
+    // 每个读取器各自缓冲，按需拉取样本 / Each reader buffers on its own; samples are pulled on demand
+    let mut sources: Vec<SampleSource<'_>> = readers
+        .iter_mut()
+        .map(|reader| channel_samples(reader, spec.sample_format))
+        .collect();
+
+    // Create output writer / 创建输出写入器（超过 4 GB 自动切换为 RF64 / switches to RF64 past 4 GB）
+    let channels = u16::try_from(channel_files.len()).expect("channels <= u16");
+    let mask = config.map_or(0, |c| channel_mask(&c.names));
+    let mut writer =
+        WavWriter::create(output_file, channels, spec.sample_rate, mask).map_err(|e| {
+            DecodeError::MergeFailed(format!("无法创建输出 WAV/Cannot create output WAV: {e}"))
         })?;
 
-        #[allow(clippy::cast_precision_loss)]
-        let samples: Vec<f32> = match spec.sample_format {
-            hound::SampleFormat::Float => reader
-                .into_samples::<f32>()
-                .collect::<std::result::Result<Vec<_>, _>>()
-                .map_err(|e| {
-                    DecodeError::MergeFailed(format!("无法读取样本/Cannot read samples: {e}"))
-                })?,
-            hound::SampleFormat::Int => {
-                reader
-                    .into_samples::<i32>()
-                    .collect::<std::result::Result<Vec<_>, _>>()
-                    .map_err(|e| {
-                        DecodeError::MergeFailed(format!("无法读取样本/Cannot read samples: {e}"))
-                    })?
-                    .into_iter()
-                    .map(|s| s as f32 / 2_147_483_648.0) // Convert i32 to f32 / 将 i32 转换为 f32
-                    .collect()
-            }
-        };
+    // Interleave and write samples / 交错并写入样本
+    interleave_chunks(&mut sources, num_frames, |sample| {
+        writer
+            .write_sample(sample)
+            .map_err(|e| DecodeError::MergeFailed(format!("无法写入样本/Cannot write sample: {e}")))
+    })?;
+
+    writer.finalize().map_err(|e| {
+        DecodeError::MergeFailed(format!("无法最终化 WAV 文件/Cannot finalize WAV file: {e}"))
+    })?;
 
-        all_channels.push(samples);
+    // 在 WAV 文件备注中写入声道配置信息 / Add channel configuration to WAV file comments
+    if let Some(ch_config) = config {
+        write_channel_comment(output_file, ch_config)?;
     }
 
-    // Create output writer / 创建输出写入器
-    let out_spec = hound::WavSpec {
-        channels: u16::try_from(channel_files.len()).expect("channels <= u16"),
-        sample_rate: spec.sample_rate,
-        bits_per_sample: 32,
-        sample_format: hound::SampleFormat::Float,
+    Ok(())
+}
+
+/// 将单声道读取器包装为 f32 样本源（整数样本按 i32 满量程归一化）
+/// Wrap a mono reader as an f32 sample source (integer samples normalised against i32 full scale)
+fn channel_samples<R: Read>(
+    reader: &mut hound::WavReader<R>,
+    sample_format: hound::SampleFormat,
+) -> SampleSource<'_> {
+    let read_err = |e: hound::Error| {
+        DecodeError::MergeFailed(format!("无法读取样本/Cannot read samples: {e}"))
     };
+    match sample_format {
+        hound::SampleFormat::Float => {
+            Box::new(reader.samples::<f32>().map(move |s| s.map_err(read_err)))
+        }
+        #[allow(clippy::cast_precision_loss)]
+        hound::SampleFormat::Int => Box::new(
+            reader
+                .samples::<i32>()
+                .map(move |s| s.map(|v| v as f32 / 2_147_483_648.0).map_err(read_err)), // Convert i32 to f32 / 将 i32 转换为 f32
+        ),
+    }
+}
 
-    let mut writer = hound::WavWriter::create(output_file, out_spec).map_err(|e| {
-        DecodeError::MergeFailed(format!("无法创建输出 WAV/Cannot create output WAV: {e}"))
-    })?;
+/// 按块从每个声道拉取样本并按帧交错写出；缓冲上限为 `MERGE_CHUNK_FRAMES` 帧
+/// Pull samples from each channel chunk by chunk and emit them frame-interleaved; at most `MERGE_CHUNK_FRAMES` frames are buffered
+fn interleave_chunks<S, W>(sources: &mut [S], num_frames: usize, mut write: W) -> Result<()>
+where
+    S: Iterator<Item = Result<f32>>,
+    W: FnMut(f32) -> Result<()>,
+{
+    let mut chunks = vec![Vec::with_capacity(MERGE_CHUNK_FRAMES); sources.len()];
+    let mut done = 0;
+    while done < num_frames {
+        let n = MERGE_CHUNK_FRAMES.min(num_frames - done);
+        for (source, chunk) in sources.iter_mut().zip(chunks.iter_mut()) {
+            chunk.clear();
+            for _ in 0..n {
+                let sample = source.next().ok_or_else(|| {
+                    DecodeError::MergeFailed(
+                        "声道数据提前结束/Channel data ended early".to_string(),
+                    )
+                })??;
+                chunk.push(sample);
+            }
+        }
+        for frame_idx in 0..n {
+            for chunk in &chunks {
+                write(chunk[frame_idx])?;
+            }
+        }
+        done += n;
+    }
+    Ok(())
+}
 
-    // Interleave and write samples / 交错并写入样本
-    for frame_idx in 0..num_frames {
-        for channel_data in &all_channels {
-            if frame_idx < channel_data.len() {
-                writer.write_sample(channel_data[frame_idx]).map_err(|e| {
-                    DecodeError::MergeFailed(format!("无法写入样本/Cannot write sample: {e}"))
-                })?;
+/// 流式合并的缓冲帧数（决定内存上限）/ Frames buffered per read in streaming merge (bounds memory)
+const STREAM_BUFFER_FRAMES: usize = 4096;
+
+/// 从交错 PCM 流（gst-launch 的 wavenc 输出）直接写出多声道 WAV
+/// Write the final multichannel WAV straight from an interleaved PCM stream (wavenc output from gst-launch)
+///
+/// 流来自不可回写的管道，头中的 RIFF/data 大小不可信，因此 data 一直读到 EOF；
+/// 仅持有固定大小的缓冲区。返回写入的帧数。
+/// The stream comes from a non-seekable pipe, so RIFF/data sizes in its header are not trusted and
+/// data runs to EOF; only a fixed-size buffer is held. Returns the number of frames written.
+pub fn merge_stream<R: Read>(
+    mut source: R,
+    output_file: &Path,
+    config: Option<&ChannelConfig>,
+) -> Result<u64> {
+    let format = read_stream_header(&mut source)?;
+
+    if let Some(ch_config) = config {
+        if !ch_config.names.is_empty() && ch_config.names.len() != usize::from(format.channels) {
+            return Err(DecodeError::MergeFailed(format!(
+                "流声道数 {} 与配置 {} 不匹配/Stream has {} channels, layout {} expects {}",
+                format.channels,
+                ch_config.name,
+                format.channels,
+                ch_config.name,
+                ch_config.names.len()
+            )));
+        }
+    }
+
+    let mask = config.map_or(0, |c| channel_mask(&c.names));
+    let mut writer = WavWriter::create(output_file, format.channels, format.sample_rate, mask)
+        .map_err(|e| {
+            DecodeError::MergeFailed(format!("无法创建输出 WAV/Cannot create output WAV: {e}"))
+        })?;
+
+    let frame_bytes = usize::from(format.channels) * 4;
+    let mut buf = vec![0u8; STREAM_BUFFER_FRAMES * frame_bytes];
+    let mut frames: u64 = 0;
+    loop {
+        let filled = fill_buffer(&mut source, &mut buf)?;
+        let whole = filled - filled % frame_bytes;
+        for bytes in buf[..whole].chunks_exact(4) {
+            let sample = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            writer.write_sample(sample).map_err(|e| {
+                DecodeError::MergeFailed(format!("无法写入样本/Cannot write sample: {e}"))
+            })?;
+        }
+        frames += (whole / frame_bytes) as u64;
+        if filled < buf.len() {
+            if whole != filled {
+                log::warn!(
+                    "流末尾有 {} 字节不完整帧已丢弃/Dropped {} trailing bytes of an incomplete frame",
+                    filled - whole,
+                    filled - whole
+                );
             }
+            break;
         }
     }
 
@@ -121,69 +224,153 @@ pub fn merge_channels(
         DecodeError::MergeFailed(format!("无法最终化 WAV 文件/Cannot finalize WAV file: {e}"))
     })?;
 
-    // 在 WAV 文件备注中写入声道配置信息 / Add channel configuration to WAV file comments
     if let Some(ch_config) = config {
-        let channel_list = ch_config
-            .names
-            .iter()
-            .enumerate()
-            .map(|(idx, name)| format!("{}: {}", idx + 1, name))
-            .collect::<Vec<_>>()
-            .join(", ");
-        let comment = format!("{} [{}]", ch_config.name, channel_list);
-        add_wav_comment(output_file, &comment).map_err(|e| {
-            DecodeError::MergeFailed(format!("无法添加 WAV 备注/Failed to add WAV comment: {e}"))
-        })?;
+        write_channel_comment(output_file, ch_config)?;
     }
 
-    Ok(())
+    Ok(frames)
 }
 
-/// 在 WAV 文件中添加备注信息 / Add comment to WAV file
-/// 将声道配置信息写入 WAV 文件的 LIST chunk 中的 ICOM (comment) 字段
-fn add_wav_comment(file_path: &Path, comment: &str) -> std::io::Result<()> {
-    let comment_bytes = comment.as_bytes();
-    // ICOM 块需要偶数长度的数据（如果奇数则补一个 null byte）
-    let padded_len = (comment_bytes.len() + 1) & !1;
+/// 流头中解析出的格式 / Format parsed from a stream header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StreamFormat {
+    channels: u16,
+    sample_rate: u32,
+}
 
-    // LIST chunk 的结构：
-    // "LIST" (4 bytes) + size (4 bytes) + "INFO" (4 bytes) + "ICOM" (4 bytes) + size (4 bytes) + data
-    let list_size = 4 + 4 + 4 + 4 + padded_len; // INFO + ICOM + ICOM_size + comment data
+/// 解析流式 WAV 头直到 data 块开头（忽略大小字段）/ Parse a streamed WAV header up to the start of data (sizes ignored)
+fn read_stream_header<R: Read>(source: &mut R) -> Result<StreamFormat> {
+    let header_err = |msg: &str| {
+        DecodeError::MergeFailed(format!("无效的 PCM 流头/Invalid PCM stream header: {msg}"))
+    };
 
-    let mut file = OpenOptions::new().read(true).write(true).open(file_path)?;
+    let mut riff = [0u8; 12];
+    source
+        .read_exact(&mut riff)
+        .map_err(|e| header_err(&e.to_string()))?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(header_err("missing RIFF/WAVE"));
+    }
 
-    // Seek 到 RIFF 大小字段（偏移 4）/ Seek to RIFF size field (offset 4)
-    file.seek(SeekFrom::Start(4))?;
+    let mut format = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        source
+            .read_exact(&mut chunk)
+            .map_err(|e| header_err(&e.to_string()))?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        match &chunk[0..4] {
+            b"data" => break,
+            b"fmt " => {
+                let mut fmt = vec![0u8; size + size % 2];
+                source
+                    .read_exact(&mut fmt)
+                    .map_err(|e| header_err(&e.to_string()))?;
+                if size < 16 {
+                    return Err(header_err("fmt chunk too short"));
+                }
+                let tag = u16::from_le_bytes([fmt[0], fmt[1]]);
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits = u16::from_le_bytes([fmt[14], fmt[15]]);
+                // 3 = IEEE float；0xFFFE = EXTENSIBLE（子格式 GUID 首两字节为格式码）
+                // 3 = IEEE float; 0xFFFE = EXTENSIBLE (sub-format GUID starts with the format code)
+                let is_float = match tag {
+                    3 => true,
+                    0xFFFE if size >= 26 => u16::from_le_bytes([fmt[24], fmt[25]]) == 3,
+                    _ => false,
+                };
+                if !is_float || bits != 32 || channels == 0 {
+                    return Err(header_err("expected 32-bit float PCM"));
+                }
+                format = Some(StreamFormat {
+                    channels,
+                    sample_rate,
+                });
+            }
+            _ => {
+                // 跳过其他块 / Skip other chunks
+                std::io::copy(
+                    &mut source.by_ref().take((size + size % 2) as u64),
+                    &mut std::io::sink(),
+                )?;
+            }
+        }
+    }
+
+    format.ok_or_else(|| header_err("data before fmt"))
+}
 
-    // 读取 RIFF 大小 / Read RIFF size
-    let mut riff_size_bytes = [0u8; 4];
-    file.read_exact(&mut riff_size_bytes)?;
-    let mut riff_size = u32::from_le_bytes(riff_size_bytes) as u64;
+/// 尽量填满缓冲区，仅在 EOF 时返回较少字节 / Fill the buffer, returning fewer bytes only at EOF
+fn fill_buffer<R: Read>(source: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match source.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
 
-    // 添加新的 LIST chunk 到文件末尾（在任何现有数据之前）
-    file.seek(SeekFrom::End(0))?;
+/// 将声道配置写入 ICOM 备注 / Write the channel configuration into the ICOM comment
+fn write_channel_comment(output_file: &Path, ch_config: &ChannelConfig) -> Result<()> {
+    let channel_list = ch_config
+        .names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| format!("{}: {}", idx + 1, name))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let comment = format!("{} [{}]", ch_config.name, channel_list);
+    add_wav_comment(output_file, &comment).map_err(|e| {
+        DecodeError::MergeFailed(format!("无法添加 WAV 备注/Failed to add WAV comment: {e}"))
+    })
+}
 
-    // 写入 LIST chunk header
-    file.write_all(b"LIST")?;
-    file.write_all(&(list_size as u32).to_le_bytes())?;
-    file.write_all(b"INFO")?;
+/// 为合并文件添加 ADM 元数据（chna + axml）并改写为 BW64
+/// Add ADM metadata (chna + axml) to a merged file and rewrite it as BW64
+pub fn write_adm_metadata(output_file: &Path, ch_config: &ChannelConfig) -> Result<()> {
+    let layout = AdmLayout::from_config(ch_config)?;
+    let adm_err = |e: std::io::Error| {
+        DecodeError::MergeFailed(format!("无法写入 ADM/Failed to write ADM: {e}"))
+    };
+    let (channels, sample_rate) = crate::wav::read_format(output_file).map_err(adm_err)?;
+    if usize::from(channels) != ch_config.names.len() {
+        return Err(DecodeError::MergeFailed(format!(
+            "声道数与布局不符/Channel count does not match layout: {channels} vs {}",
+            ch_config.names.len()
+        )));
+    }
 
-    // 写入 ICOM subchunk
-    file.write_all(b"ICOM")?;
-    file.write_all(&(comment_bytes.len() as u32).to_le_bytes())?;
-    file.write_all(comment_bytes)?;
+    crate::wav::convert_to_bw64(output_file).map_err(adm_err)?;
+    crate::wav::append_chunk(output_file, b"chna", &layout.chna()).map_err(adm_err)?;
+    crate::wav::append_chunk(output_file, b"axml", layout.axml(sample_rate).as_bytes())
+        .map_err(adm_err)
+}
 
-    // 如果数据长度为奇数，添加 padding byte
+/// 在 WAV 文件中添加备注信息 / Add comment to WAV file
+/// 将声道配置信息写入 WAV 文件的 LIST chunk 中的 ICOM (comment) 字段；RF64 文件更新 ds64 中的 64 位大小
+/// Writes the channel layout into the ICOM (comment) field of a LIST chunk; RF64 files get the 64-bit size in ds64 updated
+fn add_wav_comment(file_path: &Path, comment: &str) -> std::io::Result<()> {
+    let comment_bytes = comment.as_bytes();
+
+    // LIST chunk 的结构：
+    // "LIST" (4 bytes) + size (4 bytes) + "INFO" (4 bytes) + "ICOM" (4 bytes) + size (4 bytes) + data
+    let mut payload = Vec::with_capacity(12 + comment_bytes.len() + 1);
+    payload.extend_from_slice(b"INFO");
+    payload.extend_from_slice(b"ICOM");
+    payload.extend_from_slice(&(comment_bytes.len() as u32).to_le_bytes());
+    payload.extend_from_slice(comment_bytes);
+
+    // ICOM 块需要偶数长度的数据（如果奇数则补一个 null byte）
     if !comment_bytes.len().is_multiple_of(2) {
-        file.write_all(&[0u8])?;
+        payload.push(0);
     }
 
-    // 更新 RIFF 大小
-    riff_size += 4 + 4 + 4 + 4 + padded_len as u64;
-    file.seek(SeekFrom::Start(4))?;
-    file.write_all(&(riff_size as u32).to_le_bytes())?;
-
-    Ok(())
+    crate::wav::append_chunk(file_path, b"LIST", &payload)
 }
 
 #[cfg(test)]
@@ -300,8 +487,193 @@ mod tests {
         let reader = hound::WavReader::open(&out).unwrap();
         assert_eq!(reader.spec().channels, 2);
 
-        // 文件中包含 ICOM 注释块 / file contains ICOM comment chunk
+        // dwChannelMask 由声道名推导（L/R → 0x3）/ dwChannelMask derived from names (L/R → 0x3)
         let bytes = std::fs::read(&out).unwrap();
+        let fmt = bytes.windows(4).position(|w| w == b"fmt ").unwrap();
+        assert_eq!(&bytes[fmt + 8..fmt + 10], &0xFFFEu16.to_le_bytes());
+        assert_eq!(&bytes[fmt + 28..fmt + 32], &0x3u32.to_le_bytes());
+
+        // 文件中包含 ICOM 注释块 / file contains ICOM comment chunk
         assert!(bytes.windows(4).any(|w| w == b"ICOM"));
     }
+
+    // ADM 导出：文件改为 BW64，末尾依次为 chna 与 axml / ADM export: file becomes BW64 with chna then axml at the end
+    #[test]
+    fn write_adm_metadata_produces_bw64_with_chna_and_axml() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.wav");
+        let b = dir.path().join("b.wav");
+        write_mono_f32(&a, &[0.0, 0.1], 48000, 1);
+        write_mono_f32(&b, &[1.0, 1.1], 48000, 1);
+        let out = dir.path().join("out.wav");
+
+        let config = crate::channels::get_config("2.0").unwrap();
+        merge_channels(&[a, b], &out, Some(&config)).unwrap();
+        write_adm_metadata(&out, &config).unwrap();
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(&bytes[0..4], b"BW64");
+        assert_eq!(&bytes[12..16], b"ds64");
+        let riff_size = u64::from_le_bytes(bytes[20..28].try_into().unwrap());
+        assert_eq!(riff_size as usize, bytes.len() - 8);
+
+        let chna = bytes.windows(4).position(|w| w == b"chna").unwrap();
+        let axml = bytes.windows(4).position(|w| w == b"axml").unwrap();
+        assert!(chna < axml);
+        assert_eq!(&bytes[chna + 8..chna + 10], &2u16.to_le_bytes());
+        let xml = String::from_utf8_lossy(&bytes[axml + 8..]);
+        assert!(xml.contains("AP_00010002"));
+    }
+
+    // 声道数与布局不符时拒绝写入 ADM / ADM is refused when the channel count does not match the layout
+    #[test]
+    fn write_adm_metadata_rejects_channel_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.wav");
+        let b = dir.path().join("b.wav");
+        write_mono_f32(&a, &[0.0], 48000, 1);
+        write_mono_f32(&b, &[1.0], 48000, 1);
+        let out = dir.path().join("out.wav");
+        merge_channels(&[a, b], &out, None).unwrap();
+
+        let config = crate::channels::get_config("5.1").unwrap();
+        assert!(write_adm_metadata(&out, &config).is_err());
+    }
+
+    // 长时合成输入：已读取但未写出的样本数始终不超过一个块，内存与时长无关
+    // Long synthetic input: samples read but not yet written never exceed one chunk, so memory is independent of duration
+    #[test]
+    fn interleave_keeps_buffer_bounded_for_long_input() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        const CHANNELS: usize = 4;
+        let num_frames = MERGE_CHUNK_FRAMES * 200 + 123;
+        let pulled = Rc::new(Cell::new(0usize));
+        let mut sources: Vec<_> = (0..CHANNELS)
+            .map(|ch| {
+                let pulled = Rc::clone(&pulled);
+                (0..num_frames).map(move |i| {
+                    pulled.set(pulled.get() + 1);
+                    #[allow(clippy::cast_precision_loss)]
+                    Ok((ch * num_frames + i) as f32)
+                })
+            })
+            .collect();
+
+        let mut written = 0usize;
+        let mut max_pending = 0usize;
+        interleave_chunks(&mut sources, num_frames, |sample| {
+            let frame = written / CHANNELS;
+            let ch = written % CHANNELS;
+            #[allow(clippy::cast_precision_loss)]
+            let expected = (ch * num_frames + frame) as f32;
+            assert_eq!(sample, expected);
+            written += 1;
+            max_pending = max_pending.max(pulled.get() - written);
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(written, num_frames * CHANNELS);
+        assert!(
+            max_pending <= MERGE_CHUNK_FRAMES * CHANNELS,
+            "缓冲超出上限/buffer exceeded bound: {max_pending}"
+        );
+    }
+
+    // 跨多个块的文件合并结果与逐帧交错一致 / Merging a file spanning several chunks matches plain interleaving
+    #[test]
+    fn merge_spanning_multiple_chunks_interleaves_correctly() {
+        let dir = tempfile::tempdir().unwrap();
+        let frames = MERGE_CHUNK_FRAMES * 3 + 17;
+        #[allow(clippy::cast_precision_loss)]
+        let ch0: Vec<f32> = (0..frames).map(|i| i as f32 / frames as f32).collect();
+        let ch1: Vec<f32> = ch0.iter().map(|s| -s).collect();
+        let a = dir.path().join("a.wav");
+        let b = dir.path().join("b.wav");
+        write_mono_f32(&a, &ch0, 48000, 1);
+        write_mono_f32(&b, &ch1, 48000, 1);
+        let out = dir.path().join("out.wav");
+        merge_channels(&[a, b], &out, None).unwrap();
+
+        let reader = hound::WavReader::open(&out).unwrap();
+        let samples: Vec<f32> = reader.into_samples::<f32>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples.len(), frames * 2);
+        for (i, pair) in samples.chunks_exact(2).enumerate() {
+            assert_eq!(pair[0], ch0[i]);
+            assert_eq!(pair[1], ch1[i]);
+        }
+    }
+
+    // 构造 wavenc 流式输出：大小字段为 0，可选 EXTENSIBLE / Build a wavenc-style stream: zero sizes, optional EXTENSIBLE
+    fn stream_bytes(channels: u16, sample_rate: u32, samples: &[f32], extensible: bool) -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(b"RIFF");
+        v.extend_from_slice(&0u32.to_le_bytes());
+        v.extend_from_slice(b"WAVE");
+        v.extend_from_slice(b"fmt ");
+        v.extend_from_slice(&(if extensible { 40u32 } else { 16u32 }).to_le_bytes());
+        v.extend_from_slice(&(if extensible { 0xFFFEu16 } else { 3u16 }).to_le_bytes());
+        v.extend_from_slice(&channels.to_le_bytes());
+        v.extend_from_slice(&sample_rate.to_le_bytes());
+        v.extend_from_slice(&(sample_rate * u32::from(channels) * 4).to_le_bytes());
+        v.extend_from_slice(&(channels * 4).to_le_bytes());
+        v.extend_from_slice(&32u16.to_le_bytes());
+        if extensible {
+            v.extend_from_slice(&22u16.to_le_bytes());
+            v.extend_from_slice(&32u16.to_le_bytes());
+            v.extend_from_slice(&0u32.to_le_bytes());
+            v.extend_from_slice(&[
+                0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38,
+                0x9B, 0x71,
+            ]);
+        }
+        v.extend_from_slice(b"data");
+        v.extend_from_slice(&0u32.to_le_bytes());
+        for s in samples {
+            v.extend_from_slice(&s.to_le_bytes());
+        }
+        v
+    }
+
+    // 流式写入：忽略头中大小，数据读到 EOF / Streaming: header sizes ignored, data read to EOF
+    #[test]
+    fn merge_stream_writes_all_frames_despite_zero_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let samples: Vec<f32> = (0..(STREAM_BUFFER_FRAMES * 2 + 7) * 3)
+            .map(|i| i as f32 / 100_000.0)
+            .collect();
+        let bytes = stream_bytes(3, 48000, &samples, true);
+
+        let frames = merge_stream(std::io::Cursor::new(bytes), &out, None).unwrap();
+        assert_eq!(frames as usize, samples.len() / 3);
+
+        let reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.spec().channels, 3);
+        assert_eq!(reader.spec().sample_rate, 48000);
+        let got: Vec<f32> = reader.into_samples::<f32>().map(|s| s.unwrap()).collect();
+        assert_eq!(got, samples);
+    }
+
+    // 流声道数与配置不符时报错 / Stream channel count must match the layout
+    #[test]
+    fn merge_stream_rejects_layout_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let bytes = stream_bytes(2, 48000, &[0.0; 8], false);
+        let config = crate::channels::get_config("5.1").unwrap();
+        assert!(merge_stream(std::io::Cursor::new(bytes), &out, Some(&config)).is_err());
+    }
+
+    // 非浮点流报错 / Non-float streams are rejected
+    #[test]
+    fn merge_stream_rejects_integer_pcm() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let mut bytes = stream_bytes(2, 48000, &[0.0; 4], false);
+        bytes[20] = 1; // format tag = PCM
+        assert!(merge_stream(std::io::Cursor::new(bytes), &out, None).is_err());
+    }
 }
@@ -0,0 +1,1043 @@
+use crate::channels::{covering_config, get_config, ChannelConfig};
+use crate::demux::{self, InputSource};
+use crate::error::{DecodeError, Result};
+use crate::format::{locate_stream, AudioFormat};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// 用于统计子流数量的帧数 / Frames inspected when counting substreams
+const SUBSTREAM_SCAN_FRAMES: usize = 64;
+
+/// 每帧读取的头部字节数（足以覆盖 BSI 与主同步）/ Header bytes read per frame (enough for the BSI and major sync)
+const HEADER_BYTES: usize = 256;
+
+/// 从容器音轨读取的码流字节数上限（覆盖 SUBSTREAM_SCAN_FRAMES 个 E-AC3 帧）
+/// Bitstream bytes read from a container track (enough for SUBSTREAM_SCAN_FRAMES E-AC3 frames)
+const TRACK_HEAD_BYTES: usize = 256 << 10;
+
+/// 码流探测结果 / Bitstream probe result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbeReport {
+    pub format: String,
+    pub sample_rate: u32,
+    /// 按文件大小与时长计算的平均码率 / Average bitrate from file size and duration
+    pub bitrate_kbps: u32,
+    /// TrueHD 主同步中的峰值码率 / Peak bitrate from the TrueHD major sync
+    pub peak_bitrate_kbps: Option<u32>,
+    pub channels: u32,
+    /// 声道编码模式（如 3/2，E-AC3 的 acmod 或 TrueHD 的声道分配）/ Coding mode (e.g. 3/2: E-AC3 acmod or TrueHD channel assignment)
+    pub channel_mode: String,
+    pub lfe: bool,
+    pub substreams: u32,
+    /// 是否包含 JOC（E-AC3）或 16 声道呈现（TrueHD）的全景声扩展 / Atmos extension present: JOC (E-AC3) or 16-channel presentation (TrueHD)
+    pub atmos: bool,
+    /// JOC 复杂度索引（对象数量上限）/ JOC complexity index (upper bound on objects)
+    pub joc_complexity: Option<u8>,
+    pub dialnorm_db: Option<i32>,
+    pub frames: u64,
+    pub duration_secs: f64,
+}
+
+impl ProbeReport {
+    /// 人类可读的多行文本 / Human-readable multi-line text
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("格式/Format: {}", self.format),
+            format!("采样率/Sample rate: {} Hz", self.sample_rate),
+            format!("平均码率/Average bitrate: {} kbps", self.bitrate_kbps),
+        ];
+        if let Some(peak) = self.peak_bitrate_kbps {
+            lines.push(format!("峰值码率/Peak bitrate: {peak} kbps"));
+        }
+        lines.push(format!(
+            "声道/Channels: {} ({}{})",
+            self.channels,
+            self.channel_mode,
+            if self.lfe { " + LFE" } else { "" }
+        ));
+        lines.push(format!("子流/Substreams: {}", self.substreams));
+        lines.push(format!(
+            "全景声/Atmos: {}",
+            match (self.atmos, self.joc_complexity) {
+                (true, Some(c)) => format!("是/yes (JOC complexity {c})"),
+                (true, None) => "是/yes".to_string(),
+                (false, _) => "否/no".to_string(),
+            }
+        ));
+        if let Some(d) = self.dialnorm_db {
+            lines.push(format!("对白电平/Dialnorm: {d} dB"));
+        }
+        lines.push(format!("帧数/Frames: {}", self.frames));
+        lines.push(format!("时长/Duration: {:.3} s", self.duration_secs));
+        lines.join("\n")
+    }
+}
+
+/// 探测文件中的码流信息，不解码音频 / Probe the bitstream in a file without decoding audio
+pub fn probe(path: &Path, explicit_format: Option<&str>) -> Result<ProbeReport> {
+    let (format, offset) = locate_stream(path, explicit_format)?;
+    let mut file = File::open(path).map_err(|e| {
+        DecodeError::FormatDetectionFailed(format!("无法打开文件/Cannot open file: {e}"))
+    })?;
+    // 跳过前导数据 / Skip leading junk
+    let file_size = file.metadata()?.len().saturating_sub(offset);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    match format {
+        AudioFormat::Ac3 | AudioFormat::Eac3 | AudioFormat::Eac3Joc => {
+            probe_ac3(&mut reader, file_size)
+        }
+        AudioFormat::TrueHD => probe_truehd(&mut reader, file_size),
+    }
+}
+
+fn probe_failed(msg: &str) -> DecodeError {
+    DecodeError::FormatDetectionFailed(msg.to_string())
+}
+
+/// 读取下一帧头部，返回读取的字节数 / Read the next frame header, returning the bytes read
+fn read_header<R: Read>(reader: &mut R, buf: &mut [u8; HEADER_BYTES]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// 跳到下一帧（当前已读 `read` 字节）/ Skip to the next frame (`read` bytes of this one already consumed)
+fn skip_to_next<R: Seek>(reader: &mut BufReader<R>, frame_bytes: usize, read: usize) -> Result<()> {
+    reader.seek_relative(frame_bytes as i64 - read as i64)?;
+    Ok(())
+}
+
+fn bitrate_kbps(file_size: u64, duration_secs: f64) -> u32 {
+    if duration_secs > 0.0 {
+        (file_size as f64 * 8.0 / duration_secs / 1000.0).round() as u32
+    } else {
+        0
+    }
+}
+
+/// 高位在前的位读取器 / MSB-first bit reader
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..bits {
+            let byte = *self.data.get(self.pos / 8)?;
+            value = (value << 1) | u32::from((byte >> (7 - self.pos % 8)) & 1);
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        self.read(1).map(|b| b == 1)
+    }
+
+    fn skip(&mut self, bits: usize) -> Option<()> {
+        if self.pos + bits > self.data.len() * 8 {
+            return None;
+        }
+        self.pos += bits;
+        Some(())
+    }
+}
+
+/// AC-3 / E-AC3 同步帧头信息 / AC-3 / E-AC3 syncframe header information
+#[derive(Debug, Clone, PartialEq)]
+struct Ac3Frame {
+    bsid: u32,
+    /// 0 = 独立，1 = 依赖，2 = AC-3 转换 / 0 = independent, 1 = dependent, 2 = AC-3 convert
+    strmtyp: u32,
+    substreamid: u32,
+    frame_bytes: usize,
+    sample_rate: u32,
+    samples: u32,
+    acmod: u32,
+    lfeon: bool,
+    dialnorm: u32,
+    chanmap: Option<u32>,
+    /// JOC 扩展存在时为复杂度索引 / Complexity index when the JOC extension is present
+    joc_complexity: Option<u8>,
+}
+
+impl Ac3Frame {
+    fn is_primary(&self) -> bool {
+        self.strmtyp != 1 && self.substreamid == 0
+    }
+}
+
+const AC3_RATES: [u32; 3] = [48000, 44100, 32000];
+const AC3_BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+/// 按 bsid 解析 AC-3（≤8）或 E-AC3（11–16）同步帧 / Parse an AC-3 (bsid ≤ 8) or E-AC3 (bsid 11–16) syncframe
+fn parse_ac3_frame(data: &[u8]) -> Option<Ac3Frame> {
+    if data.len() < 6 || data[0] != 0x0B || data[1] != 0x77 {
+        return None;
+    }
+    // bsid 在两种语法中位于同一位置 / bsid sits at the same position in both syntaxes
+    let bsid = u32::from(data[5] >> 3);
+    match bsid {
+        0..=8 => parse_ac3_bsi(data, bsid),
+        11..=16 => parse_eac3_bsi(data, bsid),
+        _ => None,
+    }
+}
+
+fn parse_ac3_bsi(data: &[u8], bsid: u32) -> Option<Ac3Frame> {
+    let mut r = BitReader::new(&data[2..]);
+    r.skip(16)?; // crc1
+    let fscod = r.read(2)? as usize;
+    let frmsizecod = r.read(6)? as usize;
+    let sample_rate = *AC3_RATES.get(fscod)?;
+    let bitrate = *AC3_BITRATES.get(frmsizecod / 2)?;
+    // 每帧 16 位字数；44.1 kHz 奇数码多一个字 / 16-bit words per frame; odd codes at 44.1 kHz carry one extra word
+    let mut words = bitrate * 96_000 / sample_rate;
+    if sample_rate == 44100 {
+        words += (frmsizecod & 1) as u32;
+    }
+
+    r.skip(5 + 3)?; // bsid, bsmod
+    let acmod = r.read(3)?;
+    if acmod & 1 != 0 && acmod != 1 {
+        r.skip(2)?; // cmixlev
+    }
+    if acmod & 4 != 0 {
+        r.skip(2)?; // surmixlev
+    }
+    if acmod == 2 {
+        r.skip(2)?; // dsurmod
+    }
+    let lfeon = r.flag()?;
+    let dialnorm = r.read(5)?;
+    Some(Ac3Frame {
+        bsid,
+        strmtyp: 0,
+        substreamid: 0,
+        frame_bytes: words as usize * 2,
+        sample_rate,
+        samples: 1536,
+        acmod,
+        lfeon,
+        dialnorm,
+        chanmap: None,
+        joc_complexity: None,
+    })
+}
+
+/// E-AC3 BSI（ETSI TS 102 366 附录 E）完整解析至 addbsi
+/// E-AC3 BSI (ETSI TS 102 366 Annex E) parsed in full up to addbsi
+fn parse_eac3_bsi(data: &[u8], bsid: u32) -> Option<Ac3Frame> {
+    let mut r = BitReader::new(&data[2..]);
+    let strmtyp = r.read(2)?;
+    let substreamid = r.read(3)?;
+    let frmsiz = r.read(11)?;
+    let fscod = r.read(2)?;
+    let (sample_rate, numblkscod) = if fscod == 3 {
+        let fscod2 = r.read(2)? as usize;
+        (*AC3_RATES.get(fscod2)? / 2, 3)
+    } else {
+        (AC3_RATES[fscod as usize], r.read(2)?)
+    };
+    let numblks = [1u32, 2, 3, 6][numblkscod as usize];
+    let acmod = r.read(3)?;
+    let lfeon = r.flag()?;
+    r.skip(5)?; // bsid
+    let dialnorm = r.read(5)?;
+    if r.flag()? {
+        r.skip(8)?; // compr
+    }
+    if acmod == 0 {
+        r.skip(5)?; // dialnorm2
+        if r.flag()? {
+            r.skip(8)?; // compr2
+        }
+    }
+    let mut chanmap = None;
+    if strmtyp == 1 && r.flag()? {
+        chanmap = Some(r.read(16)?);
+    }
+    if r.flag()? {
+        // mixmdate
+        if acmod > 2 {
+            r.skip(2)?; // dmixmod
+        }
+        if acmod & 1 != 0 && acmod > 2 {
+            r.skip(6)?; // ltrtcmixlev, lorocmixlev
+        }
+        if acmod & 4 != 0 {
+            r.skip(6)?; // ltrtsurmixlev, lorosurmixlev
+        }
+        if lfeon && r.flag()? {
+            r.skip(5)?; // lfemixlevcod
+        }
+        if strmtyp == 0 {
+            if r.flag()? {
+                r.skip(6)?; // pgmscl
+            }
+            if acmod == 0 && r.flag()? {
+                r.skip(6)?; // pgmscl2
+            }
+            if r.flag()? {
+                r.skip(6)?; // extpgmscl
+            }
+            match r.read(2)? {
+                1 => r.skip(5)?, // premixcmpsel, drcsrc, premixcmpscl
+                2 => r.skip(12)?,
+                3 => {
+                    let mixdeflen = r.read(5)? as usize;
+                    r.skip(8 * (mixdeflen + 2))?;
+                }
+                _ => {}
+            }
+            if acmod < 2 {
+                if r.flag()? {
+                    r.skip(14)?; // panmean, paninfo
+                }
+                if acmod == 0 && r.flag()? {
+                    r.skip(14)?; // panmean2, paninfo2
+                }
+            }
+            if r.flag()? {
+                // frmmixcfginfoe
+                if numblkscod == 0 {
+                    r.skip(5)?;
+                } else {
+                    for _ in 0..numblks {
+                        if r.flag()? {
+                            r.skip(5)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if r.flag()? {
+        // infomdate
+        r.skip(3 + 1 + 1)?; // bsmod, copyrightb, origbs
+        if acmod == 2 {
+            r.skip(4)?; // dsurmod, dheadphonmod
+        }
+        if acmod >= 6 {
+            r.skip(2)?; // dsurexmod
+        }
+        if r.flag()? {
+            r.skip(8)?; // mixlevel, roomtyp, adconvtyp
+        }
+        if acmod == 0 && r.flag()? {
+            r.skip(8)?;
+        }
+        if fscod < 3 {
+            r.skip(1)?; // sourcefscod
+        }
+    }
+    if strmtyp == 0 && numblkscod != 3 {
+        r.skip(1)?; // convsync
+    }
+    if strmtyp == 2 {
+        let blkid = numblkscod == 3 || r.flag()?;
+        if blkid {
+            r.skip(6)?; // frmsizecod
+        }
+    }
+    let mut joc_complexity = None;
+    if r.flag()? {
+        // addbsi：首字节最低位为 flag_ec3_extension_type_a（JOC），其后一字节为复杂度索引
+        // addbsi: the LSB of the first byte is flag_ec3_extension_type_a (JOC), followed by a complexity index byte
+        let _addbsil = r.read(6)?;
+        r.skip(7)?;
+        if r.flag()? {
+            joc_complexity = Some(r.read(8)? as u8);
+        }
+    }
+
+    Some(Ac3Frame {
+        bsid,
+        strmtyp,
+        substreamid,
+        frame_bytes: (frmsiz as usize + 1) * 2,
+        sample_rate,
+        samples: 256 * numblks,
+        acmod,
+        lfeon,
+        dialnorm,
+        chanmap,
+        joc_complexity,
+    })
+}
+
+/// acmod 对应的声道模式与 chanmap 位置位（位 0 = L … 位 15 = LFE，高位在前编号）
+/// Coding mode and chanmap location bits for an acmod (bit 0 = L … bit 15 = LFE, numbered from the MSB)
+fn acmod_layout(acmod: u32) -> (&'static str, u32) {
+    const L: u32 = 1 << 15;
+    const C: u32 = 1 << 14;
+    const R: u32 = 1 << 13;
+    const LS: u32 = 1 << 12;
+    const RS: u32 = 1 << 11;
+    const CS: u32 = 1 << 8;
+    match acmod {
+        0 => ("1+1", L | R),
+        1 => ("1/0", C),
+        2 => ("2/0", L | R),
+        3 => ("3/0", L | C | R),
+        4 => ("2/1", L | R | CS),
+        5 => ("3/1", L | C | R | CS),
+        6 => ("2/2", L | R | LS | RS),
+        _ => ("3/2", L | C | R | LS | RS),
+    }
+}
+
+/// chanmap 位集合对应的声道数（成对位置计 2）/ Channel count of a chanmap bit set (pair locations count twice)
+fn chanmap_channels(chanmap: u32) -> u32 {
+    // 自高位起：L C R Ls Rs Lc/Rc Lrs/Rrs Cs Ts Lsd/Rsd Lw/Rw Lvh/Rvh Cvh Lts/Rts LFE2 LFE
+    const WIDTH: [u32; 16] = [1, 1, 1, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1, 2, 1, 1];
+    (0..16)
+        .filter(|bit| chanmap & (1 << (15 - bit)) != 0)
+        .map(|bit| WIDTH[bit])
+        .sum()
+}
+
+/// 解析 AC-3/E-AC3 帧头，返回帧长（字节）/ Parse an AC-3/E-AC3 frame header, returning the frame length in bytes
+pub(crate) fn ac3_frame_bytes(data: &[u8]) -> Option<usize> {
+    parse_ac3_frame(data).map(|f| f.frame_bytes)
+}
+
+/// 是否为可解析的 TrueHD 主同步（含 0xB752 签名）/ Whether this is a parsable TrueHD major sync (with the 0xB752 signature)
+pub(crate) fn is_major_sync(data: &[u8]) -> bool {
+    parse_major_sync(data).is_some()
+}
+
+/// 扫描前若干帧，按 bsid 与 JOC 扩展区分 AC-3、E-AC3 与 E-AC3+JOC
+/// Scan the leading frames and tell AC-3, E-AC3 and E-AC3+JOC apart by bsid and the JOC extension
+pub(crate) fn classify_ac3<R: Read + Seek>(reader: &mut BufReader<R>) -> Result<AudioFormat> {
+    let mut buf = [0u8; HEADER_BYTES];
+    let mut format = None;
+    for _ in 0..SUBSTREAM_SCAN_FRAMES {
+        let read = read_header(reader, &mut buf)?;
+        let Some(frame) = parse_ac3_frame(&buf[..read]) else {
+            break;
+        };
+        if frame.joc_complexity.is_some() {
+            return Ok(AudioFormat::Eac3Joc);
+        }
+        // AC-3 核心加 E-AC3 依赖子流按 E-AC3 处理 / An AC-3 core with E-AC3 dependent substreams counts as E-AC3
+        if frame.bsid > 10 || format.is_none() {
+            format = Some(if frame.bsid <= 8 {
+                AudioFormat::Ac3
+            } else {
+                AudioFormat::Eac3
+            });
+        }
+        skip_to_next(reader, frame.frame_bytes, read)?;
+    }
+    format.ok_or_else(|| {
+        probe_failed("无法解析 AC-3/E-AC3 帧头/Cannot parse AC-3/E-AC3 frame header")
+    })
+}
+
+fn probe_ac3<R: Read + Seek>(reader: &mut BufReader<R>, file_size: u64) -> Result<ProbeReport> {
+    let mut buf = [0u8; HEADER_BYTES];
+    let mut first: Option<Ac3Frame> = None;
+    let mut substreams = std::collections::BTreeSet::new();
+    let mut locations = 0u32;
+    let mut joc_complexity = None;
+    let mut primary_frames = 0u64;
+    let mut scanned = 0usize;
+
+    loop {
+        let read = read_header(reader, &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        let Some(frame) = parse_ac3_frame(&buf[..read]) else {
+            if first.is_none() {
+                return Err(probe_failed(
+                    "无法解析 AC-3/E-AC3 帧头/Cannot parse AC-3/E-AC3 frame header",
+                ));
+            }
+            // 同步丢失：按已统计的帧报告 / Sync lost: report the frames counted so far
+            log::warn!("同步丢失，停止统计/Sync lost, stopping the frame count");
+            break;
+        };
+
+        if scanned < SUBSTREAM_SCAN_FRAMES {
+            scanned += 1;
+            substreams.insert((frame.strmtyp == 1, frame.substreamid));
+            if frame.is_primary() {
+                locations |= acmod_layout(frame.acmod).1 | if frame.lfeon { 1 } else { 0 };
+            } else if let Some(map) = frame.chanmap {
+                locations |= map;
+            }
+            joc_complexity = joc_complexity.or(frame.joc_complexity);
+        }
+        if frame.is_primary() {
+            primary_frames += 1;
+            if first.is_none() {
+                first = Some(frame.clone());
+            }
+        }
+        skip_to_next(reader, frame.frame_bytes, read)?;
+    }
+
+    let first =
+        first.ok_or_else(|| probe_failed("未找到独立子流/No independent substream found"))?;
+    let duration_secs =
+        primary_frames as f64 * f64::from(first.samples) / f64::from(first.sample_rate);
+    Ok(ProbeReport {
+        format: if first.bsid <= 8 { "AC-3" } else { "E-AC3" }.to_string(),
+        sample_rate: first.sample_rate,
+        bitrate_kbps: bitrate_kbps(file_size, duration_secs),
+        peak_bitrate_kbps: None,
+        channels: chanmap_channels(locations),
+        channel_mode: acmod_layout(first.acmod).0.to_string(),
+        lfe: first.lfeon,
+        substreams: substreams.len() as u32,
+        atmos: joc_complexity.is_some(),
+        joc_complexity,
+        dialnorm_db: Some(
+            -(if first.dialnorm == 0 {
+                31
+            } else {
+                first.dialnorm
+            } as i32),
+        ),
+        frames: primary_frames,
+        duration_secs,
+    })
+}
+
+/// TrueHD 主同步信息 / TrueHD major sync information
+#[derive(Debug, Clone, PartialEq)]
+struct MajorSync {
+    sample_rate: u32,
+    /// 每个访问单元的采样数 / Samples per access unit
+    au_samples: u32,
+    channels_6ch: u32,
+    channels_8ch: u32,
+    assignment_6ch: u32,
+    assignment_8ch: u32,
+    peak_bitrate: u32,
+    substreams: u32,
+    substream_info: u32,
+}
+
+/// TrueHD 声道分配中每一位代表的声道数 / Channels represented by each TrueHD channel assignment bit
+fn truehd_channels(assignment: u32) -> u32 {
+    // LR C LFE LRs LRvh LRc LRrs Cs Ts LRsd LRw Cvh LFE2
+    const COUNT: [u32; 13] = [2, 1, 1, 2, 2, 2, 2, 1, 1, 2, 2, 1, 1];
+    (0..13)
+        .filter(|bit| assignment & (1 << bit) != 0)
+        .map(|bit| COUNT[bit])
+        .sum()
+}
+
+/// 解析从 0xF8726FBA 开始的主同步 / Parse a major sync starting at 0xF8726FBA
+fn parse_major_sync(data: &[u8]) -> Option<MajorSync> {
+    if data.len() < 28 || data[0..4] != [0xF8, 0x72, 0x6F, 0xBA] {
+        return None;
+    }
+    let mut r = BitReader::new(&data[4..]);
+    let ratebits = r.read(4)?;
+    let base = if ratebits & 8 == 0 { 48000 } else { 44100 };
+    if ratebits & 7 > 2 {
+        return None;
+    }
+    let sample_rate = base << (ratebits & 7);
+    r.skip(4)?; // 6ch/8ch multichannel type, reserved
+    r.skip(2 + 2)?; // 2ch/6ch presentation channel modifier
+    let assignment_6ch = r.read(5)?;
+    r.skip(2)?; // 8ch presentation channel modifier
+    let assignment_8ch = r.read(13)?;
+    if r.read(16)? != 0xB752 {
+        return None;
+    }
+    r.skip(16 + 16)?; // flags, reserved
+    r.skip(1)?; // variable_rate
+    let peak = r.read(15)?;
+    let substreams = r.read(4)?;
+    r.skip(2 + 2)?; // reserved, extended_substream_info
+    let substream_info = r.read(8)?;
+    Some(MajorSync {
+        sample_rate,
+        au_samples: 40 << (ratebits & 7),
+        channels_6ch: truehd_channels(assignment_6ch),
+        channels_8ch: truehd_channels(assignment_8ch),
+        assignment_6ch,
+        assignment_8ch,
+        peak_bitrate: (peak * sample_rate + 8) >> 4,
+        substreams,
+        substream_info,
+    })
+}
+
+fn probe_truehd<R: Read + Seek>(reader: &mut BufReader<R>, file_size: u64) -> Result<ProbeReport> {
+    let mut buf = [0u8; HEADER_BYTES];
+    let read = read_header(reader, &mut buf)?;
+    // 主同步位于访问单元头之后 4 字节处 / The major sync follows the 4-byte access unit header
+    let start = buf[..read]
+        .windows(4)
+        .position(|w| w == [0xF8, 0x72, 0x6F, 0xBA])
+        .filter(|&i| i >= 4)
+        .ok_or_else(|| probe_failed("未找到 TrueHD 主同步/TrueHD major sync not found"))?;
+    let sync = parse_major_sync(&buf[start..read])
+        .ok_or_else(|| probe_failed("无法解析 TrueHD 主同步/Cannot parse TrueHD major sync"))?;
+
+    reader.seek_relative((start - 4) as i64 - read as i64)?;
+    let mut units = 0u64;
+    let mut header = [0u8; 4];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        // 访问单元长度以 16 位字计 / Access unit length is counted in 16-bit words
+        let words = (usize::from(header[0] & 0x0F) << 8) | usize::from(header[1]);
+        if words < 2 {
+            log::warn!("访问单元长度无效，停止统计/Invalid access unit length, stopping the count");
+            break;
+        }
+        units += 1;
+        reader.seek_relative(words as i64 * 2 - 4)?;
+    }
+
+    let duration_secs = units as f64 * f64::from(sync.au_samples) / f64::from(sync.sample_rate);
+    let channels = sync.channels_8ch.max(sync.channels_6ch);
+    Ok(ProbeReport {
+        format: "TrueHD".to_string(),
+        sample_rate: sync.sample_rate,
+        bitrate_kbps: bitrate_kbps(file_size, duration_secs),
+        peak_bitrate_kbps: Some(sync.peak_bitrate / 1000),
+        channels,
+        channel_mode: format!("0x{:02X}", sync.assignment_6ch),
+        lfe: sync.assignment_6ch & 0b100 != 0,
+        substreams: sync.substreams,
+        // 16 声道呈现（全景声）以 substream_info 最高位标示 / The 16-channel (Atmos) presentation is flagged by the top bit of substream_info
+        atmos: sync.substream_info & 0x80 != 0,
+        joc_complexity: None,
+        dialnorm_db: None,
+        frames: units,
+        duration_secs,
+    })
+}
+
+/// chanmap 位置位对应的声道名；没有对应名称的位置（Lc/Rc、Cs 等）被略去
+/// Channel names for chanmap location bits; locations without a matching name (Lc/Rc, Cs, ...) are dropped
+fn ac3_location_names(locations: u32) -> Vec<&'static str> {
+    // 自高位起：L C R Ls Rs Lc/Rc Lrs/Rrs Cs Ts Lsd/Rsd Lw/Rw Lvh/Rvh Cvh Lts/Rts LFE2 LFE
+    const NAMES: [&[&str]; 16] = [
+        &["L"],
+        &["C"],
+        &["R"],
+        &["Ls"],
+        &["Rs"],
+        &[],
+        &["Lrs", "Rrs"],
+        &[],
+        &[],
+        &[],
+        &["Lw", "Rw"],
+        &["Ltf", "Rtf"],
+        &[],
+        &["Ltm", "Rtm"],
+        &[],
+        &["LFE"],
+    ];
+    (0..16)
+        .filter(|bit| locations & (1 << (15 - bit)) != 0)
+        .flat_map(|bit| NAMES[bit].iter().copied())
+        .collect()
+}
+
+/// TrueHD 声道分配对应的声道名（同样略去无对应名称的位置）/ Channel names for a TrueHD channel assignment (unnamed locations dropped likewise)
+fn truehd_location_names(assignment: u32) -> Vec<&'static str> {
+    // LR C LFE LRs LRvh LRc LRrs Cs Ts LRsd LRw Cvh LFE2
+    const NAMES: [&[&str]; 13] = [
+        &["L", "R"],
+        &["C"],
+        &["LFE"],
+        &["Ls", "Rs"],
+        &["Ltf", "Rtf"],
+        &[],
+        &["Lrs", "Rrs"],
+        &[],
+        &[],
+        &[],
+        &["Lw", "Rw"],
+        &[],
+        &[],
+    ];
+    (0..13)
+        .filter(|bit| assignment & (1 << bit) != 0)
+        .flat_map(|bit| NAMES[bit].iter().copied())
+        .collect()
+}
+
+/// 由码流头确定原生声道布局（无需试探解码）；全景声内容使用最高布局 9.1.6（macOS 上的 TrueHD 除外）
+/// 容器输入读取所选音轨开头的载荷。
+/// Find the native channel layout from the bitstream headers (no trial decoding); Atmos content uses the highest layout, 9.1.6 (except TrueHD on macOS).
+/// Container inputs read the payload at the start of the selected track.
+pub fn native_config(source: &InputSource, format: AudioFormat) -> Result<ChannelConfig> {
+    let Some(track) = &source.demux else {
+        let mut file = File::open(&source.path).map_err(|e| {
+            DecodeError::FormatDetectionFailed(format!("无法打开文件/Cannot open file: {e}"))
+        })?;
+        file.seek(SeekFrom::Start(source.offset))?;
+        return native_config_from(&mut BufReader::new(file), format);
+    };
+    let head = demux::read_track_head(&source.path, track, TRACK_HEAD_BYTES)?;
+    // 从第一个同步字开始（M2TS 的 TrueHD 音轨与 AC-3 核心交错）
+    // Start at the first sync word (TrueHD tracks in M2TS are interleaved with an AC-3 core)
+    let start = if format == AudioFormat::TrueHD {
+        head.windows(4)
+            .position(|w| w == [0xF8, 0x72, 0x6F, 0xBA])
+            .map(|i| i.saturating_sub(4))
+    } else {
+        head.windows(2).position(|w| w == [0x0B, 0x77])
+    }
+    .ok_or_else(|| probe_failed("音轨开头没有同步字/No sync word at the start of the track"))?;
+    native_config_from(&mut BufReader::new(Cursor::new(&head[start..])), format)
+}
+
+fn native_config_from<R: Read + Seek>(
+    reader: &mut BufReader<R>,
+    format: AudioFormat,
+) -> Result<ChannelConfig> {
+    let mut buf = [0u8; HEADER_BYTES];
+
+    let (names, atmos) = if format == AudioFormat::TrueHD {
+        let read = read_header(reader, &mut buf)?;
+        let sync = buf[..read]
+            .windows(4)
+            .position(|w| w == [0xF8, 0x72, 0x6F, 0xBA])
+            .and_then(|i| parse_major_sync(&buf[i..read]))
+            .ok_or_else(|| probe_failed("无法解析 TrueHD 主同步/Cannot parse TrueHD major sync"))?;
+        let assignment = if sync.assignment_8ch != 0 {
+            sync.assignment_8ch
+        } else {
+            sync.assignment_6ch
+        };
+        // macOS 插件只能解码前 8 声道，全景声 TrueHD 在此取 8 声道呈现
+        // The macOS plugin only decodes the first 8 channels, so Atmos TrueHD takes the 8-channel presentation there
+        (
+            truehd_location_names(assignment),
+            sync.substream_info & 0x80 != 0 && !cfg!(target_os = "macos"),
+        )
+    } else {
+        // 与 probe 相同：独立子流给出基础布局，依赖子流的 chanmap 补充其余位置
+        // As in probe: the independent substream gives the base layout, dependent chanmaps add the rest
+        let mut locations = 0u32;
+        let mut atmos = false;
+        for _ in 0..SUBSTREAM_SCAN_FRAMES {
+            let read = read_header(reader, &mut buf)?;
+            let Some(frame) = parse_ac3_frame(&buf[..read]) else {
+                break;
+            };
+            if frame.is_primary() {
+                locations |= acmod_layout(frame.acmod).1 | u32::from(frame.lfeon);
+            } else if let Some(map) = frame.chanmap {
+                locations |= map;
+            }
+            atmos |= frame.joc_complexity.is_some();
+            skip_to_next(reader, frame.frame_bytes, read)?;
+        }
+        (ac3_location_names(locations), atmos)
+    };
+
+    let config = if atmos {
+        get_config("9.1.6").ok()
+    } else {
+        covering_config(&names)
+    };
+    config.ok_or_else(|| {
+        probe_failed(
+            "无法将码流声道映射到支持的布局/Cannot map the stream channels to a supported layout",
+        )
+    })
+}
+
+/// 测试用的合成帧 / Synthetic frames for tests
+#[cfg(test)]
+pub(crate) mod test_frames {
+    /// 测试用位写入器 / Bit writer for building synthetic frames
+    #[derive(Default)]
+    pub(crate) struct Bits {
+        bytes: Vec<u8>,
+        bit: usize,
+    }
+
+    impl Bits {
+        pub(crate) fn put(&mut self, value: u32, bits: usize) -> &mut Self {
+            for i in (0..bits).rev() {
+                if self.bit.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let b = ((value >> i) & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= b << (7 - self.bit % 8);
+                self.bit += 1;
+            }
+            self
+        }
+    }
+
+    /// E-AC3 帧：5.1、48 kHz、6 块，可选 JOC 扩展 / E-AC3 frame: 5.1, 48 kHz, 6 blocks, optional JOC extension
+    pub(crate) fn eac3_frame(
+        strmtyp: u32,
+        substreamid: u32,
+        frame_bytes: usize,
+        joc: Option<u8>,
+    ) -> Vec<u8> {
+        let mut b = Bits::default();
+        b.put(0x0B77, 16)
+            .put(strmtyp, 2)
+            .put(substreamid, 3)
+            .put((frame_bytes / 2 - 1) as u32, 11)
+            .put(0, 2) // fscod 48 kHz
+            .put(3, 2) // numblkscod: 6 blocks
+            .put(if strmtyp == 1 { 2 } else { 7 }, 3) // acmod
+            .put(u32::from(strmtyp != 1), 1) // lfeon
+            .put(16, 5) // bsid
+            .put(27, 5) // dialnorm
+            .put(0, 1); // compre
+        if strmtyp == 1 {
+            b.put(1, 1).put(1 << (15 - 6), 16); // chanmap: Lrs/Rrs
+        }
+        b.put(0, 1) // mixmdate
+            .put(1, 1) // infomdate
+            .put(0, 5) // bsmod, copyrightb, origbs
+            .put(0, if strmtyp == 1 { 4 } else { 2 }) // dsurmod+dheadphonmod (2/0) or dsurexmod (3/2)
+            .put(0, 1) // audprodie
+            .put(0, 1); // sourcefscod
+        match joc {
+            Some(c) => b.put(1, 1).put(1, 6).put(1, 8).put(u32::from(c), 8),
+            None => b.put(0, 1),
+        };
+        let mut bytes = b.bytes;
+        bytes.resize(frame_bytes, 0);
+        bytes
+    }
+
+    /// AC-3 帧：2/0、48 kHz、192 kbps / AC-3 frame: 2/0, 48 kHz, 192 kbps
+    pub(crate) fn ac3_frame() -> Vec<u8> {
+        let mut b = Bits::default();
+        b.put(0x0B77, 16)
+            .put(0, 16) // crc1
+            .put(0, 2) // fscod
+            .put(20, 6) // frmsizecod: 192 kbps
+            .put(8, 5) // bsid
+            .put(0, 3) // bsmod
+            .put(2, 3) // acmod 2/0
+            .put(0, 2) // dsurmod
+            .put(0, 1) // lfeon
+            .put(24, 5); // dialnorm
+        let mut bytes = b.bytes;
+        bytes.resize(768, 0);
+        bytes
+    }
+
+    /// TrueHD 访问单元；`major` 时带主同步 / TrueHD access unit; carries a major sync when `major`
+    pub(crate) fn truehd_unit(major: bool, atmos: bool) -> Vec<u8> {
+        let words = 40u32;
+        let mut b = Bits::default();
+        b.put(0xF, 4).put(words, 12).put(0, 16);
+        if major {
+            b.put(0xF872_6FBA, 32)
+                .put(0, 4) // ratebits: 48 kHz
+                .put(0, 4)
+                .put(0, 4)
+                .put(0b01111, 5) // 6ch: LR C LFE LRs → 5.1
+                .put(0, 2)
+                .put(0b000_0100_1111, 13) // 8ch: + LRrs → 7.1
+                .put(0xB752, 16)
+                .put(0, 32)
+                .put(1, 1)
+                .put(0x1000, 15) // peak_data_rate
+                .put(if atmos { 4 } else { 3 }, 4)
+                .put(0, 4)
+                .put(if atmos { 0x80 } else { 0x00 }, 8);
+        }
+        let mut bytes = b.bytes;
+        bytes.resize(words as usize * 2, 0);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_frames::*;
+    use super::*;
+    use std::io::Cursor;
+
+    // 按 bsid 与 JOC 区分 AC-3、E-AC3、E-AC3+JOC / AC-3, E-AC3 and E-AC3+JOC told apart by bsid and JOC
+    #[test]
+    fn classifies_ac3_eac3_and_joc() {
+        let classify =
+            |data: Vec<u8>| classify_ac3(&mut BufReader::new(Cursor::new(data))).unwrap();
+        let joc: Vec<u8> = (0..3)
+            .flat_map(|_| eac3_frame(0, 0, 1536, Some(16)))
+            .collect();
+        assert_eq!(classify(joc), AudioFormat::Eac3Joc);
+        let plain: Vec<u8> = (0..3).flat_map(|_| eac3_frame(0, 0, 1536, None)).collect();
+        assert_eq!(classify(plain), AudioFormat::Eac3);
+        let ac3: Vec<u8> = (0..3).flat_map(|_| ac3_frame()).collect();
+        assert_eq!(classify(ac3), AudioFormat::Ac3);
+        // AC-3 核心 + E-AC3 依赖子流 / AC-3 core + E-AC3 dependent substream
+        let mut mixed = ac3_frame();
+        mixed.extend(eac3_frame(1, 0, 512, None));
+        assert_eq!(classify(mixed), AudioFormat::Eac3);
+        assert!(classify_ac3(&mut BufReader::new(Cursor::new(vec![0u8; 16]))).is_err());
+    }
+
+    // 原生布局：码流声道映射到最小覆盖布局，全景声取 9.1.6
+    // Native layout: stream channels map to the smallest covering layout, Atmos takes 9.1.6
+    #[test]
+    fn native_config_from_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let layout = |name: &str, data: Vec<u8>| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, data).unwrap();
+            let (source, format) = demux::open_input(&path, None, None).unwrap();
+            native_config(&source, format).unwrap().name
+        };
+        let eac3_71: Vec<u8> = (0..4)
+            .flat_map(|_| {
+                let mut f = eac3_frame(0, 0, 1536, None);
+                f.extend(eac3_frame(1, 0, 512, None));
+                f
+            })
+            .collect();
+        assert_eq!(layout("a.ec3", eac3_71), "7.1");
+        let joc: Vec<u8> = (0..4)
+            .flat_map(|_| eac3_frame(0, 0, 1536, Some(16)))
+            .collect();
+        assert_eq!(layout("b.ec3", joc), "9.1.6");
+        assert_eq!(
+            layout("c.ac3", (0..4).flat_map(|_| ac3_frame()).collect()),
+            "2.0"
+        );
+        let truehd = |atmos| {
+            let mut data = truehd_unit(true, atmos);
+            (0..3).for_each(|_| data.extend(truehd_unit(false, atmos)));
+            data
+        };
+        assert_eq!(layout("d.thd", truehd(false)), "7.1");
+        let atmos_truehd = if cfg!(target_os = "macos") {
+            "7.1"
+        } else {
+            "9.1.6"
+        };
+        assert_eq!(layout("e.thd", truehd(true)), atmos_truehd);
+    }
+
+    // E-AC3 + JOC：独立 + 依赖子流，统计帧、时长、声道与全景声
+    // E-AC3 + JOC: independent + dependent substreams; frames, duration, channels and Atmos are reported
+    #[test]
+    fn probes_eac3_with_joc_and_dependent_substream() {
+        let mut data = Vec::new();
+        for _ in 0..10 {
+            data.extend(eac3_frame(0, 0, 1536, Some(16)));
+            data.extend(eac3_frame(1, 0, 512, None));
+        }
+        let size = data.len() as u64;
+        let report = probe_ac3(&mut BufReader::new(Cursor::new(data)), size).unwrap();
+        assert_eq!(report.format, "E-AC3");
+        assert_eq!(report.sample_rate, 48000);
+        assert_eq!(report.frames, 10);
+        assert!((report.duration_secs - 0.32).abs() < 1e-9);
+        assert_eq!(report.channels, 8);
+        assert_eq!(report.channel_mode, "3/2");
+        assert!(report.lfe);
+        assert_eq!(report.substreams, 2);
+        assert!(report.atmos);
+        assert_eq!(report.joc_complexity, Some(16));
+        assert_eq!(report.dialnorm_db, Some(-27));
+        assert_eq!(report.bitrate_kbps, 512);
+    }
+
+    // 没有 addbsi 的 E-AC3 不报告全景声 / E-AC3 without addbsi is not reported as Atmos
+    #[test]
+    fn eac3_without_addbsi_is_not_atmos() {
+        let frame = parse_ac3_frame(&eac3_frame(0, 0, 1536, None)).unwrap();
+        assert_eq!(frame.bsid, 16);
+        assert_eq!(frame.joc_complexity, None);
+        assert_eq!(frame.samples, 1536);
+    }
+
+    // AC-3：bsid 8，帧长由 frmsizecod 得出 / AC-3: bsid 8, frame size from frmsizecod
+    #[test]
+    fn probes_ac3() {
+        let data: Vec<u8> = (0..4).flat_map(|_| ac3_frame()).collect();
+        let size = data.len() as u64;
+        let report = probe_ac3(&mut BufReader::new(Cursor::new(data)), size).unwrap();
+        assert_eq!(report.format, "AC-3");
+        assert_eq!(report.frames, 4);
+        assert_eq!(report.channels, 2);
+        assert_eq!(report.bitrate_kbps, 192);
+        assert_eq!(report.dialnorm_db, Some(-24));
+        assert!(!report.atmos);
+    }
+
+    // TrueHD：主同步给出采样率、声道与子流；按访问单元统计时长
+    // TrueHD: the major sync gives rate, channels and substreams; duration comes from access units
+    #[test]
+    fn probes_truehd_atmos() {
+        let mut data = truehd_unit(true, true);
+        for _ in 0..119 {
+            data.extend(truehd_unit(false, false));
+        }
+        let size = data.len() as u64;
+        let report = probe_truehd(&mut BufReader::new(Cursor::new(data)), size).unwrap();
+        assert_eq!(report.format, "TrueHD");
+        assert_eq!(report.sample_rate, 48000);
+        assert_eq!(report.frames, 120);
+        assert!((report.duration_secs - 0.1).abs() < 1e-9);
+        assert_eq!(report.channels, 8);
+        assert!(report.lfe);
+        assert_eq!(report.substreams, 4);
+        assert!(report.atmos);
+        assert_eq!(report.peak_bitrate_kbps, Some(12288));
+    }
+
+    // TrueHD 非全景声 / TrueHD without Atmos
+    #[test]
+    fn truehd_without_16ch_presentation_is_not_atmos() {
+        let unit = truehd_unit(true, false);
+        let sync = parse_major_sync(&unit[4..]).unwrap();
+        assert_eq!(sync.substreams, 3);
+        assert_eq!(sync.substream_info & 0x80, 0);
+    }
+
+    // 文本与 JSON 输出包含关键字段 / Text and JSON output carry the key fields
+    #[test]
+    fn report_renders_text_and_json() {
+        let data: Vec<u8> = (0..2)
+            .flat_map(|_| eac3_frame(0, 0, 1536, Some(8)))
+            .collect();
+        let size = data.len() as u64;
+        let report = probe_ac3(&mut BufReader::new(Cursor::new(data)), size).unwrap();
+        let text = report.to_text();
+        assert!(text.contains("E-AC3"));
+        assert!(text.contains("JOC complexity 8"));
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["atmos"], true);
+        assert_eq!(json["frames"], 2);
+    }
+}
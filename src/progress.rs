@@ -0,0 +1,214 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 进度条的分辨率（每个文件）/ Bar resolution per file
+const STEPS_PER_FILE: u64 = 1000;
+/// 非终端时的输出间隔 / Line interval when not on a terminal
+const LINE_INTERVAL: Duration = Duration::from_secs(10);
+
+static PROGRESS: Mutex<Option<Tracker>> = Mutex::new(None);
+
+/// 汇总全部批处理文件与声道的进度 / Aggregates progress over every batch file and channel
+struct Tracker {
+    files: usize,
+    done_files: usize,
+    /// 当前文件每个进度来源（声道或整条管道）的完成比例 / Completed fraction of each progress source (channel or whole pipeline) of the current file
+    slots: Vec<f32>,
+    bar: Option<ProgressBar>,
+    started: Instant,
+    last_line: Option<Instant>,
+}
+
+impl Tracker {
+    fn fraction(&self) -> f64 {
+        overall_fraction(self.done_files, self.files, &self.slots)
+    }
+
+    fn refresh(&mut self) {
+        let fraction = self.fraction();
+        if let Some(bar) = &self.bar {
+            bar.set_position((fraction * (self.files as u64 * STEPS_PER_FILE) as f64) as u64);
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_line
+            .is_some_and(|t| now.duration_since(t) < LINE_INTERVAL)
+        {
+            return;
+        }
+        self.last_line = Some(now);
+        eprintln!(
+            "{}",
+            progress_line(fraction, now.duration_since(self.started))
+        );
+    }
+}
+
+/// 整体完成比例：已完成文件加上当前文件各来源的平均值 / Overall fraction: finished files plus the mean of the current file's sources
+fn overall_fraction(done_files: usize, files: usize, slots: &[f32]) -> f64 {
+    if files == 0 {
+        return 0.0;
+    }
+    let current = if slots.is_empty() {
+        0.0
+    } else {
+        slots.iter().map(|&f| f64::from(f)).sum::<f64>() / slots.len() as f64
+    };
+    ((done_files as f64 + current) / files as f64).clamp(0.0, 1.0)
+}
+
+/// 非终端时输出的进度行 / Progress line printed when not on a terminal
+fn progress_line(fraction: f64, elapsed: Duration) -> String {
+    let percent = fraction * 100.0;
+    if fraction <= 0.0 {
+        return format!("进度/Progress: {percent:.0}%");
+    }
+    let eta = elapsed.as_secs_f64() * (1.0 - fraction) / fraction;
+    format!(
+        "进度/Progress: {percent:.0}% (剩余/ETA {}m{:02}s)",
+        eta as u64 / 60,
+        eta as u64 % 60
+    )
+}
+
+fn with_tracker(f: impl FnOnce(&mut Tracker)) {
+    if let Ok(mut guard) = PROGRESS.lock() {
+        if let Some(tracker) = guard.as_mut() {
+            f(tracker);
+        }
+    }
+}
+
+/// 开始批处理进度：终端上显示进度条，否则定期输出百分比行
+/// Start batch progress: a bar on a terminal, periodic percentage lines otherwise
+pub fn start(files: usize) {
+    let bar = std::io::stderr().is_terminal().then(|| {
+        let bar = ProgressBar::with_draw_target(
+            Some(files as u64 * STEPS_PER_FILE),
+            ProgressDrawTarget::stderr(),
+        );
+        bar.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] [{wide_bar}] {percent:>3}% 剩余/ETA {eta}",
+            )
+            .unwrap_or_else(|_| ProgressStyle::default_bar()),
+        );
+        bar
+    });
+    if let Ok(mut guard) = PROGRESS.lock() {
+        *guard = Some(Tracker {
+            files,
+            done_files: 0,
+            slots: Vec::new(),
+            bar,
+            started: Instant::now(),
+            last_line: None,
+        });
+    }
+}
+
+/// 是否有进度显示在接收更新 / Whether a progress display is taking updates
+pub fn is_active() -> bool {
+    PROGRESS.lock().is_ok_and(|guard| guard.is_some())
+}
+
+/// 当前文件有 `count` 个进度来源 / The current file has `count` progress sources
+pub fn set_slots(count: usize) {
+    with_tracker(|t| t.slots = vec![0.0; count]);
+}
+
+/// 更新某个来源的完成比例 / Update the completed fraction of one source
+pub fn update(slot: usize, fraction: f32) {
+    with_tracker(|t| {
+        if let Some(s) = t.slots.get_mut(slot) {
+            *s = fraction.clamp(0.0, 1.0);
+            t.refresh();
+        }
+    });
+}
+
+/// 当前文件已结束（完成或跳过）/ The current file has ended (finished or skipped)
+pub fn file_done() {
+    with_tracker(|t| {
+        t.done_files = (t.done_files + 1).min(t.files);
+        t.slots.clear();
+        // 每个文件结束时总输出一行 / Always print a line when a file ends
+        t.last_line = None;
+        t.refresh();
+    });
+}
+
+/// 结束并移除进度显示 / Finish and remove the progress display
+pub fn finish() {
+    if let Ok(mut guard) = PROGRESS.lock() {
+        if let Some(bar) = guard.take().and_then(|t| t.bar) {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// 在进度条暂时隐藏时执行 `f`，避免文本输出撕裂进度条；没有进度条时直接执行
+/// Run `f` with the bar hidden so text output doesn't tear it; runs `f` directly when there is no bar
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    // 先取出进度条再执行，`f` 期间不持有锁 / Take the bar out first so the lock isn't held while `f` runs
+    let bar = PROGRESS
+        .lock()
+        .ok()
+        .and_then(|guard| guard.as_ref().and_then(|t| t.bar.clone()));
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
+
+/// 解析 progressreport 的输出行，返回完成比例
+/// Parse a progressreport line, returning the completed fraction
+///
+/// 例/e.g. `progress (00:00:05): 12 / 100 percent (12.0 %)`
+pub(crate) fn parse_progressreport(line: &str) -> Option<f32> {
+    let line = line.trim();
+    if !line.starts_with("progress (") {
+        return None;
+    }
+    let open = line.rfind('(')?;
+    let percent = line[open + 1..].strip_suffix("%)")?.trim();
+    percent.parse::<f32>().ok().map(|p| p / 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 解析 progressreport 行，忽略其它输出 / Parse progressreport lines and ignore other output
+    #[test]
+    fn parses_progressreport_lines() {
+        assert_eq!(
+            parse_progressreport("progress (00:00:05): 12 / 100 percent (12.0 %)"),
+            Some(0.12)
+        );
+        assert_eq!(
+            parse_progressreport("progress (00:01:00): 1048576 / 4194304 bytes ( 25.0 %)"),
+            Some(0.25)
+        );
+        assert_eq!(
+            parse_progressreport("Setting pipeline to PLAYING ..."),
+            None
+        );
+        assert_eq!(parse_progressreport("progress (00:00:01): 5 seconds"), None);
+    }
+
+    // 汇总所有文件与声道，并估算剩余时间 / Aggregate over files and channels, and estimate the remaining time
+    #[test]
+    fn aggregates_files_and_channels() {
+        assert_eq!(overall_fraction(0, 2, &[]), 0.0);
+        assert!((overall_fraction(1, 2, &[1.0, 0.0]) - 0.75).abs() < 1e-9);
+        assert_eq!(overall_fraction(2, 2, &[]), 1.0);
+        assert_eq!(
+            progress_line(0.25, Duration::from_secs(60)),
+            "进度/Progress: 25% (剩余/ETA 3m00s)"
+        );
+    }
+}
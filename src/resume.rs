@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// 输出目录中的状态文件名 / State file name in the output directory
+pub const STATE_FILE: &str = ".mcat-state.json";
+const STATE_VERSION: u32 = 1;
+
+/// 单个输入已完成的处理阶段 / A completed processing stage of one input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Decoded,
+    Merged,
+    Flac,
+    Wavpack,
+    Stems,
+    Done,
+}
+
+/// 一个输入的记录；大小、修改时间或设置变化时作废
+/// Record for one input; invalidated when its size, mtime or the settings change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct Entry {
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    /// 影响输出的设置（布局、管道模式、命名、音轨、格式、编码参数）/ Settings that shape the outputs (layout, pipeline, naming, track, format, encode parameters)
+    settings: String,
+    stages: Vec<Stage>,
+    /// 已解码的声道文件 / Decoded channel files
+    decoded: Vec<PathBuf>,
+}
+
+impl Entry {
+    /// 是否描述同一文件与设置 / Whether it describes the same file and settings
+    fn same_input(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.mtime_secs == other.mtime_secs
+            && self.mtime_nanos == other.mtime_nanos
+            && self.settings == other.settings
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StateData {
+    version: u32,
+    inputs: BTreeMap<String, Entry>,
+}
+
+/// 续跑状态：每个阶段完成后立即写回状态文件
+/// Resume state: written back to the state file as soon as each stage completes
+pub struct ResumeState {
+    path: PathBuf,
+    data: Mutex<StateData>,
+}
+
+/// 输入的状态键：规范化后的绝对路径 / State key of an input: its canonical absolute path
+fn key_of(input: &Path) -> String {
+    input
+        .canonicalize()
+        .unwrap_or_else(|_| input.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// 由输入的大小与修改时间生成新记录 / Fresh record from the input's size and mtime
+fn fresh_entry(input: &Path, settings: &str) -> Entry {
+    let meta = std::fs::metadata(input).ok();
+    let mtime = meta
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    Entry {
+        size: meta.map_or(0, |m| m.len()),
+        mtime_secs: mtime.as_secs(),
+        mtime_nanos: mtime.subsec_nanos(),
+        settings: settings.to_string(),
+        stages: Vec::new(),
+        decoded: Vec::new(),
+    }
+}
+
+impl ResumeState {
+    /// 读取 `dir` 下的状态文件；不存在或无法解析时从空状态开始
+    /// Load the state file under `dir`; start empty when it is missing or unreadable
+    pub fn load(dir: &Path) -> Self {
+        let path = dir.join(STATE_FILE);
+        let data = match std::fs::read_to_string(&path) {
+            Ok(text) => match serde_json::from_str::<StateData>(&text) {
+                Ok(data) if data.version == STATE_VERSION => data,
+                Ok(_) | Err(_) => {
+                    eprintln!(
+                        "[警告] 无法识别的状态文件，重新开始/Unrecognised state file, starting over: {}",
+                        path.display()
+                    );
+                    StateData::default()
+                }
+            },
+            Err(_) => StateData::default(),
+        };
+        Self {
+            path,
+            data: Mutex::new(data),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 开始处理一个输入：记录与当前文件或设置不符时作废
+    /// Start an input: a record that no longer matches the file or the settings is dropped
+    pub fn begin(&self, input: &Path, settings: &str) {
+        let fresh = fresh_entry(input, settings);
+        if let Ok(mut data) = self.data.lock() {
+            let entry = data.inputs.entry(key_of(input)).or_default();
+            if !entry.same_input(&fresh) {
+                *entry = fresh;
+            }
+        }
+    }
+
+    /// 当前文件与设置下该阶段是否已完成 / Whether the stage is done for the current file and settings
+    pub fn has(&self, input: &Path, settings: &str, stage: Stage) -> bool {
+        let fresh = fresh_entry(input, settings);
+        self.data.lock().is_ok_and(|data| {
+            data.inputs
+                .get(&key_of(input))
+                .is_some_and(|e| e.same_input(&fresh) && e.stages.contains(&stage))
+        })
+    }
+
+    /// 记录的声道文件 / The recorded channel files
+    pub fn decoded(&self, input: &Path) -> Vec<PathBuf> {
+        self.data
+            .lock()
+            .ok()
+            .and_then(|data| data.inputs.get(&key_of(input)).map(|e| e.decoded.clone()))
+            .unwrap_or_default()
+    }
+
+    /// 记录已完成的阶段并写回状态文件 / Record a completed stage and write the state file back
+    pub fn record(&self, input: &Path, stage: Stage, decoded: &[PathBuf]) -> std::io::Result<()> {
+        let json = {
+            let Ok(mut data) = self.data.lock() else {
+                return Ok(());
+            };
+            data.version = STATE_VERSION;
+            let entry = data.inputs.entry(key_of(input)).or_default();
+            if !entry.stages.contains(&stage) {
+                entry.stages.push(stage);
+            }
+            if stage == Stage::Decoded {
+                entry.decoded = decoded.to_vec();
+            }
+            serde_json::to_string_pretty(&*data).map_err(std::io::Error::other)?
+        };
+        // 先写临时文件再改名，中断时不会留下半个状态文件 / Write a temp file then rename, so an interrupt never leaves half a state file
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, json)?;
+        std::fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 阶段写入磁盘后可重新读取；输入变化或设置变化时作废
+    // Stages survive a reload; they are dropped when the input or the settings change
+    #[test]
+    fn stages_persist_and_invalidate() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("movie.ec3");
+        std::fs::write(&input, b"first").unwrap();
+        let files = [dir.path().join("movie.01_L.wav")];
+
+        let state = ResumeState::load(dir.path());
+        state.begin(&input, "5.1");
+        state.record(&input, Stage::Decoded, &files).unwrap();
+        state.record(&input, Stage::Merged, &[]).unwrap();
+
+        let state = ResumeState::load(dir.path());
+        assert!(state.has(&input, "5.1", Stage::Merged));
+        assert!(!state.has(&input, "5.1", Stage::Flac));
+        assert!(!state.has(&input, "7.1", Stage::Merged));
+        assert_eq!(state.decoded(&input), files);
+
+        std::fs::write(&input, b"changed contents").unwrap();
+        assert!(!state.has(&input, "5.1", Stage::Decoded));
+        state.begin(&input, "5.1");
+        assert!(state.decoded(&input).is_empty());
+    }
+
+    // 损坏的状态文件不会阻止运行 / A corrupt state file doesn't stop the run
+    #[test]
+    fn corrupt_state_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(STATE_FILE), b"{ not json").unwrap();
+        let input = dir.path().join("movie.ec3");
+        std::fs::write(&input, b"data").unwrap();
+        let state = ResumeState::load(dir.path());
+        assert!(!state.has(&input, "", Stage::Done));
+        state.begin(&input, "");
+        state.record(&input, Stage::Done, &[]).unwrap();
+        assert!(ResumeState::load(dir.path()).has(&input, "", Stage::Done));
+    }
+}
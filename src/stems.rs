@@ -0,0 +1,292 @@
+use crate::channels::{channel_mask, ChannelConfig};
+use crate::error::{DecodeError, Result};
+use crate::interrupt;
+use crate::wav::{WavReader, WavWriter};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 分轨分组：床（≤8 声道）、宽声道、顶部声道 / Stem groups: bed (≤8 channels), wides, heights
+const STEM_GROUPS: &[(&str, &[&str])] = &[
+    ("bed", &["L", "R", "C", "LFE", "Ls", "Rs", "Lrs", "Rrs"]),
+    ("wides", &["Lw", "Rw"]),
+    ("heights", &["Ltf", "Rtf", "Ltm", "Rtm", "Ltr", "Rtr"]),
+];
+
+/// 每次从合并文件读取的帧数 / Frames read from the merged file per step
+const SPLIT_CHUNK_FRAMES: usize = 8192;
+
+/// 分轨清单：描述如何将各分轨重新组合为原布局
+/// Stem manifest: describes how to recombine the stems into the original layout
+#[derive(Debug, Serialize)]
+pub struct StemManifest {
+    pub layout: String,
+    pub channels: Vec<String>,
+    pub sample_rate: u32,
+    pub frames: u64,
+    pub stems: Vec<StemEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StemEntry {
+    pub name: String,
+    pub file: String,
+    pub channels: Vec<String>,
+    /// 各声道在原布局中的位置（0 起始）/ Position of each channel in the original layout (0-based)
+    pub source_indices: Vec<usize>,
+}
+
+/// 按声道名将布局拆分为分组，返回（组名，原布局中的索引）；空组省略
+/// Split a layout into groups by channel name, returning (group name, indices in the layout); empty groups are omitted
+fn stem_groups(names: &[String]) -> Vec<(&'static str, Vec<usize>)> {
+    STEM_GROUPS
+        .iter()
+        .map(|(group, members)| {
+            let indices = names
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| members.contains(&n.as_str()))
+                .map(|(idx, _)| idx)
+                .collect::<Vec<_>>();
+            (*group, indices)
+        })
+        .filter(|(_, indices)| !indices.is_empty())
+        .collect()
+}
+
+/// 将合并文件拆分为床/宽/顶部分轨并各自编码为 FLAC，写出 `<stem>.stems.json` 清单
+/// Split the merged file into bed/wides/heights stems, encode each as FLAC and write a `<stem>.stems.json` manifest
+///
+/// 返回清单路径 / Returns the manifest path
+pub fn export_stems(merged_file: &Path, config: &ChannelConfig, level: u8) -> Result<PathBuf> {
+    let stem_err = |e: std::io::Error| {
+        DecodeError::FlacConversionFailed(format!("分轨导出失败/Stem export failed: {e}"))
+    };
+    if config.names.is_empty() {
+        return Err(DecodeError::FlacConversionFailed(
+            "分轨导出需要已知声道布局/Stem export needs a known channel layout".to_string(),
+        ));
+    }
+    let groups = stem_groups(&config.names);
+    let grouped: usize = groups.iter().map(|(_, indices)| indices.len()).sum();
+    if grouped != config.names.len() {
+        return Err(DecodeError::FlacConversionFailed(format!(
+            "布局含无法分组的声道/Layout has channels that fit no stem group: {}",
+            config.name
+        )));
+    }
+
+    let mut reader = WavReader::open(merged_file).map_err(stem_err)?;
+    if usize::from(reader.channels()) != config.names.len() {
+        return Err(DecodeError::FlacConversionFailed(format!(
+            "声道数与布局不符/Channel count does not match layout: {} vs {}",
+            reader.channels(),
+            config.names.len()
+        )));
+    }
+    let sample_rate = reader.sample_rate();
+
+    let base = merged_file.with_extension("");
+    let base_name = base
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or("output")
+        .to_string();
+    let stem_path =
+        |group: &str, ext: &str| base.with_file_name(format!("{base_name}.{group}.{ext}"));
+    let wavs: Vec<PathBuf> = groups.iter().map(|(g, _)| stem_path(g, "wav")).collect();
+    let flacs: Vec<PathBuf> = groups.iter().map(|(g, _)| stem_path(g, "flac")).collect();
+    let manifest_path = stem_path("stems", "json");
+    // 中断时删除全部分轨输出 / Every stem output is removed on interrupt
+    for path in wavs.iter().chain(&flacs).chain([&manifest_path]) {
+        interrupt::track_output(path.clone());
+    }
+
+    let result = split_and_encode(&mut reader, config, &groups, &wavs, &flacs, level);
+    // 临时 WAV 无论成败都不保留 / Temporary WAVs are never kept, whether or not the export succeeded
+    for wav in &wavs {
+        let _ = std::fs::remove_file(wav);
+    }
+    let (frames, stems) = result?;
+
+    let manifest = StemManifest {
+        layout: config.name.clone(),
+        channels: config.names.clone(),
+        sample_rate,
+        frames,
+        stems,
+    };
+    let json = serde_json::to_string_pretty(&manifest).map_err(|e| {
+        DecodeError::FlacConversionFailed(format!("无法写出清单/Cannot write manifest: {e}"))
+    })?;
+    std::fs::write(&manifest_path, json).map_err(stem_err)?;
+    Ok(manifest_path)
+}
+
+/// 一次读取合并文件写出各组的临时 WAV，再逐个编码为 FLAC；返回帧数与清单条目
+/// Read the merged file once writing every group's temporary WAV, then encode each as FLAC; returns the frame count and manifest entries
+fn split_and_encode(
+    reader: &mut WavReader,
+    config: &ChannelConfig,
+    groups: &[(&str, Vec<usize>)],
+    wavs: &[PathBuf],
+    flacs: &[PathBuf],
+    level: u8,
+) -> Result<(u64, Vec<StemEntry>)> {
+    let stem_err = |e: std::io::Error| {
+        DecodeError::FlacConversionFailed(format!("分轨导出失败/Stem export failed: {e}"))
+    };
+    let mut writers = groups
+        .iter()
+        .zip(wavs)
+        .map(|((_, indices), wav)| {
+            let names: Vec<String> = indices.iter().map(|&i| config.names[i].clone()).collect();
+            WavWriter::create(
+                wav,
+                indices.len() as u16,
+                reader.sample_rate(),
+                channel_mask(&names),
+            )
+        })
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(stem_err)?;
+
+    let channels = config.names.len();
+    let mut frames = 0u64;
+    let mut buf = Vec::new();
+    loop {
+        let read = reader
+            .read_frames(&mut buf, SPLIT_CHUNK_FRAMES)
+            .map_err(stem_err)?;
+        if read == 0 {
+            break;
+        }
+        frames += read as u64;
+        for frame in buf.chunks_exact(channels) {
+            for ((_, indices), writer) in groups.iter().zip(writers.iter_mut()) {
+                for &i in indices {
+                    writer.write_sample(frame[i]).map_err(stem_err)?;
+                }
+            }
+        }
+    }
+    for writer in writers {
+        writer.finalize().map_err(stem_err)?;
+    }
+
+    let mut stems = Vec::with_capacity(groups.len());
+    for (((group, indices), wav), flac) in groups.iter().zip(wavs).zip(flacs) {
+        let stem_config = ChannelConfig {
+            name: format!("{} {group}", config.name),
+            id: config.id,
+            names: indices.iter().map(|&i| config.names[i].clone()).collect(),
+        };
+        crate::flac_converter::convert_batch(wav, flac, Some(&stem_config), level)?;
+        let _ = std::fs::remove_file(wav);
+
+        stems.push(StemEntry {
+            name: (*group).to_string(),
+            file: flac
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string(),
+            channels: stem_config.names,
+            source_indices: indices.clone(),
+        });
+    }
+    Ok((frames, stems))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::get_config;
+
+    // 9.1.6：7.1 床 + 宽声道对 + 6 个顶部声道 / 9.1.6: 7.1 bed + wides pair + 6 heights
+    #[test]
+    fn groups_for_916() {
+        let config = get_config("9.1.6").unwrap();
+        let groups = stem_groups(&config.names);
+        let sizes: Vec<(&str, usize)> = groups.iter().map(|(g, i)| (*g, i.len())).collect();
+        assert_eq!(sizes, [("bed", 8), ("wides", 2), ("heights", 6)]);
+    }
+
+    // 5.1.4 没有宽声道组 / 5.1.4 has no wides group
+    #[test]
+    fn groups_for_514_skip_empty() {
+        let config = get_config("5.1.4").unwrap();
+        let groups = stem_groups(&config.names);
+        let names: Vec<&str> = groups.iter().map(|(g, _)| *g).collect();
+        assert_eq!(names, ["bed", "heights"]);
+    }
+
+    // 编码失败时不留下临时 WAV 与清单 / A failed encode leaves no temporary WAVs or manifest
+    #[test]
+    fn failed_export_removes_temp_wavs() {
+        let dir = tempfile::tempdir().unwrap();
+        let merged = dir.path().join("movie.wav");
+        let config = get_config("5.1.4").unwrap();
+        let mut w = WavWriter::create(&merged, 10, 48000, 0).unwrap();
+        for _ in 0..10 * 100 {
+            w.write_sample(0.0).unwrap();
+        }
+        w.finalize().unwrap();
+        // 目标路径被目录占用，编码必然失败 / A directory sits on the target path, so encoding fails
+        std::fs::create_dir(dir.path().join("movie.bed.flac")).unwrap();
+
+        assert!(export_stems(&merged, &config, 5).is_err());
+        assert!(!dir.path().join("movie.bed.wav").exists());
+        assert!(!dir.path().join("movie.heights.wav").exists());
+        assert!(!dir.path().join("movie.stems.json").exists());
+    }
+
+    // 导出后各分轨可按清单重新组合为原始样本 / Stems recombine into the original samples using the manifest
+    #[cfg(feature = "native-flac")]
+    #[test]
+    fn export_writes_flac_stems_and_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let merged = dir.path().join("movie.wav");
+        let config = get_config("7.1.4").unwrap();
+        let channels = config.names.len();
+        let frames = 300;
+
+        let mut w = WavWriter::create(&merged, channels as u16, 48000, 0).unwrap();
+        let value = |f: usize, c: usize| ((f * channels + c) % 1000) as f32 / 2000.0;
+        for f in 0..frames {
+            for c in 0..channels {
+                w.write_sample(value(f, c)).unwrap();
+            }
+        }
+        w.finalize().unwrap();
+
+        let manifest_path = export_stems(&merged, &config, 5).unwrap();
+        assert_eq!(manifest_path, dir.path().join("movie.stems.json"));
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&manifest_path).unwrap()).unwrap();
+        assert_eq!(manifest["frames"], frames);
+        let stems = manifest["stems"].as_array().unwrap();
+        assert_eq!(stems.len(), 2);
+        assert!(!dir.path().join("movie.bed.wav").exists());
+
+        for stem in stems {
+            let file = dir.path().join(stem["file"].as_str().unwrap());
+            let indices: Vec<usize> = stem["source_indices"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|v| v.as_u64().unwrap() as usize)
+                .collect();
+            let mut reader = claxon::FlacReader::open(&file).unwrap();
+            let layout = reader.get_tag("CHANNEL_LAYOUT").next().unwrap().to_string();
+            let decoded: Vec<i32> = reader.samples().map(|s| s.unwrap()).collect();
+            assert_eq!(decoded.len(), frames * indices.len());
+            for (k, &i) in indices.iter().enumerate() {
+                assert!(layout.contains(&config.names[i]));
+                assert_eq!(
+                    decoded[5 * indices.len() + k],
+                    (value(5, i) * 8_388_607.0) as i32
+                );
+            }
+        }
+    }
+}
@@ -1,15 +1,145 @@
 use crate::error::{DecodeError, Result};
+use crate::events::say;
 use std::env;
 use std::path::{Path, PathBuf};
 
+/// 子进程库搜索路径变量 / Library search path variable for child processes
+pub const LIBRARY_PATH_VAR: &str = if cfg!(target_os = "macos") {
+    "DYLD_LIBRARY_PATH"
+} else {
+    "LD_LIBRARY_PATH"
+};
+
+/// 一个候选的工具位置 / One candidate tool location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCandidate {
+    /// 来源说明 / Where the candidate came from
+    pub origin: String,
+    pub gst_launch: PathBuf,
+    pub gst_plugins: PathBuf,
+}
+
+impl ToolCandidate {
+    /// dolby-tools 目录布局：gstreamer/bin/gst-launch-1.0 与 gst-plugins
+    /// dolby-tools layout: gstreamer/bin/gst-launch-1.0 and gst-plugins
+    fn from_base(origin: String, base: &Path) -> Self {
+        Self {
+            origin,
+            gst_launch: base.join("gstreamer/bin/gst-launch-1.0"),
+            gst_plugins: base.join("gst-plugins"),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.gst_launch.exists() && self.gst_plugins.exists()
+    }
+}
+
+/// XDG 数据目录下的 dolby-tools（XDG_DATA_HOME 优先，其后为 XDG_DATA_DIRS）
+/// dolby-tools under the XDG data dirs (XDG_DATA_HOME first, then XDG_DATA_DIRS)
+fn xdg_tool_dirs(
+    data_home: Option<String>,
+    home: Option<String>,
+    data_dirs: Option<String>,
+) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    match (data_home.filter(|s| !s.is_empty()), home) {
+        (Some(data_home), _) => dirs.push(PathBuf::from(data_home)),
+        (None, Some(home)) => dirs.push(Path::new(&home).join(".local/share")),
+        (None, None) => {}
+    }
+    let data_dirs = data_dirs
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string());
+    dirs.extend(
+        data_dirs
+            .split(':')
+            .filter(|s| !s.is_empty())
+            .map(PathBuf::from),
+    );
+    dirs.into_iter().map(|d| d.join("dolby-tools")).collect()
+}
+
+/// 按查找顺序列出全部候选位置（不含 --dolby-tools）
+/// List every candidate location in lookup order (excluding --dolby-tools)
+pub fn tool_candidates() -> Vec<ToolCandidate> {
+    let mut candidates = Vec::new();
+
+    // 1) Explicit env overrides / 显式环境变量覆盖
+    if let (Ok(gst_launch), Ok(gst_plugins)) =
+        (env::var("MCAT_GST_LAUNCH"), env::var("MCAT_GST_PLUGINS"))
+    {
+        candidates.push(ToolCandidate {
+            origin: "MCAT_GST_LAUNCH + MCAT_GST_PLUGINS".to_string(),
+            gst_launch: PathBuf::from(gst_launch),
+            gst_plugins: PathBuf::from(gst_plugins),
+        });
+    }
+
+    // 2) Base directory via env / 通过环境变量指定基目录
+    if let Ok(base) = env::var("MCAT_DOLBY_TOOLS") {
+        candidates.push(ToolCandidate::from_base(
+            "MCAT_DOLBY_TOOLS".to_string(),
+            Path::new(&base),
+        ));
+    }
+
+    // 3) Try alongside executable: <exe_dir>/dolby-tools / 可执行文件同目录的 dolby-tools
+    if let Some(exe_dir) = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        candidates.push(ToolCandidate::from_base(
+            "<exe_dir>/dolby-tools".to_string(),
+            &exe_dir.join("dolby-tools"),
+        ));
+    }
+
+    // 4) Linux：XDG 数据目录与 /opt / Linux: XDG data dirs and /opt
+    if cfg!(target_os = "linux") {
+        let xdg = xdg_tool_dirs(
+            env::var("XDG_DATA_HOME").ok(),
+            env::var("HOME").ok(),
+            env::var("XDG_DATA_DIRS").ok(),
+        );
+        for dir in xdg {
+            candidates.push(ToolCandidate::from_base("XDG data dir".to_string(), &dir));
+        }
+        for dir in ["/opt/dolby-tools", "/opt/dolby/dolby-tools"] {
+            candidates.push(ToolCandidate::from_base("/opt".to_string(), Path::new(dir)));
+        }
+    }
+
+    // 5) macOS：系统安装的 Dolby Reference Player / macOS: system Dolby Reference Player
+    if cfg!(target_os = "macos") {
+        let drp_base = PathBuf::from("/Applications/Dolby/Dolby Reference Player.app/Contents");
+        candidates.push(ToolCandidate {
+            origin: "Dolby Reference Player".to_string(),
+            gst_launch: drp_base
+                .join("Frameworks/GStreamer.framework/Versions/1_22/Resources/bin/gst-launch-1.0"),
+            gst_plugins: drp_base.join("PlugIns/gst-plugins"),
+        });
+    }
+
+    candidates
+}
+
+/// `locate_tools` 会考虑的全部位置：给定 --dolby-tools 时仅此一处
+/// Every location `locate_tools` considers: only --dolby-tools when it is given
+pub fn considered_candidates(cli_base: Option<&Path>) -> Vec<ToolCandidate> {
+    match cli_base {
+        Some(base) => vec![ToolCandidate::from_base("--dolby-tools".to_string(), base)],
+        None => tool_candidates(),
+    }
+}
+
 pub fn locate_tools(cli_base: Option<&Path>) -> Result<(PathBuf, PathBuf)> {
     // 0) CLI override / 命令行参数优先
     if let Some(base) = cli_base {
-        let gst = base.join("gstreamer/bin/gst-launch-1.0");
-        let plugins = base.join("gst-plugins");
-        if gst.exists() && plugins.exists() {
-            println!("使用命令行指定的 dolby-tools 目录/Using dolby-tools from --dolby-tools");
-            return Ok((gst, plugins));
+        let candidate = ToolCandidate::from_base("--dolby-tools".to_string(), base);
+        if candidate.is_complete() {
+            say!("使用命令行指定的 dolby-tools 目录/Using dolby-tools from --dolby-tools");
+            return Ok((candidate.gst_launch, candidate.gst_plugins));
         }
         return Err(DecodeError::ToolsNotFound(format!(
             "--dolby-tools 路径无效，应包含: gstreamer/bin/gst-launch-1.0 与 gst-plugins/Invalid --dolby-tools path; expected layout with gstreamer/bin/gst-launch-1.0 and gst-plugins: {}",
@@ -17,68 +147,115 @@ pub fn locate_tools(cli_base: Option<&Path>) -> Result<(PathBuf, PathBuf)> {
         )));
     }
 
-    // 1) Explicit env overrides / 显式环境变量覆盖
-    if let (Ok(gst_launch_s), Ok(gst_plugins_s)) =
-        (env::var("MCAT_GST_LAUNCH"), env::var("MCAT_GST_PLUGINS"))
+    if let Some(found) = tool_candidates()
+        .into_iter()
+        .find(ToolCandidate::is_complete)
     {
-        let gst_launch = PathBuf::from(gst_launch_s);
-        let gst_plugins = PathBuf::from(gst_plugins_s);
-        if gst_launch.exists() && gst_plugins.exists() {
-            println!(
-                "使用环境变量中的 GStreamer 路径/Using GStreamer paths from environment variables"
-            );
-            return Ok((gst_launch, gst_plugins));
-        }
+        say!(
+            "使用 GStreamer 工具/Using GStreamer tools from {}: {}",
+            found.origin,
+            found.gst_launch.display()
+        );
+        return Ok((found.gst_launch, found.gst_plugins));
     }
 
-    // 2) Base directory via env / 通过环境变量指定基目录
-    if let Ok(base) = env::var("MCAT_DOLBY_TOOLS") {
-        let base = PathBuf::from(base);
-        let env_gst = base.join("gstreamer/bin/gst-launch-1.0");
-        let env_plugins = base.join("gst-plugins");
-        if env_gst.exists() && env_plugins.exists() {
-            println!(
-                "使用环境变量指定的 dolby-tools 目录/Using dolby-tools from environment variable"
-            );
-            return Ok((env_gst, env_plugins));
+    Err(DecodeError::ToolsNotFound(
+        "未找到 Dolby 工具；请设置 MCAT_DOLBY_TOOLS、将 dolby-tools 放在可执行文件旁或 XDG 数据目录/opt 下，或安装 Dolby Reference Player（macOS）/Dolby tools not found; set MCAT_DOLBY_TOOLS, put dolby-tools next to the executable, in an XDG data dir or /opt, or install Dolby Reference Player (macOS)"
+            .to_string(),
+    ))
+}
+
+/// 基于已定位的工具目录得到子进程的库搜索路径（仅返回存在的目录）
+/// Library search dirs for child processes, derived from the located tool directories (existing ones only)
+pub fn library_dirs(gst_launch: &Path, gst_plugins: &Path) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    // dolby-tools/gst-plugins-libs：Dolby 插件依赖的库 / libraries the Dolby plugins depend on
+    if let Some(base) = gst_plugins.parent() {
+        dirs.push(base.join("gst-plugins-libs"));
+    }
+    // Linux：GStreamer 自带的 lib 目录（bin 的同级）/ Linux: GStreamer's own lib dir (sibling of bin)
+    if !cfg!(target_os = "macos") {
+        if let Some(root) = gst_launch.parent().and_then(Path::parent) {
+            dirs.push(root.join("lib"));
+            dirs.push(root.join("lib64"));
         }
     }
+    dirs.into_iter().filter(|d| d.is_dir()).collect()
+}
 
-    // 3) Try alongside executable: <exe_dir>/dolby-tools / 可执行文件同目录的 dolby-tools
-    if let Ok(exe) = std::env::current_exe() {
-        if let Some(exe_dir) = exe.parent() {
-            let exe_gst = exe_dir.join("dolby-tools/gstreamer/bin/gst-launch-1.0");
-            let exe_plugins = exe_dir.join("dolby-tools/gst-plugins");
-            if exe_gst.exists() && exe_plugins.exists() {
-                println!(
-                    "使用可执行文件同目录的 dolby-tools/Using dolby-tools next to the executable"
-                );
-                return Ok((exe_gst, exe_plugins));
-            }
-        }
+/// 将库目录前置到已有的搜索路径 / Prepend library dirs to an existing search path
+pub fn join_library_path(dirs: &[PathBuf], existing: Option<&str>) -> Option<String> {
+    if dirs.is_empty() {
+        return None;
     }
+    let mut parts: Vec<String> = dirs
+        .iter()
+        .map(|d| {
+            d.canonicalize()
+                .unwrap_or_else(|_| d.clone())
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    if let Some(existing) = existing.filter(|s| !s.is_empty()) {
+        parts.push(existing.to_string());
+    }
+    Some(parts.join(":"))
+}
 
-    // 4) Fallback to system Dolby Reference Player / 回退到系统安装的播放器
-    let drp_base = PathBuf::from("/Applications/Dolby/Dolby Reference Player.app/Contents");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !drp_base.exists() {
-        return Err(DecodeError::ToolsNotFound(
-            "未找到 Dolby 工具；请设置 MCAT_DOLBY_TOOLS 或安装 Dolby Reference Player/Dolby tools not found; set MCAT_DOLBY_TOOLS or install Dolby Reference Player"
-                .to_string(),
-        ));
+    // XDG：XDG_DATA_HOME 优先，未设置时用 ~/.local/share；XDG_DATA_DIRS 默认 /usr/local/share:/usr/share
+    // XDG: XDG_DATA_HOME first, ~/.local/share when unset; XDG_DATA_DIRS defaults to /usr/local/share:/usr/share
+    #[test]
+    fn xdg_dirs_follow_spec_defaults() {
+        let dirs = xdg_tool_dirs(None, Some("/home/u".to_string()), None);
+        assert_eq!(
+            dirs,
+            [
+                PathBuf::from("/home/u/.local/share/dolby-tools"),
+                PathBuf::from("/usr/local/share/dolby-tools"),
+                PathBuf::from("/usr/share/dolby-tools"),
+            ]
+        );
+        let dirs = xdg_tool_dirs(
+            Some("/data".to_string()),
+            Some("/home/u".to_string()),
+            Some("/a::/b".to_string()),
+        );
+        assert_eq!(
+            dirs,
+            [
+                PathBuf::from("/data/dolby-tools"),
+                PathBuf::from("/a/dolby-tools"),
+                PathBuf::from("/b/dolby-tools"),
+            ]
+        );
     }
 
-    let gst_launch =
-        drp_base.join("Frameworks/GStreamer.framework/Versions/1_22/Resources/bin/gst-launch-1.0");
-    let gst_plugins = drp_base.join("PlugIns/gst-plugins");
+    // 库路径来自工具目录而非工作目录 / Library dirs come from the tool directory, not the working directory
+    #[test]
+    fn library_dirs_follow_tool_location() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("dolby-tools");
+        std::fs::create_dir_all(base.join("gstreamer/bin")).unwrap();
+        std::fs::create_dir_all(base.join("gstreamer/lib")).unwrap();
+        std::fs::create_dir_all(base.join("gst-plugins")).unwrap();
+        std::fs::create_dir_all(base.join("gst-plugins-libs")).unwrap();
 
-    if !gst_launch.exists() || !gst_plugins.exists() {
-        return Err(DecodeError::ToolsNotFound(
-            "未找到 Dolby Reference Player 组件/Dolby Reference Player components not found"
-                .to_string(),
-        ));
-    }
+        let dirs = library_dirs(
+            &base.join("gstreamer/bin/gst-launch-1.0"),
+            &base.join("gst-plugins"),
+        );
+        assert_eq!(dirs[0], base.join("gst-plugins-libs"));
+        if !cfg!(target_os = "macos") {
+            assert_eq!(dirs[1..], [base.join("gstreamer/lib")]);
+        }
 
-    println!("使用系统安装的 GStreamer/Using system Dolby Reference Player");
-    Ok((gst_launch, gst_plugins))
+        let joined = join_library_path(&dirs[..1], Some("/usr/lib")).unwrap();
+        assert!(joined.ends_with("gst-plugins-libs:/usr/lib"));
+        assert_eq!(join_library_path(&[], Some("/usr/lib")), None);
+    }
 }
@@ -0,0 +1,611 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// RIFF 32 位大小字段的上限 / Upper bound of the 32-bit RIFF size fields
+const RIFF_LIMIT: u64 = u32::MAX as u64;
+
+/// 头部固定布局：RIFF(12) + JUNK/ds64(8+28) + fmt(8+16) + data 头(8)
+/// Fixed header layout: RIFF(12) + JUNK/ds64(8+28) + fmt(8+16) + data header(8)
+const DS64_OFFSET: u64 = 12;
+const DS64_SIZE: u32 = 28;
+const FMT_OFFSET: u64 = DS64_OFFSET + 8 + DS64_SIZE as u64;
+const FMT_SIZE: u32 = 40;
+const DATA_OFFSET: u64 = FMT_OFFSET + 8 + FMT_SIZE as u64;
+
+/// WAVE_FORMAT_EXTENSIBLE 中 IEEE float 的子格式 GUID / IEEE float sub-format GUID for WAVE_FORMAT_EXTENSIBLE
+const KSDATAFORMAT_SUBTYPE_IEEE_FLOAT: [u8; 16] = [
+    0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71,
+];
+
+/// 32-bit Float 多声道 WAV 写入器（WAVE_FORMAT_EXTENSIBLE）；数据超过 4 GB 时自动切换为 RF64（EBU Tech 3306）
+/// 32-bit float multichannel WAV writer (WAVE_FORMAT_EXTENSIBLE); switches to RF64 (EBU Tech 3306) when the data exceeds 4 GB
+///
+/// 头部预留 28 字节的 JUNK 块，需要时原地改写为 ds64，因此无需移动音频数据。
+/// A 28-byte JUNK chunk is reserved in the header and rewritten in place as ds64 when needed, so audio data never moves.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    channels: u16,
+    data_bytes: u64,
+    riff_limit: u64,
+}
+
+impl WavWriter {
+    /// `channel_mask` 为 `dwChannelMask`，0 表示全部未分配 / `channel_mask` is `dwChannelMask`; 0 leaves every channel unassigned
+    pub fn create(
+        path: &Path,
+        channels: u16,
+        sample_rate: u32,
+        channel_mask: u32,
+    ) -> io::Result<Self> {
+        Self::with_limit(path, channels, sample_rate, channel_mask, RIFF_LIMIT)
+    }
+
+    fn with_limit(
+        path: &Path,
+        channels: u16,
+        sample_rate: u32,
+        channel_mask: u32,
+        riff_limit: u64,
+    ) -> io::Result<Self> {
+        if channels == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "声道数必须大于 0/channel count must be > 0",
+            ));
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = channels * 4;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+
+        // ds64 占位 / ds64 placeholder
+        file.write_all(b"JUNK")?;
+        file.write_all(&DS64_SIZE.to_le_bytes())?;
+        file.write_all(&[0u8; DS64_SIZE as usize])?;
+
+        // fmt：0xFFFE = WAVE_FORMAT_EXTENSIBLE，子格式为 IEEE float
+        // fmt: 0xFFFE = WAVE_FORMAT_EXTENSIBLE with an IEEE float sub-format
+        file.write_all(b"fmt ")?;
+        file.write_all(&FMT_SIZE.to_le_bytes())?;
+        file.write_all(&0xFFFEu16.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * u32::from(block_align)).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&32u16.to_le_bytes())?;
+        file.write_all(&22u16.to_le_bytes())?; // cbSize
+        file.write_all(&32u16.to_le_bytes())?; // wValidBitsPerSample
+        file.write_all(&channel_mask.to_le_bytes())?;
+        file.write_all(&KSDATAFORMAT_SUBTYPE_IEEE_FLOAT)?;
+
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self {
+            file,
+            channels,
+            data_bytes: 0,
+            riff_limit,
+        })
+    }
+
+    pub fn write_sample(&mut self, sample: f32) -> io::Result<()> {
+        self.file.write_all(&sample.to_le_bytes())?;
+        self.data_bytes += 4;
+        Ok(())
+    }
+
+    /// 回填大小字段；超过 RIFF 上限时改写为 RF64 / Back-fill size fields; rewrite as RF64 beyond the RIFF limit
+    pub fn finalize(self) -> io::Result<()> {
+        let mut file = self
+            .file
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        // RIFF 大小 = 文件长度 - 8 / RIFF size = file length - 8
+        let riff_size = DATA_OFFSET + self.data_bytes;
+        let frames = self.data_bytes / (u64::from(self.channels) * 4);
+
+        if riff_size > self.riff_limit {
+            write_ds64(&mut file, b"RF64", riff_size, self.data_bytes, frames)?;
+        } else {
+            file.seek(SeekFrom::Start(4))?;
+            file.write_all(&(riff_size as u32).to_le_bytes())?;
+            file.seek(SeekFrom::Start(DATA_OFFSET + 4))?;
+            file.write_all(&(self.data_bytes as u32).to_le_bytes())?;
+        }
+        file.flush()
+    }
+}
+
+/// 将 RIFF 头改写为 RF64/BW64：32 位大小置为 -1，JUNK 占位改为 ds64
+/// Rewrite the RIFF header as RF64/BW64: 32-bit sizes become -1 and the JUNK placeholder becomes ds64
+fn write_ds64(
+    file: &mut File,
+    magic: &[u8; 4],
+    riff_size: u64,
+    data_size: u64,
+    frames: u64,
+) -> io::Result<()> {
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(magic)?;
+    file.write_all(&u32::MAX.to_le_bytes())?;
+
+    file.seek(SeekFrom::Start(DS64_OFFSET))?;
+    file.write_all(b"ds64")?;
+    file.write_all(&DS64_SIZE.to_le_bytes())?;
+    file.write_all(&riff_size.to_le_bytes())?;
+    file.write_all(&data_size.to_le_bytes())?;
+    file.write_all(&frames.to_le_bytes())?;
+    file.write_all(&0u32.to_le_bytes())?; // table length / 表长度
+
+    file.seek(SeekFrom::Start(DATA_OFFSET + 4))?;
+    file.write_all(&u32::MAX.to_le_bytes())?;
+    Ok(())
+}
+
+/// 在文件末尾追加一个块并更新 RIFF/RF64 大小 / Append a chunk at the end of the file and update the RIFF/RF64 size
+///
+/// 若 RIFF 文件因此超过 4 GB，则借助 JUNK 占位切换为 RF64。
+/// If a RIFF file grows past 4 GB as a result, it is switched to RF64 through the JUNK placeholder.
+pub fn append_chunk(path: &Path, id: &[u8; 4], payload: &[u8]) -> io::Result<()> {
+    append_chunk_with_limit(path, id, payload, RIFF_LIMIT)
+}
+
+fn append_chunk_with_limit(
+    path: &Path,
+    id: &[u8; 4],
+    payload: &[u8],
+    riff_limit: u64,
+) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+    let magic = [header[0], header[1], header[2], header[3]];
+    let riff_size = match &magic {
+        b"RIFF" => u64::from(u32::from_le_bytes([
+            header[4], header[5], header[6], header[7],
+        ])),
+        b"RF64" | b"BW64" => read_u64_at(&mut file, DS64_OFFSET + 8)?,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "不是 RIFF/RF64 文件/not a RIFF/RF64 file",
+            ))
+        }
+    };
+
+    let padded_len = payload.len() as u64 + payload.len() as u64 % 2;
+    let payload_len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "块过大/chunk too large"))?;
+    let new_size = riff_size + 8 + padded_len;
+    let promote = &magic == b"RIFF" && new_size > riff_limit;
+    // 先确认可以升级，避免写入后才失败 / Check promotion is possible before writing anything
+    if promote && !has_ds64_placeholder(&mut file)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "缺少 ds64 占位，无法超过 4 GB/no ds64 placeholder, cannot exceed 4 GB",
+        ));
+    }
+
+    file.seek(SeekFrom::End(0))?;
+    file.write_all(id)?;
+    file.write_all(&payload_len.to_le_bytes())?;
+    file.write_all(payload)?;
+    if payload.len() % 2 == 1 {
+        file.write_all(&[0u8])?;
+    }
+
+    if &magic != b"RIFF" {
+        file.seek(SeekFrom::Start(DS64_OFFSET + 8))?;
+        file.write_all(&new_size.to_le_bytes())?;
+    } else if promote {
+        promote_to_rf64(&mut file, new_size)?;
+    } else {
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&(new_size as u32).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// 将已有 RIFF 文件原地升级为 RF64（需要 JUNK 占位在固定位置）
+/// Promote an existing RIFF file to RF64 in place (requires the JUNK placeholder at its fixed position)
+fn promote_to_rf64(file: &mut File, riff_size: u64) -> io::Result<()> {
+    promote(file, b"RF64", riff_size)
+}
+
+fn promote(file: &mut File, magic: &[u8; 4], riff_size: u64) -> io::Result<()> {
+    let block_align = u64::from(read_u16_at(file, FMT_OFFSET + 8 + 12)?);
+    let data_size = u64::from(read_u32_at(file, DATA_OFFSET + 4)?);
+    write_ds64(
+        file,
+        magic,
+        riff_size,
+        data_size,
+        data_size / block_align.max(1),
+    )
+}
+
+/// 将本写入器生成的文件改为 BW64（ITU-R BS.2088）；BW64 始终带 ds64
+/// Turn a file produced by this writer into BW64 (ITU-R BS.2088); BW64 always carries ds64
+pub fn convert_to_bw64(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let mut header = [0u8; 8];
+    file.read_exact(&mut header)?;
+    match &header[0..4] {
+        b"BW64" => Ok(()),
+        b"RF64" => {
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(b"BW64")
+        }
+        b"RIFF" if has_ds64_placeholder(&mut file)? => {
+            let riff_size = u64::from(u32::from_le_bytes([
+                header[4], header[5], header[6], header[7],
+            ]));
+            promote(&mut file, b"BW64", riff_size)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "缺少 ds64 占位，无法转换为 BW64/no ds64 placeholder, cannot convert to BW64",
+        )),
+    }
+}
+
+/// 读取本写入器生成文件的声道数与采样率 / Read the channel count and sample rate of a file produced by this writer
+pub fn read_format(path: &Path) -> io::Result<(u16, u32)> {
+    let mut file = File::open(path)?;
+    let mut fmt = [0u8; 16];
+    file.seek(SeekFrom::Start(FMT_OFFSET))?;
+    file.read_exact(&mut fmt)?;
+    if &fmt[0..4] != b"fmt " {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "fmt 块位置不符/unexpected fmt chunk position",
+        ));
+    }
+    Ok((
+        u16::from_le_bytes([fmt[10], fmt[11]]),
+        u32::from_le_bytes([fmt[12], fmt[13], fmt[14], fmt[15]]),
+    ))
+}
+
+/// 检查固定位置是否有本写入器预留的 JUNK 占位 / Check for the JUNK placeholder this writer reserves at its fixed position
+fn has_ds64_placeholder(file: &mut File) -> io::Result<bool> {
+    let mut junk = [0u8; 8];
+    file.seek(SeekFrom::Start(DS64_OFFSET))?;
+    file.read_exact(&mut junk)?;
+    Ok(&junk[0..4] == b"JUNK"
+        && u32::from_le_bytes([junk[4], junk[5], junk[6], junk[7]]) == DS64_SIZE)
+}
+
+/// 流式 WAV 读取器，支持 RIFF、RF64 与 BW64（hound 无法读取后两者）
+/// Streaming WAV reader for RIFF, RF64 and BW64 (hound cannot read the latter two)
+pub struct WavReader {
+    file: BufReader<File>,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    /// 1 = PCM，3 = IEEE float（EXTENSIBLE 取子格式）/ 1 = PCM, 3 = IEEE float (EXTENSIBLE uses its sub-format)
+    format_tag: u16,
+    data_remaining: u64,
+}
+
+impl WavReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let mut file = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 12];
+        file.read_exact(&mut header)?;
+        if !matches!(&header[0..4], b"RIFF" | b"RF64" | b"BW64") || &header[8..12] != b"WAVE" {
+            return Err(invalid("不是 WAV 文件/not a WAV file"));
+        }
+
+        let mut ds64_data_size = None;
+        let mut format = None;
+        loop {
+            let mut chunk = [0u8; 8];
+            file.read_exact(&mut chunk)
+                .map_err(|_| invalid("缺少 data 块/missing data chunk"))?;
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            match &chunk[0..4] {
+                b"ds64" => {
+                    let mut body = vec![0u8; size as usize];
+                    file.read_exact(&mut body)?;
+                    if body.len() < 16 {
+                        return Err(invalid("ds64 块过短/ds64 chunk too short"));
+                    }
+                    ds64_data_size = Some(u64::from_le_bytes(body[8..16].try_into().unwrap()));
+                }
+                b"fmt " => {
+                    let mut body = vec![0u8; size as usize + size as usize % 2];
+                    file.read_exact(&mut body)?;
+                    if body.len() < 16 {
+                        return Err(invalid("fmt 块过短/fmt chunk too short"));
+                    }
+                    let mut tag = u16::from_le_bytes([body[0], body[1]]);
+                    if tag == 0xFFFE && body.len() >= 26 {
+                        tag = u16::from_le_bytes([body[24], body[25]]);
+                    }
+                    format = Some((
+                        tag,
+                        u16::from_le_bytes([body[2], body[3]]),
+                        u32::from_le_bytes(body[4..8].try_into().unwrap()),
+                        u16::from_le_bytes([body[14], body[15]]),
+                    ));
+                }
+                b"data" => {
+                    let (format_tag, channels, sample_rate, bits_per_sample) = format
+                        .ok_or_else(|| invalid("data 块前缺少 fmt/fmt missing before data"))?;
+                    if channels == 0 || bits_per_sample == 0 {
+                        return Err(invalid("无效的 fmt 块/invalid fmt chunk"));
+                    }
+                    let data_size = match (size, ds64_data_size) {
+                        (u32::MAX, Some(ds64)) => ds64,
+                        _ => u64::from(size),
+                    };
+                    return Ok(Self {
+                        file,
+                        channels,
+                        sample_rate,
+                        bits_per_sample,
+                        format_tag,
+                        data_remaining: data_size,
+                    });
+                }
+                _ => {
+                    let skip = i64::from(size) + i64::from(size % 2);
+                    file.seek_relative(skip)?;
+                }
+            }
+        }
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bits_per_sample
+    }
+
+    pub fn is_float(&self) -> bool {
+        self.format_tag == 3
+    }
+
+    /// 读取至多 `max_frames` 帧交错 32-bit float 样本到 `out`，返回帧数（0 表示结束）
+    /// Read up to `max_frames` interleaved 32-bit float frames into `out`; returns the frame count (0 at the end)
+    pub fn read_frames(&mut self, out: &mut Vec<f32>, max_frames: usize) -> io::Result<usize> {
+        if !self.is_float() || self.bits_per_sample != 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "只支持 32-bit float/only 32-bit float is supported",
+            ));
+        }
+        let block_align = usize::from(self.channels) * 4;
+        let frames = (self.data_remaining / block_align as u64).min(max_frames as u64) as usize;
+        let mut bytes = vec![0u8; frames * block_align];
+        self.file.read_exact(&mut bytes)?;
+        self.data_remaining -= bytes.len() as u64;
+
+        out.clear();
+        out.extend(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        );
+        Ok(frames)
+    }
+}
+
+fn read_u16_at(file: &mut File, offset: u64) -> io::Result<u16> {
+    let mut b = [0u8; 2];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+}
+
+fn read_u32_at(file: &mut File, offset: u64) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_u64_at(file: &mut File, offset: u64) -> io::Result<u64> {
+    let mut b = [0u8; 8];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    fn write_samples(path: &Path, channels: u16, samples: &[f32], limit: u64) {
+        let mut w = WavWriter::with_limit(path, channels, 48000, 0, limit).unwrap();
+        for s in samples {
+            w.write_sample(*s).unwrap();
+        }
+        w.finalize().unwrap();
+    }
+
+    // 未超过上限时输出普通 RIFF，hound 可读 / Below the limit the output is plain RIFF and hound reads it
+    #[test]
+    fn small_output_stays_riff() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        write_samples(&out, 2, &[0.5, -0.5, 0.25, -0.25], RIFF_LIMIT);
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(&bytes, 4) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[12..16], b"JUNK");
+
+        let reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let samples: Vec<f32> = reader.into_samples::<f32>().map(|s| s.unwrap()).collect();
+        assert_eq!(samples, [0.5, -0.5, 0.25, -0.25]);
+    }
+
+    // fmt 为 EXTENSIBLE：IEEE float 子格式与给定掩码 / fmt is EXTENSIBLE with the IEEE float sub-format and given mask
+    #[test]
+    fn fmt_chunk_is_extensible_with_mask() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let mut w = WavWriter::create(&out, 6, 48000, 0x60F).unwrap();
+        for _ in 0..6 {
+            w.write_sample(0.0).unwrap();
+        }
+        w.finalize().unwrap();
+
+        let bytes = std::fs::read(&out).unwrap();
+        let fmt = FMT_OFFSET as usize;
+        assert_eq!(&bytes[fmt..fmt + 4], b"fmt ");
+        assert_eq!(u32_at(&bytes, fmt + 4), 40);
+        assert_eq!(&bytes[fmt + 8..fmt + 10], &0xFFFEu16.to_le_bytes());
+        assert_eq!(u32_at(&bytes, fmt + 8 + 20), 0x60F);
+        assert_eq!(
+            &bytes[fmt + 8 + 24..fmt + 8 + 40],
+            &KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+        );
+
+        let reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.spec().channels, 6);
+        assert_eq!(reader.spec().sample_format, hound::SampleFormat::Float);
+    }
+
+    // 超过上限时改写为 RF64，ds64 中记录 64 位大小 / Beyond the limit: RF64 with 64-bit sizes in ds64
+    #[test]
+    fn large_output_switches_to_rf64() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let samples = [0.0f32; 30];
+        write_samples(&out, 3, &samples, 64);
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(&bytes[0..4], b"RF64");
+        assert_eq!(u32_at(&bytes, 4), u32::MAX);
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(u64_at(&bytes, 20) as usize, bytes.len() - 8);
+        assert_eq!(u64_at(&bytes, 28), 120);
+        assert_eq!(u64_at(&bytes, 36), 10);
+        assert_eq!(u32_at(&bytes, DATA_OFFSET as usize + 4), u32::MAX);
+    }
+
+    // RF64 上追加块时更新 ds64 中的 64 位 RIFF 大小 / Appending to RF64 updates the 64-bit size in ds64
+    #[test]
+    fn append_chunk_updates_ds64_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        write_samples(&out, 1, &[0.0; 20], 64);
+
+        append_chunk(&out, b"LIST", b"abc").unwrap();
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(u32_at(&bytes, 4), u32::MAX);
+        assert_eq!(u64_at(&bytes, 20) as usize, bytes.len() - 8);
+        assert_eq!(&bytes[bytes.len() - 12..bytes.len() - 8], b"LIST");
+        assert_eq!(bytes.len() % 2, 0);
+    }
+
+    // RIFF 因追加而越过上限时升级为 RF64 / A RIFF that crosses the limit through an append is promoted to RF64
+    #[test]
+    fn append_chunk_promotes_riff_past_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        write_samples(&out, 2, &[0.0; 4], RIFF_LIMIT);
+        let before = std::fs::metadata(&out).unwrap().len();
+
+        append_chunk_with_limit(&out, b"LIST", &[0u8; 32], before).unwrap();
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(&bytes[0..4], b"RF64");
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(u64_at(&bytes, 20) as usize, bytes.len() - 8);
+        assert_eq!(u64_at(&bytes, 28), 16);
+        assert_eq!(u64_at(&bytes, 36), 2);
+    }
+
+    // 转为 BW64 后 ds64 记录完整大小，追加的块继续更新 ds64
+    // After conversion to BW64 the ds64 holds full sizes and later appends keep updating it
+    #[test]
+    fn convert_to_bw64_writes_ds64_and_accepts_appends() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        write_samples(&out, 2, &[0.0; 8], RIFF_LIMIT);
+        assert_eq!(read_format(&out).unwrap(), (2, 48000));
+
+        convert_to_bw64(&out).unwrap();
+        append_chunk(&out, b"axml", b"<x/>").unwrap();
+
+        let bytes = std::fs::read(&out).unwrap();
+        assert_eq!(&bytes[0..4], b"BW64");
+        assert_eq!(u32_at(&bytes, 4), u32::MAX);
+        assert_eq!(&bytes[12..16], b"ds64");
+        assert_eq!(u64_at(&bytes, 20) as usize, bytes.len() - 8);
+        assert_eq!(u64_at(&bytes, 28), 32);
+        assert_eq!(u64_at(&bytes, 36), 4);
+        assert_eq!(u32_at(&bytes, DATA_OFFSET as usize + 4), u32::MAX);
+    }
+
+    // 读取器可读取 RF64/BW64 并返回全部帧 / The reader handles RF64/BW64 and returns every frame
+    #[test]
+    fn reader_reads_rf64_and_bw64() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let samples: Vec<f32> = (0..30).map(|i| i as f32 / 30.0).collect();
+        write_samples(&out, 3, &samples, 64);
+        append_chunk(&out, b"LIST", b"abc").unwrap();
+
+        for _ in 0..2 {
+            let mut reader = WavReader::open(&out).unwrap();
+            assert_eq!((reader.channels(), reader.sample_rate()), (3, 48000));
+            assert!(reader.is_float());
+
+            let mut all = Vec::new();
+            let mut buf = Vec::new();
+            while reader.read_frames(&mut buf, 4).unwrap() > 0 {
+                all.extend_from_slice(&buf);
+            }
+            assert_eq!(all, samples);
+            convert_to_bw64(&out).unwrap();
+        }
+    }
+
+    // 没有 JUNK 占位的 RIFF 无法升级，返回错误而不是写出损坏文件
+    // A RIFF without the JUNK placeholder cannot be promoted: error instead of a corrupt file
+    #[test]
+    fn append_chunk_without_placeholder_errors_past_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out.wav");
+        let spec = hound::WavSpec {
+            channels: 1,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut w = hound::WavWriter::create(&out, spec).unwrap();
+        w.write_sample(0.0f32).unwrap();
+        w.finalize().unwrap();
+
+        let before = std::fs::read(&out).unwrap();
+        assert!(append_chunk_with_limit(&out, b"LIST", &[0u8; 8], 16).is_err());
+        assert_eq!(std::fs::read(&out).unwrap(), before);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::channels::ChannelConfig;
+use crate::error::{DecodeError, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// WavPack 命令位置：优先 MCAT_WAVPACK，否则使用 PATH 中的 wavpack
+/// WavPack command location: MCAT_WAVPACK first, otherwise wavpack from PATH
+fn wavpack_program() -> PathBuf {
+    std::env::var_os("MCAT_WAVPACK").map_or_else(|| PathBuf::from("wavpack"), PathBuf::from)
+}
+
+/// 检查 wavpack 命令行工具是否可用 / Check if the wavpack CLI tool is available
+fn check_wavpack_command(program: &Path) -> Result<()> {
+    match Command::new(program).arg("--version").output() {
+        Ok(output) if output.status.success() => Ok(()),
+        _ => Err(DecodeError::WavPackConversionFailed(
+            "未找到 wavpack 命令行工具，请安装 WavPack / wavpack command not found, please install WavPack"
+                .to_string(),
+        )),
+    }
+}
+
+/// 批量转码 WAV 文件为 WavPack（带声道配置）/ Convert a merged WAV to WavPack with channel config
+///
+/// WavPack 原生支持 32-bit Float 与最多 4096 个声道，因此 9.1.6 等布局可无损保存；
+/// 声道位置取自 WAV 头的 dwChannelMask，完整布局另写入 APEv2 标签。
+/// WavPack natively stores 32-bit float and up to 4096 channels, so layouts such as 9.1.6 stay lossless;
+/// speaker positions come from the WAV header's dwChannelMask and the full layout is also written as an APEv2 tag.
+pub fn convert_batch(
+    wav_path: &Path,
+    wv_path: &Path,
+    channel_config: Option<&ChannelConfig>,
+) -> Result<()> {
+    log::info!(
+        "开始转码为 WavPack / Starting WavPack conversion: {} → {}",
+        wav_path.display(),
+        wv_path.display()
+    );
+
+    wav_to_wavpack(&wavpack_program(), wav_path, wv_path, channel_config)?;
+
+    log::info!(
+        "WavPack 转码完成 / WavPack conversion completed: {}",
+        wv_path.display()
+    );
+    Ok(())
+}
+
+fn wav_to_wavpack(
+    program: &Path,
+    wav_path: &Path,
+    wv_path: &Path,
+    channel_config: Option<&ChannelConfig>,
+) -> Result<()> {
+    check_wavpack_command(program)?;
+
+    let mut cmd = Command::new(program);
+    cmd.arg("-hh") // 最高压缩 / Highest compression
+        .arg("-m") // 存储 MD5 / Store MD5 sum
+        .arg("-q") // 静默输出 / Quiet output
+        .arg("-y"); // 覆盖输出 / Overwrite output
+
+    if let Some(config) = channel_config {
+        if config.name != "auto" && !config.names.is_empty() {
+            let layout_str = config.names.join(" ");
+            cmd.arg("-w")
+                .arg(format!("CHANNEL_LAYOUT={layout_str} (Sourced from Dolby)"));
+        }
+    }
+
+    cmd.arg("-w")
+        .arg("Comment=Converted by MacinConvert-Atmos-Tool")
+        .arg(wav_path)
+        .arg("-o")
+        .arg(wv_path);
+
+    let output = cmd.output().map_err(|e| {
+        DecodeError::WavPackConversionFailed(format!(
+            "执行 wavpack 命令失败 / Failed to execute wavpack command: {e}"
+        ))
+    })?;
+
+    if !output.status.success() {
+        let _ = std::fs::remove_file(wv_path);
+        return Err(DecodeError::WavPackConversionFailed(format!(
+            "WavPack 编码失败/WavPack encoding failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::decoder::test_scripts::write_script;
+
+    // 伪造 wavpack：记录参数，按 exit_code 退出 / Fake wavpack: records its arguments and exits with exit_code
+    fn fake_wavpack(dir: &Path, exit_code: i32) -> (PathBuf, PathBuf) {
+        let log = dir.join("args.log");
+        let script = dir.join("wavpack");
+        write_script(
+            &script,
+            &format!(
+                "[ \"$1\" = --version ] && exit 0\nprintf '%s\\n' \"$@\" > '{}'\nexit {exit_code}",
+                log.display()
+            ),
+        );
+        (script, log)
+    }
+
+    // 16 声道布局也直接交给 wavpack，并带上声道布局标签 / 16-channel layouts go straight to wavpack with the layout tag
+    #[test]
+    fn passes_layout_tag_and_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let (program, log) = fake_wavpack(dir.path(), 0);
+        let wav = dir.path().join("in.wav");
+        let wv = dir.path().join("in.wv");
+        let config = crate::channels::get_config("9.1.6").unwrap();
+
+        wav_to_wavpack(&program, &wav, &wv, Some(&config)).unwrap();
+
+        let args = std::fs::read_to_string(log).unwrap();
+        let args: Vec<&str> = args.lines().collect();
+        assert!(args.contains(&"-hh"));
+        let layout = format!(
+            "CHANNEL_LAYOUT={} (Sourced from Dolby)",
+            config.names.join(" ")
+        );
+        assert!(args.contains(&layout.as_str()));
+        assert_eq!(
+            &args[args.len() - 3..],
+            [wav.to_str().unwrap(), "-o", wv.to_str().unwrap()]
+        );
+    }
+
+    // wavpack 失败时返回错误并删除不完整的输出 / On wavpack failure: error and the partial output is removed
+    #[test]
+    fn failure_removes_partial_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let (program, _) = fake_wavpack(dir.path(), 1);
+        let wav = dir.path().join("in.wav");
+        let wv = dir.path().join("in.wv");
+        std::fs::write(&wv, b"partial").unwrap();
+
+        assert!(wav_to_wavpack(&program, &wav, &wv, None).is_err());
+        assert!(!wv.exists());
+    }
+
+    // 找不到命令时报错 / Missing command errors
+    #[test]
+    fn missing_command_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("no-such-wavpack");
+        assert!(check_wavpack_command(&missing).is_err());
+    }
+}